@@ -1,10 +1,12 @@
+use std::collections::HashSet;
 use std::io::{Cursor, Result, Write};
-use std::path::Path;
-
-use zip::write::FileOptions;
+use std::path::{Path, PathBuf};
 
 use crate::spec::TargetAmalgamation;
-use crate::{create_targz, GeneratedAsset, GeneratedAssetKind, PlatformFile, Project};
+use crate::{
+    build_config, create_targz, zip_file_options, GeneratedAsset, GeneratedAssetKind, PlatformFile,
+    Project,
+};
 
 pub(crate) fn write_amalgamation(
     project: &Project,
@@ -24,10 +26,22 @@ pub(crate) fn write_amalgamation(
             })
         })
         .collect();
-    let files = files?;
+    let mut files = files?;
+    if amalgamation_config.merge {
+        files = merge(
+            &project.spec_directory,
+            &project.spec.package.name,
+            &project.version,
+            &amalgamation_config.include,
+        )?;
+    }
+    files.extend(build_config::amalgamation_files(project, &files));
     let mut assets = vec![];
 
-    let targz = create_targz(files.iter().collect::<Vec<&PlatformFile>>().as_ref())?;
+    let targz = create_targz(
+        files.iter().collect::<Vec<&PlatformFile>>().as_ref(),
+        project.source_date_epoch,
+    )?;
     assets.push(GeneratedAsset::from(
         GeneratedAssetKind::Amalgamation,
         &amalgamation_dir.join(format!(
@@ -40,7 +54,8 @@ pub(crate) fn write_amalgamation(
     let buffer = Cursor::new(Vec::new());
     let mut zipfile = zip::ZipWriter::new(buffer);
     for file in files {
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let options = zip_file_options(project.source_date_epoch)
+            .compression_method(zip::CompressionMethod::Deflated);
         zipfile.start_file(file.name, options)?;
         zipfile.write_all(&file.data)?;
     }
@@ -55,3 +70,229 @@ pub(crate) fn write_amalgamation(
 
     Ok(assets)
 }
+
+// these come from whichever SQLite the extension is built against
+const SYSTEM_HEADERS: [&str; 2] = ["sqlite3.h", "sqlite3ext.h"];
+
+/// The `#include "path"` target of a line, if it has one.
+fn local_include(line: &str) -> Option<&str> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let path = directive.strip_prefix("include")?.trim_start();
+    let path = path.strip_prefix('"')?;
+    Some(&path[..path.find('"')?])
+}
+
+/// Appends a source file to an amalgamation, inlining the local files it
+/// includes the first time they're included, with #line directives pointing
+/// back at the original files.
+struct Merger<'a> {
+    spec_directory: &'a Path,
+    output: String,
+    inlined: HashSet<PathBuf>,
+}
+
+impl Merger<'_> {
+    fn append(&mut self, relative_path: &Path) -> Result<()> {
+        let path = self.spec_directory.join(relative_path);
+        if !self.inlined.insert(path.canonicalize()?) {
+            return Ok(());
+        }
+        let display_path = relative_path.to_string_lossy().replace('\\', "/");
+        let source = std::fs::read_to_string(&path)?;
+        self.output.push_str(&format!(
+            "/************** Begin file {display_path} **************/\n#line 1 \"{display_path}\"\n"
+        ));
+        for (index, line) in source.lines().enumerate() {
+            let included = local_include(line)
+                .filter(|include| !SYSTEM_HEADERS.contains(include))
+                .map(|include| {
+                    relative_path
+                        .parent()
+                        .unwrap_or(Path::new(""))
+                        .join(include)
+                })
+                .filter(|include| self.spec_directory.join(include).is_file());
+            let Some(included) = included else {
+                self.output.push_str(line);
+                self.output.push('\n');
+                continue;
+            };
+            if self
+                .inlined
+                .contains(&self.spec_directory.join(&included).canonicalize()?)
+            {
+                self.output
+                    .push_str(&format!("/* {} -- already inlined */\n", line.trim()));
+                continue;
+            }
+            self.output.push_str(&format!("/* {} */\n", line.trim()));
+            self.append(&included)?;
+            self.output.push_str(&format!(
+                "/************** Continuing where we left off in {display_path} **************/\n#line {} \"{display_path}\"\n",
+                index + 2
+            ));
+        }
+        self.output.push_str(&format!(
+            "/************** End of {display_path} **************/\n"
+        ));
+        Ok(())
+    }
+}
+
+/// Merges the .c files into {name}.c and the headers into {name}.h, like
+/// SQLite's own sqlite3.c and sqlite3.h. The header also defines
+/// {NAME}_VERSION, like SQLITE_VERSION, unless the sources already do.
+fn merge(
+    spec_directory: &Path,
+    name: &str,
+    version: &semver::Version,
+    include: &[String],
+) -> Result<Vec<PlatformFile>> {
+    let macro_prefix = name.to_ascii_uppercase().replace(['-', '.'], "_");
+    let guard = format!("{macro_prefix}_AMALGAMATION_H");
+    let mut files = vec![];
+    for (extension, prefix, suffix) in [
+        ("c", String::new(), String::new()),
+        (
+            "h",
+            format!("#ifndef {guard}\n#define {guard}\n"),
+            format!(
+                "#ifndef {macro_prefix}_VERSION\n#define {macro_prefix}_VERSION \"{version}\"\n#endif\n#endif\n"
+            ),
+        ),
+    ] {
+        let sources: Vec<&String> = include
+            .iter()
+            .filter(|path| path.ends_with(&format!(".{extension}")))
+            .collect();
+        if sources.is_empty() {
+            continue;
+        }
+        let mut merger = Merger {
+            spec_directory,
+            output: String::new(),
+            inlined: HashSet::new(),
+        };
+        for source in sources {
+            merger.append(Path::new(source))?;
+        }
+        let data = format!(
+            "/*\n** This file is an amalgamation of the {name} {version} sources,\n** generated by sqlite-dist.\n*/\n{prefix}{output}{suffix}",
+            output = merger.output,
+        );
+        files.push(PlatformFile::new(format!("{name}.{extension}"), data, None));
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn spec_directory(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new("sqlite-dist-amalgamation").unwrap();
+        for (path, contents) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn merged(dir: &TempDir, sources: &[&str]) -> String {
+        let mut merger = Merger {
+            spec_directory: dir.path(),
+            output: String::new(),
+            inlined: HashSet::new(),
+        };
+        for source in sources {
+            merger.append(Path::new(source)).unwrap();
+        }
+        merger.output
+    }
+
+    fn sample_spec_directory() -> TempDir {
+        spec_directory(&[
+            ("shared.h", "int shared(void);\n"),
+            (
+                "a.c",
+                "#include \"sqlite3ext.h\"\n#include \"shared.h\"\nint a(void) { return shared(); }\n",
+            ),
+            (
+                "b.c",
+                "#include \"shared.h\"\nint b(void) { return shared(); }\n",
+            ),
+        ])
+    }
+
+    #[test]
+    fn local_include_only_matches_quoted_includes() {
+        assert_eq!(local_include("#include \"a.h\""), Some("a.h"));
+        assert_eq!(
+            local_include("  #  include \"sub/b.h\" // b"),
+            Some("sub/b.h")
+        );
+        assert_eq!(local_include("#include <stdio.h>"), None);
+        assert_eq!(local_include("int include = 1;"), None);
+    }
+
+    #[test]
+    fn inlines_each_include_once() {
+        let dir = sample_spec_directory();
+        let output = merged(&dir, &["a.c", "b.c"]);
+        assert_eq!(output.matches("int shared(void);").count(), 1);
+        assert!(output.contains("/* #include \"shared.h\" -- already inlined */\n"));
+        // sqlite3ext.h comes from the SQLite the extension is built against
+        assert!(output.contains("#include \"sqlite3ext.h\"\n"));
+    }
+
+    #[test]
+    fn line_directives_point_at_the_original_files() {
+        let dir = sample_spec_directory();
+        let output = merged(&dir, &["a.c"]);
+        let lines: Vec<&str> = output.lines().collect();
+        let shared = lines
+            .iter()
+            .position(|l| *l == "int shared(void);")
+            .unwrap();
+        assert_eq!(lines[shared - 1], "#line 1 \"shared.h\"");
+        // shared.h is included on line 2 of a.c, so a.c continues on line 3
+        let a = lines
+            .iter()
+            .position(|l| *l == "int a(void) { return shared(); }")
+            .unwrap();
+        assert_eq!(lines[a - 1], "#line 3 \"a.c\"");
+        assert_eq!(lines[0], "/************** Begin file a.c **************/");
+        assert_eq!(lines[1], "#line 1 \"a.c\"");
+    }
+
+    #[test]
+    fn header_has_an_include_guard_and_version() {
+        let dir = sample_spec_directory();
+        let include = ["a.c".to_owned(), "shared.h".to_owned()];
+        let version = semver::Version::parse("1.2.3-alpha.1").unwrap();
+        let files = merge(dir.path(), "sqlite-sample", &version, &include).unwrap();
+        let names: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["sqlite-sample.c", "sqlite-sample.h"]);
+
+        let header = std::str::from_utf8(&files[1].data).unwrap();
+        let code: Vec<&str> = header
+            .lines()
+            .filter(|line| line.starts_with('#') && !line.starts_with("#line"))
+            .collect();
+        assert_eq!(
+            code,
+            [
+                "#ifndef SQLITE_SAMPLE_AMALGAMATION_H",
+                "#define SQLITE_SAMPLE_AMALGAMATION_H",
+                "#ifndef SQLITE_SAMPLE_VERSION",
+                "#define SQLITE_SAMPLE_VERSION \"1.2.3-alpha.1\"",
+                "#endif",
+                "#endif",
+            ]
+        );
+        let source = std::str::from_utf8(&files[0].data).unwrap();
+        assert!(!source.contains("_AMALGAMATION_H"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+use zip::result::ZipError;
+
+use crate::jvm_loader;
+use crate::maven::{self, create_jar};
+use crate::spec::{camel_case, TargetAndroid};
+use crate::{
+    binary, create_zip, warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os,
+    PlatformFile, Project,
+};
+
+#[derive(Error, Debug)]
+pub enum AndroidError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Zipfile error: {0}")]
+    ZipError(#[from] ZipError),
+    #[error("no android platform directory has a loadable library")]
+    NoLibraries,
+}
+
+fn android_abi(cpu: &Cpu) -> Option<&'static str> {
+    match cpu {
+        Cpu::Aarch64 => Some("arm64-v8a"),
+        Cpu::Armv7a => Some("armeabi-v7a"),
+        Cpu::X86_64 => Some("x86_64"),
+        Cpu::I686 => Some("x86"),
+        _ => None,
+    }
+}
+
+// Android only extracts native libraries named lib*.so from an APK
+fn jni_file_name(name: &str) -> String {
+    if name.starts_with("lib") {
+        name.to_owned()
+    } else {
+        format!("lib{name}")
+    }
+}
+
+/// Writes an .aar with every android-* loadable under jni/<abi>/ and a loader
+/// class, and a Maven POM for it.
+pub(crate) fn write_android(
+    project: &Project,
+    android_path: &Path,
+    android_config: &TargetAndroid,
+) -> Result<Vec<GeneratedAsset>, AndroidError> {
+    let mut jni_files = vec![];
+    for platform_dir in &project.platform_directories {
+        if platform_dir.os != Os::Android {
+            continue;
+        }
+        let Some(abi) = android_abi(&platform_dir.cpu) else {
+            warn_unsupported_platform("android", platform_dir);
+            continue;
+        };
+        for loadable in &platform_dir.loadable_files {
+            jni_files.push((abi, loadable));
+        }
+    }
+    let Some((_, first)) = jni_files.first() else {
+        return Err(AndroidError::NoLibraries);
+    };
+
+    let package_name = &project.spec.package.name;
+    let java_package = android_config.java_package(package_name);
+    let class_name = camel_case(package_name);
+    let binary_class_name = format!("{}/{class_name}", java_package.replace('.', "/"));
+    let library_name = jni_file_name(&first.file.name)
+        .strip_prefix("lib")
+        .and_then(|name| name.strip_suffix(".so"))
+        .unwrap_or(&first.file_stem)
+        .to_owned();
+    let entrypoint = project
+        .spec
+        .package
+        .entrypoint
+        .clone()
+        .unwrap_or_else(|| binary::init_function(&first.file.name, &first.init_symbols));
+
+    let classes_jar = create_jar(
+        &[PlatformFile::new(
+            format!("{binary_class_name}.class"),
+            jvm_loader::aar_loader(&binary_class_name, &library_name, &entrypoint),
+            None,
+        )],
+        project.source_date_epoch,
+    )?;
+    let mut aar_files = vec![
+        PlatformFile::new(
+            "AndroidManifest.xml".to_owned(),
+            templates::android_manifest_xml(&java_package, android_config.min_sdk_version()),
+            None,
+        ),
+        PlatformFile::new("classes.jar".to_owned(), classes_jar, None),
+        PlatformFile::new("R.txt".to_owned(), "", None),
+    ];
+    for (abi, loadable) in &jni_files {
+        aar_files.push(PlatformFile::new(
+            format!("jni/{abi}/{}", jni_file_name(&loadable.file.name)),
+            loadable.file.data.clone(),
+            None,
+        ));
+    }
+
+    let base_name = format!("{package_name}-{}", project.version);
+    Ok(vec![
+        GeneratedAsset::from(
+            GeneratedAssetKind::Android,
+            &android_path.join(format!("{base_name}.aar")),
+            &create_zip(&aar_files, project.source_date_epoch)?,
+        )?,
+        GeneratedAsset::from(
+            GeneratedAssetKind::Android,
+            &android_path.join(format!("{base_name}.pom")),
+            maven::templates::pom_xml(project, &android_config.group_id, "aar").as_bytes(),
+        )?,
+    ])
+}
+
+mod templates {
+    pub(crate) fn android_manifest_xml(java_package: &str, min_sdk_version: u32) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="{java_package}">
+    <uses-sdk android:minSdkVersion="{min_sdk_version}" />
+</manifest>
+"#
+        )
+    }
+}
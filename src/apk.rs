@@ -0,0 +1,481 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use base64::Engine;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tar::{EntryType, Header};
+use thiserror::Error;
+
+use crate::spec::TargetApk;
+use crate::{warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
+
+#[derive(Error, Debug)]
+pub enum ApkError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("{0} is not a PEM-encoded RSA private key")]
+    InvalidKey(String),
+    #[error("signing failed: {0}")]
+    SignatureError(#[from] rsa::Error),
+    #[error("public key encoding failed: {0}")]
+    PublicKeyError(#[from] rsa::pkcs8::spki::Error),
+    #[error("version {0} has no Alpine equivalent, use a pre-release like alpha.1, beta.2, pre.3 or rc.4")]
+    UnsupportedVersion(String),
+}
+
+fn alpine_arch(cpu: &Cpu) -> Option<&'static str> {
+    match cpu {
+        Cpu::X86_64 => Some("x86_64"),
+        Cpu::Aarch64 => Some("aarch64"),
+        _ => None,
+    }
+}
+
+/// Alpine versions only allow a few pre-release suffixes with an optional
+/// number, like 1.0.0_alpha1, and always end in a package release, here -r0.
+/// Pre-releases like alpha, alpha1 or alpha.1 map onto them, others can't be
+/// written so that they sort the same way and are rejected.
+fn alpine_version(version: &semver::Version) -> Result<String, ApkError> {
+    let mut alpine = format!("{}.{}.{}", version.major, version.minor, version.patch);
+    if !version.pre.is_empty() {
+        let unsupported = || ApkError::UnsupportedVersion(version.to_string());
+        let pre = version.pre.as_str();
+        let (suffix, number) = ["alpha", "beta", "pre", "rc"]
+            .into_iter()
+            .find_map(|suffix| Some((suffix, pre.strip_prefix(suffix)?)))
+            .ok_or_else(unsupported)?;
+        let number = number.strip_prefix('.').unwrap_or(number);
+        if !number.chars().all(|c| c.is_ascii_digit()) {
+            return Err(unsupported());
+        }
+        alpine.push_str(&format!("_{suffix}{number}"));
+    }
+    alpine.push_str("-r0");
+    Ok(alpine)
+}
+
+/// An entry in one of the gzip streams an .apk is made of.
+struct TarEntry<'a> {
+    path: String,
+    mode: u32,
+    data: &'a [u8],
+}
+
+/// A gzipped tar stream. Streams that are followed by another one in the same
+/// file are `cut`, without the end of archive blocks, so they read as one tar.
+fn tar_segment(entries: &[TarEntry], mtime: u64, cut: bool) -> io::Result<Vec<u8>> {
+    let mut tar = tar::Builder::new(vec![]);
+    for entry in entries {
+        let is_dir = entry.path.ends_with('/');
+        // apk checks every file against the sha1 in its PAX header
+        if !is_dir && !entry.path.starts_with('.') {
+            let record = format!(
+                "APK-TOOLS.checksum.SHA1={}\n",
+                base16ct::lower::encode_string(&Sha1::digest(entry.data))
+            );
+            // the length prefix counts its own digits
+            let mut length = record.len() + 2;
+            while format!("{length} {record}").len() != length {
+                length += 1;
+            }
+            let record = format!("{length} {record}");
+            let mut header = Header::new_ustar();
+            header.set_entry_type(EntryType::XHeader);
+            header.set_mtime(mtime);
+            header.set_mode(0o644);
+            header.set_size(record.len() as u64);
+            tar.append_data(&mut header, "PaxHeader", record.as_bytes())?;
+        }
+        let mut header = Header::new_ustar();
+        header.set_entry_type(if is_dir {
+            EntryType::Directory
+        } else {
+            EntryType::Regular
+        });
+        header.set_mtime(mtime);
+        header.set_mode(entry.mode);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_size(entry.data.len() as u64);
+        tar.append_data(&mut header, &entry.path, entry.data)?;
+    }
+    let mut tar = tar.into_inner()?;
+    if cut {
+        tar.truncate(tar.len() - 1024);
+    }
+    let mut gz = GzEncoder::new(vec![], Compression::default());
+    gz.write_all(&tar)?;
+    gz.finish()
+}
+
+/// Signs the stream that follows, like abuild-sign.
+struct Signer {
+    key: RsaPrivateKey,
+    key_name: String,
+}
+
+impl Signer {
+    fn load(path: &Path, key_name: Option<&str>) -> Result<Self, ApkError> {
+        let pem = fs::read_to_string(path)?;
+        let key = RsaPrivateKey::from_pkcs8_pem(&pem)
+            .ok()
+            .or_else(|| RsaPrivateKey::from_pkcs1_pem(&pem).ok())
+            .ok_or_else(|| ApkError::InvalidKey(path.display().to_string()))?;
+        // abuild names the public key after the private one, ex me-1234.rsa.pub
+        let key_name = key_name
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("{}.pub", path.file_name().unwrap().to_string_lossy()));
+        Ok(Self { key, key_name })
+    }
+
+    fn signature_segment(&self, signed: &[u8], mtime: u64) -> Result<Vec<u8>, ApkError> {
+        let signature = self
+            .key
+            .sign(Pkcs1v15Sign::new::<Sha1>(), &Sha1::digest(signed))?;
+        Ok(tar_segment(
+            &[TarEntry {
+                path: format!(".SIGN.RSA.{}", self.key_name),
+                mode: 0o644,
+                data: &signature,
+            }],
+            mtime,
+            true,
+        )?)
+    }
+}
+
+/// Writes an .apk per linux musl platform directory with the loadable in
+/// /usr/lib/sqlite3/, and an APKINDEX.tar.gz next to them, so apk/<arch>/ can
+/// be used as a repository. With a private key, the packages and index are
+/// signed and the public key is written to apk/ for /etc/apk/keys/.
+pub(crate) fn write_apk(
+    project: &Project,
+    apk_path: &Path,
+    apk_config: &TargetApk,
+) -> Result<Vec<GeneratedAsset>, ApkError> {
+    let signer = apk_config
+        .private_key
+        .as_ref()
+        .map(|key| {
+            Signer::load(
+                &project.spec_directory.join(key),
+                apk_config.key_name.as_deref(),
+            )
+        })
+        .transpose()?;
+    let package = &project.spec.package;
+    let version = alpine_version(&project.version)?;
+    let builddate = project.source_date_epoch.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    });
+
+    let mut assets = vec![];
+    for platform_dir in &project.platform_directories {
+        let arch = match (&platform_dir.os, alpine_arch(&platform_dir.cpu)) {
+            (Os::LinuxMusl, Some(arch)) => arch,
+            (Os::LinuxMusl, None) => {
+                warn_unsupported_platform("apk", platform_dir);
+                continue;
+            }
+            _ => continue,
+        };
+        if platform_dir.loadable_files.is_empty() {
+            continue;
+        }
+
+        let mut data_entries = vec![
+            TarEntry {
+                path: "usr/".to_owned(),
+                mode: 0o755,
+                data: &[],
+            },
+            TarEntry {
+                path: "usr/lib/".to_owned(),
+                mode: 0o755,
+                data: &[],
+            },
+            TarEntry {
+                path: "usr/lib/sqlite3/".to_owned(),
+                mode: 0o755,
+                data: &[],
+            },
+        ];
+        data_entries.extend(platform_dir.loadable_files.iter().map(|loadable| TarEntry {
+            path: format!("usr/lib/sqlite3/{}", loadable.file.name),
+            mode: 0o755,
+            data: &loadable.file.data,
+        }));
+        let installed_size: usize = data_entries.iter().map(|entry| entry.data.len()).sum();
+        let data = tar_segment(&data_entries, builddate, false)?;
+
+        let pkginfo = templates::pkginfo(
+            project,
+            &version,
+            arch,
+            builddate,
+            installed_size,
+            &base16ct::lower::encode_string(&Sha256::digest(&data)),
+        );
+        let control = tar_segment(
+            &[TarEntry {
+                path: ".PKGINFO".to_owned(),
+                mode: 0o644,
+                data: pkginfo.as_bytes(),
+            }],
+            builddate,
+            true,
+        )?;
+
+        let mut apk = match &signer {
+            Some(signer) => signer.signature_segment(&control, builddate)?,
+            None => vec![],
+        };
+        apk.extend(&control);
+        apk.extend(&data);
+
+        let arch_path = apk_path.join(arch);
+        fs::create_dir_all(&arch_path)?;
+        assets.push(GeneratedAsset::from(
+            GeneratedAssetKind::Apk,
+            &arch_path.join(format!("{}-{version}.apk", package.name)),
+            &apk,
+        )?);
+
+        // packages are identified by the sha1 of their control stream
+        let checksum = format!(
+            "Q1{}",
+            base64::engine::general_purpose::STANDARD.encode(Sha1::digest(&control))
+        );
+        let apkindex = templates::apkindex(
+            project,
+            &version,
+            arch,
+            builddate,
+            installed_size,
+            apk.len(),
+            &checksum,
+        );
+        let description = format!("{} {}", package.name, project.version);
+        let index_data = tar_segment(
+            &[
+                TarEntry {
+                    path: "DESCRIPTION".to_owned(),
+                    mode: 0o644,
+                    data: description.as_bytes(),
+                },
+                TarEntry {
+                    path: "APKINDEX".to_owned(),
+                    mode: 0o644,
+                    data: apkindex.as_bytes(),
+                },
+            ],
+            builddate,
+            false,
+        )?;
+        let mut index = match &signer {
+            Some(signer) => signer.signature_segment(&index_data, builddate)?,
+            None => vec![],
+        };
+        index.extend(index_data);
+        assets.push(GeneratedAsset::from(
+            GeneratedAssetKind::Apk,
+            &arch_path.join("APKINDEX.tar.gz"),
+            &index,
+        )?);
+    }
+
+    if let Some(signer) = &signer {
+        let public_key = signer
+            .key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)?;
+        assets.push(GeneratedAsset::from(
+            GeneratedAssetKind::Apk,
+            &apk_path.join(&signer.key_name),
+            public_key.as_bytes(),
+        )?);
+    }
+    if assets.is_empty() {
+        println!("Warning: no linux musl platform directory found, skipping the apk target");
+    }
+    Ok(assets)
+}
+
+mod templates {
+    use crate::Project;
+
+    fn one_line(value: &str) -> String {
+        value.trim().replace(['\r', '\n'], " ")
+    }
+
+    pub(crate) fn pkginfo(
+        project: &Project,
+        version: &str,
+        arch: &str,
+        builddate: u64,
+        installed_size: usize,
+        datahash: &str,
+    ) -> String {
+        let package = &project.spec.package;
+        format!(
+            "# Generated by sqlite-dist
+pkgname = {name}
+pkgver = {version}
+pkgdesc = {description}
+url = {homepage}
+builddate = {builddate}
+size = {installed_size}
+arch = {arch}
+origin = {name}
+license = {license}
+datahash = {datahash}
+",
+            name = package.name,
+            description = one_line(&package.description),
+            homepage = package.homepage,
+            license = package.license,
+        )
+    }
+
+    pub(crate) fn apkindex(
+        project: &Project,
+        version: &str,
+        arch: &str,
+        builddate: u64,
+        installed_size: usize,
+        size: usize,
+        checksum: &str,
+    ) -> String {
+        let package = &project.spec.package;
+        format!(
+            "C:{checksum}
+P:{name}
+V:{version}
+A:{arch}
+S:{size}
+I:{installed_size}
+T:{description}
+U:{homepage}
+L:{license}
+o:{name}
+t:{builddate}
+
+",
+            name = package.name,
+            description = one_line(&package.description),
+            homepage = package.homepage,
+            license = package.license,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alpine(version: &str) -> Result<String, ApkError> {
+        alpine_version(&semver::Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn alpine_versions() {
+        assert_eq!(alpine("1.2.3").unwrap(), "1.2.3-r0");
+        assert_eq!(alpine("1.2.3+build.4").unwrap(), "1.2.3-r0");
+        assert_eq!(alpine("0.1.0-alpha").unwrap(), "0.1.0_alpha-r0");
+        assert_eq!(alpine("0.1.0-alpha.1").unwrap(), "0.1.0_alpha1-r0");
+        assert_eq!(alpine("0.1.0-alpha10").unwrap(), "0.1.0_alpha10-r0");
+        assert_eq!(alpine("0.1.0-beta.2").unwrap(), "0.1.0_beta2-r0");
+        assert_eq!(alpine("0.1.0-pre.3").unwrap(), "0.1.0_pre3-r0");
+        assert_eq!(alpine("0.1.0-rc.4").unwrap(), "0.1.0_rc4-r0");
+    }
+
+    #[test]
+    fn rejects_pre_releases_alpine_cant_order() {
+        // alpha.1.2 would become alpha12, after alpha.10
+        for version in [
+            "1.0.0-alpha.1.2",
+            "1.0.0-alpha1.2",
+            "1.0.0-alpha.x",
+            "1.0.0-dev",
+            "1.0.0-prerelease",
+            "1.0.0-1",
+        ] {
+            assert!(
+                matches!(alpine(version), Err(ApkError::UnsupportedVersion(v)) if v == version),
+                "{version}"
+            );
+        }
+    }
+
+    #[test]
+    fn tar_segments_read_as_one_tar() {
+        let control = tar_segment(
+            &[TarEntry {
+                path: ".PKGINFO".to_owned(),
+                mode: 0o644,
+                data: b"pkgname = sqlite-sample\n",
+            }],
+            1700000000,
+            true,
+        )
+        .unwrap();
+        let data = tar_segment(
+            &[
+                TarEntry {
+                    path: "usr/lib/sqlite3/".to_owned(),
+                    mode: 0o755,
+                    data: &[],
+                },
+                TarEntry {
+                    path: "usr/lib/sqlite3/sample0.so".to_owned(),
+                    mode: 0o755,
+                    data: b"loadable",
+                },
+            ],
+            1700000000,
+            false,
+        )
+        .unwrap();
+
+        let apk = [control, data].concat();
+        let mut archive = tar::Archive::new(flate2::read::MultiGzDecoder::new(apk.as_slice()));
+        let mut entries = vec![];
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let checksum = entry.pax_extensions().unwrap().map(|extensions| {
+                let extension = extensions
+                    .map(Result::unwrap)
+                    .find(|extension| extension.key() == Ok("APK-TOOLS.checksum.SHA1"))
+                    .unwrap();
+                extension.value().unwrap().to_owned()
+            });
+            entries.push((
+                entry.path().unwrap().display().to_string(),
+                entry.header().mode().unwrap(),
+                checksum,
+            ));
+        }
+        assert_eq!(
+            entries,
+            vec![
+                (".PKGINFO".to_owned(), 0o644, None),
+                ("usr/lib/sqlite3/".to_owned(), 0o755, None),
+                (
+                    "usr/lib/sqlite3/sample0.so".to_owned(),
+                    0o755,
+                    Some(base16ct::lower::encode_string(&Sha1::digest(b"loadable")))
+                ),
+            ]
+        );
+    }
+}
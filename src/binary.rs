@@ -0,0 +1,585 @@
+use std::fmt;
+
+use object::macho::{
+    LC_VERSION_MIN_IPHONEOS, LC_VERSION_MIN_MACOSX, PLATFORM_IOS, PLATFORM_IOSSIMULATOR,
+    PLATFORM_MACCATALYST, PLATFORM_MACOS,
+};
+use object::read::archive::ArchiveFile;
+use object::read::elf::{ElfFile, FileHeader};
+use object::read::macho::{
+    FatArch, LoadCommandVariant, MachHeader, MachOFatFile32, MachOFatFile64, MachOFile,
+};
+use object::{Architecture, FileKind, Object, ObjectKind, ObjectSymbol, ReadRef};
+
+use thiserror::Error;
+
+use crate::{Cpu, Os};
+
+#[derive(Error, Debug)]
+pub enum BinaryError {
+    #[error("{0}")]
+    Object(#[from] object::Error),
+    #[error("unsupported object file format")]
+    UnsupportedFormat,
+    #[error("no object files found")]
+    NoObjectFiles,
+}
+
+/// Object file format of a binary, with whatever the headers say about its OS.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum BinaryFormat {
+    // Android libraries carry a .note.android.ident section, others may be either.
+    // Binaries with GLIBC_* symbol versions can't run on musl.
+    Elf { android: bool, glibc: bool },
+    // LC_BUILD_VERSION / LC_VERSION_MIN_* platform, if any
+    MachO { platform: Option<u32> },
+    Coff,
+}
+
+impl BinaryFormat {
+    fn matches_os(&self, os: &Os) -> bool {
+        match self {
+            BinaryFormat::Elf { android: true, .. } => matches!(os, Os::Android),
+            BinaryFormat::Elf { glibc: true, .. } => matches!(os, Os::Linux),
+            BinaryFormat::Elf { .. } => matches!(os, Os::Linux | Os::LinuxMusl | Os::Android),
+            BinaryFormat::MachO { platform: None } => {
+                matches!(os, Os::Macos | Os::Ios | Os::IosSimulator)
+            }
+            BinaryFormat::MachO {
+                platform: Some(platform),
+            } => match *platform {
+                PLATFORM_MACOS | PLATFORM_MACCATALYST => matches!(os, Os::Macos),
+                PLATFORM_IOS => matches!(os, Os::Ios),
+                PLATFORM_IOSSIMULATOR => matches!(os, Os::IosSimulator),
+                _ => false,
+            },
+            BinaryFormat::Coff => matches!(os, Os::Windows),
+        }
+    }
+}
+
+impl fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryFormat::Elf { android: true, .. } => f.write_str("android ELF"),
+            BinaryFormat::Elf { glibc: true, .. } => f.write_str("glibc ELF"),
+            BinaryFormat::Elf { .. } => f.write_str("ELF"),
+            BinaryFormat::MachO { platform } => match *platform {
+                Some(PLATFORM_MACOS) | Some(PLATFORM_MACCATALYST) => f.write_str("macos Mach-O"),
+                Some(PLATFORM_IOS) => f.write_str("ios Mach-O"),
+                Some(PLATFORM_IOSSIMULATOR) => f.write_str("iossimulator Mach-O"),
+                _ => f.write_str("Mach-O"),
+            },
+            BinaryFormat::Coff => f.write_str("PE/COFF"),
+        }
+    }
+}
+
+/// One object inside a binary: the whole file, a slice of a Mach-O fat binary,
+/// or a member of a static archive.
+#[derive(Debug, Clone)]
+pub(crate) struct BinarySlice {
+    pub format: BinaryFormat,
+    pub cpu: Option<Cpu>,
+}
+
+impl fmt::Display for BinarySlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.cpu {
+            Some(cpu) => write!(f, "{} {cpu}", self.format),
+            None => write!(f, "{} (unknown CPU)", self.format),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BinaryInfo {
+    pub slices: Vec<BinarySlice>,
+    pub fat: bool,
+}
+
+impl BinaryInfo {
+    /// Every slice must be built for `os`. A fat binary needs a slice for `cpu`,
+    /// anything else must only contain `cpu` code.
+    pub(crate) fn matches(&self, os: &Os, cpu: &Cpu) -> bool {
+        if self.slices.is_empty() || !self.slices.iter().all(|s| s.format.matches_os(os)) {
+            return false;
+        }
+        if self.fat {
+            self.slices.iter().any(|s| s.cpu.as_ref() == Some(cpu))
+        } else {
+            self.slices.iter().all(|s| s.cpu.as_ref() == Some(cpu))
+        }
+    }
+}
+
+impl fmt::Display for BinaryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut descriptions: Vec<String> = self.slices.iter().map(|s| s.to_string()).collect();
+        descriptions.dedup();
+        f.write_str(&descriptions.join(", "))
+    }
+}
+
+fn architecture_cpu(architecture: Architecture) -> Option<Cpu> {
+    match architecture {
+        Architecture::X86_64 => Some(Cpu::X86_64),
+        Architecture::Aarch64 => Some(Cpu::Aarch64),
+        Architecture::I386 => Some(Cpu::I686),
+        Architecture::Arm => Some(Cpu::Armv7a),
+        _ => None,
+    }
+}
+
+fn macho_platform<'data, Mach: MachHeader, R: ReadRef<'data>>(
+    file: &MachOFile<'data, Mach, R>,
+) -> Option<u32> {
+    if let Ok(Some(build_version)) = file.build_version() {
+        return Some(build_version.platform.get(file.endian()));
+    }
+    // older toolchains only emit LC_VERSION_MIN_*, where x86 iOS means the simulator
+    let mut commands = file.macho_load_commands().ok()?;
+    while let Ok(Some(command)) = commands.next() {
+        match command.cmd() {
+            LC_VERSION_MIN_MACOSX => return Some(PLATFORM_MACOS),
+            LC_VERSION_MIN_IPHONEOS => {
+                return Some(match file.architecture() {
+                    Architecture::X86_64 | Architecture::I386 => PLATFORM_IOSSIMULATOR,
+                    _ => PLATFORM_IOS,
+                })
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn inspect_object(data: &[u8]) -> Result<BinarySlice, BinaryError> {
+    let file = object::File::parse(data)?;
+    let format = match &file {
+        object::File::Elf32(elf) => BinaryFormat::Elf {
+            android: file.section_by_name(".note.android.ident").is_some(),
+            glibc: elf_glibc_version(elf)?.is_some(),
+        },
+        object::File::Elf64(elf) => BinaryFormat::Elf {
+            android: file.section_by_name(".note.android.ident").is_some(),
+            glibc: elf_glibc_version(elf)?.is_some(),
+        },
+        object::File::MachO32(macho) => BinaryFormat::MachO {
+            platform: macho_platform(macho),
+        },
+        object::File::MachO64(macho) => BinaryFormat::MachO {
+            platform: macho_platform(macho),
+        },
+        object::File::Coff(_)
+        | object::File::CoffBig(_)
+        | object::File::Pe32(_)
+        | object::File::Pe64(_) => BinaryFormat::Coff,
+        _ => return Err(BinaryError::UnsupportedFormat),
+    };
+    Ok(BinarySlice {
+        format,
+        cpu: architecture_cpu(file.architecture()),
+    })
+}
+
+fn inspect_fat<F: FatArch>(data: &[u8], arches: &[F]) -> Result<Vec<BinarySlice>, BinaryError> {
+    arches
+        .iter()
+        .map(|arch| inspect_object(arch.data(data)?))
+        .collect()
+}
+
+// raw LLVM bitcode, or bitcode in the wrapper header Apple's toolchains emit
+fn is_llvm_bitcode(data: &[u8]) -> bool {
+    data.starts_with(b"BC\xc0\xde") || data.starts_with(&[0xde, 0xc0, 0x17, 0x0b])
+}
+
+/// Parse the object headers of a loadable or static library. None for static
+/// libraries built with LTO, whose members are all LLVM bitcode and don't say
+/// which OS they're for.
+pub(crate) fn inspect(data: &[u8]) -> Result<Option<BinaryInfo>, BinaryError> {
+    // an archive without members is too short for FileKind to read its magic
+    let kind = if data == b"!<arch>\n" {
+        FileKind::Archive
+    } else {
+        FileKind::parse(data)?
+    };
+    let info = match kind {
+        FileKind::MachOFat32 => BinaryInfo {
+            slices: inspect_fat(data, MachOFatFile32::parse(data)?.arches())?,
+            fat: true,
+        },
+        FileKind::MachOFat64 => BinaryInfo {
+            slices: inspect_fat(data, MachOFatFile64::parse(data)?.arches())?,
+            fat: true,
+        },
+        FileKind::Archive => {
+            let archive = ArchiveFile::parse(data)?;
+            let mut slices = vec![];
+            let mut bitcode = false;
+            for member in archive.members() {
+                let member = member?.data(data)?;
+                // skip members that aren't object files, like LLVM bitcode
+                match inspect_object(member) {
+                    Ok(slice) => slices.push(slice),
+                    Err(_) => bitcode |= is_llvm_bitcode(member),
+                }
+            }
+            if slices.is_empty() && bitcode {
+                return Ok(None);
+            }
+            if slices.is_empty() {
+                return Err(BinaryError::NoObjectFiles);
+            }
+            BinaryInfo { slices, fat: false }
+        }
+        _ => BinaryInfo {
+            slices: vec![inspect_object(data)?],
+            fat: false,
+        },
+    };
+    Ok(Some(info))
+}
+
+fn object_init_symbols(data: &[u8], symbols: &mut Vec<String>) -> Result<(), BinaryError> {
+    let file = object::File::parse(data)?;
+    // object files inside static archives have no dynamic symbol table
+    let names: Vec<&[u8]> = if file.kind() == ObjectKind::Relocatable {
+        file.symbols()
+            .filter(|symbol| symbol.is_global() && symbol.is_definition())
+            .filter_map(|symbol| symbol.name_bytes().ok())
+            .collect()
+    } else {
+        file.exports()?.iter().map(|export| export.name()).collect()
+    };
+    for name in names {
+        let Ok(name) = std::str::from_utf8(name) else {
+            continue;
+        };
+        // Mach-O symbols carry a leading underscore
+        let name = match file.format() {
+            object::BinaryFormat::MachO => name.strip_prefix('_').unwrap_or(name),
+            _ => name,
+        };
+        if name.starts_with("sqlite3_") && name.ends_with("_init") {
+            symbols.push(name.to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Exported `sqlite3_*_init` symbols of a loadable extension or static library,
+/// sorted and deduplicated. Binaries that can't be parsed have none.
+pub(crate) fn init_symbols(data: &[u8]) -> Vec<String> {
+    let mut symbols = vec![];
+    let _ = match FileKind::parse(data) {
+        Ok(FileKind::MachOFat32) => MachOFatFile32::parse(data)
+            .map_err(BinaryError::from)
+            .and_then(|fat| {
+                fat.arches()
+                    .iter()
+                    .try_for_each(|arch| object_init_symbols(arch.data(data)?, &mut symbols))
+            }),
+        Ok(FileKind::MachOFat64) => MachOFatFile64::parse(data)
+            .map_err(BinaryError::from)
+            .and_then(|fat| {
+                fat.arches()
+                    .iter()
+                    .try_for_each(|arch| object_init_symbols(arch.data(data)?, &mut symbols))
+            }),
+        Ok(FileKind::Archive) => {
+            ArchiveFile::parse(data)
+                .map_err(BinaryError::from)
+                .map(|archive| {
+                    for member in archive.members().filter_map(Result::ok) {
+                        if let Ok(member_data) = member.data(data) {
+                            let _ = object_init_symbols(member_data, &mut symbols);
+                        }
+                    }
+                })
+        }
+        _ => object_init_symbols(data, &mut symbols),
+    };
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+/// The init function SQLite looks for when `sqlite3_load_extension` isn't given
+/// an entrypoint: `sqlite3_extension_init` first, then `sqlite3_X_init` where X is
+/// the lowercased letters of the file name up to its first ".", minus any "lib" prefix.
+pub(crate) fn default_init_symbols(file_name: &str) -> [String; 2] {
+    let name = file_name.strip_prefix("lib").unwrap_or(file_name);
+    let derived: String = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    [
+        "sqlite3_extension_init".to_owned(),
+        format!("sqlite3_{derived}_init"),
+    ]
+}
+
+/// Major and minor version, like glibc 2.17 or macOS 11.0
+pub(crate) type OsVersion = (u32, u32);
+
+// GLIBC_2.17 -> (2, 17), GLIBC_2.2.5 -> (2, 2)
+fn parse_glibc_version(name: &[u8]) -> Option<OsVersion> {
+    let version = std::str::from_utf8(name).ok()?.strip_prefix("GLIBC_")?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+fn elf_glibc_version<'data, Elf: FileHeader, R: ReadRef<'data>>(
+    file: &ElfFile<'data, Elf, R>,
+) -> Result<Option<OsVersion>, BinaryError> {
+    let endian = file.endian();
+    let sections = file.elf_section_table();
+    let Some((mut verneeds, link)) = sections.gnu_verneed(endian, file.data())? else {
+        return Ok(None);
+    };
+    let strings = sections.strings(endian, file.data(), link)?;
+    let mut highest = None;
+    while let Some((_, mut vernauxs)) = verneeds.next()? {
+        while let Some(vernaux) = vernauxs.next()? {
+            highest = highest.max(parse_glibc_version(vernaux.name(endian, strings)?));
+        }
+    }
+    Ok(highest)
+}
+
+/// Highest `GLIBC_x.y` symbol version an ELF binary requires.
+pub(crate) fn glibc_version(data: &[u8]) -> Option<OsVersion> {
+    match object::File::parse(data).ok()? {
+        object::File::Elf32(elf) => elf_glibc_version(&elf).ok()?,
+        object::File::Elf64(elf) => elf_glibc_version(&elf).ok()?,
+        _ => None,
+    }
+}
+
+// X.Y.Z is encoded in nibbles xxxx.yy.zz
+fn decode_macho_version(version: u32) -> OsVersion {
+    (version >> 16, (version >> 8) & 0xff)
+}
+
+fn macho_macos_version<'data, Mach: MachHeader, R: ReadRef<'data>>(
+    file: &MachOFile<'data, Mach, R>,
+) -> Option<OsVersion> {
+    let endian = file.endian();
+    let mut commands = file.macho_load_commands().ok()?;
+    while let Ok(Some(command)) = commands.next() {
+        match command.variant().ok()? {
+            LoadCommandVariant::BuildVersion(build_version)
+                if build_version.platform.get(endian) == PLATFORM_MACOS =>
+            {
+                return Some(decode_macho_version(build_version.minos.get(endian)));
+            }
+            LoadCommandVariant::VersionMin(version_min)
+                if version_min.cmd.get(endian) == LC_VERSION_MIN_MACOSX =>
+            {
+                return Some(decode_macho_version(version_min.version.get(endian)));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn object_macos_version(data: &[u8]) -> Option<OsVersion> {
+    match object::File::parse(data).ok()? {
+        object::File::MachO32(macho) => macho_macos_version(&macho),
+        object::File::MachO64(macho) => macho_macos_version(&macho),
+        _ => None,
+    }
+}
+
+/// Minimum macOS version of a Mach-O binary. For a fat binary it's the lowest one
+/// across the slices, as each slice only has to run on its own CPU.
+pub(crate) fn macos_version(data: &[u8]) -> Option<OsVersion> {
+    match FileKind::parse(data).ok()? {
+        FileKind::MachOFat32 => MachOFatFile32::parse(data)
+            .ok()?
+            .arches()
+            .iter()
+            .filter_map(|arch| object_macos_version(arch.data(data).ok()?))
+            .min(),
+        FileKind::MachOFat64 => MachOFatFile64::parse(data)
+            .ok()?
+            .arches()
+            .iter()
+            .filter_map(|arch| object_macos_version(arch.data(data).ok()?))
+            .min(),
+        _ => object_macos_version(data),
+    }
+}
+
+/// The init function SQLite ends up calling for a library with the given exported
+/// `sqlite3_*_init` symbols: one of the defaults if it's exported, otherwise the
+/// first exported one, or the name derived from the file name if none are known.
+pub(crate) fn init_function(file_name: &str, symbols: &[String]) -> String {
+    let defaults = default_init_symbols(file_name);
+    symbols
+        .iter()
+        .find(|symbol| defaults.contains(symbol))
+        .or(symbols.first())
+        .cloned()
+        .unwrap_or_else(|| defaults[1].clone())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+    pub(crate) const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+    /// A 64-bit Mach-O dylib whose only load command is LC_BUILD_VERSION for
+    /// macOS `minos`, or that has no load commands at all.
+    pub(crate) fn macho_dylib(cputype: u32, minos: Option<OsVersion>) -> Vec<u8> {
+        let mut commands = vec![];
+        if let Some((major, minor)) = minos {
+            commands.extend(0x32u32.to_le_bytes()); // LC_BUILD_VERSION
+            commands.extend(24u32.to_le_bytes());
+            commands.extend(PLATFORM_MACOS.to_le_bytes());
+            commands.extend((major << 16 | minor << 8).to_le_bytes());
+            commands.extend((major << 16 | minor << 8).to_le_bytes()); // sdk
+            commands.extend(0u32.to_le_bytes()); // ntools
+        }
+        let mut macho = 0xfeed_facfu32.to_le_bytes().to_vec();
+        macho.extend(cputype.to_le_bytes());
+        macho.extend(0u32.to_le_bytes()); // cpusubtype
+        macho.extend(6u32.to_le_bytes()); // MH_DYLIB
+        macho.extend(u32::from(minos.is_some()).to_le_bytes());
+        macho.extend((commands.len() as u32).to_le_bytes());
+        macho.extend([0; 8]); // flags, reserved
+        macho.extend(commands);
+        macho
+    }
+
+    /// The 64 byte header of an ELF64 relocatable object with no sections.
+    pub(crate) fn elf_object(machine: u16) -> Vec<u8> {
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(16, 0);
+        elf.extend(1u16.to_le_bytes()); // ET_REL
+        elf.extend(machine.to_le_bytes());
+        elf.extend(1u32.to_le_bytes());
+        elf.extend([0; 28]); // entry, phoff, shoff, flags
+        elf.extend(64u16.to_le_bytes());
+        elf.extend([0; 4]); // phentsize, phnum
+        elf.extend(64u16.to_le_bytes());
+        elf.extend([0; 4]); // shnum, shstrndx
+        elf
+    }
+
+    fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut ar = b"!<arch>\n".to_vec();
+        for (name, data) in members {
+            let name = format!("{name}/");
+            ar.extend(
+                format!(
+                    "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                    0,
+                    0,
+                    0,
+                    644,
+                    data.len()
+                )
+                .as_bytes(),
+            );
+            ar.extend(*data);
+            if data.len() % 2 == 1 {
+                ar.push(b'\n');
+            }
+        }
+        ar
+    }
+
+    pub(crate) const EM_X86_64: u16 = 62;
+    pub(crate) const EM_AARCH64: u16 = 183;
+
+    #[test]
+    fn inspects_an_object() {
+        let info = inspect(&elf_object(EM_X86_64)).unwrap().unwrap();
+        assert!(!info.fat);
+        assert!(info.matches(&Os::Linux, &Cpu::X86_64));
+        assert!(info.matches(&Os::LinuxMusl, &Cpu::X86_64));
+        assert!(!info.matches(&Os::Linux, &Cpu::Aarch64));
+        assert!(!info.matches(&Os::Macos, &Cpu::X86_64));
+        assert_eq!(info.to_string(), "ELF x86_64");
+    }
+
+    #[test]
+    fn inspects_every_archive_member() {
+        let x86_64 = elf_object(EM_X86_64);
+        let aarch64 = elf_object(EM_AARCH64);
+        let info = inspect(&archive(&[("a.o", &x86_64), ("b.o", &x86_64)]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.slices.len(), 2);
+        assert!(info.matches(&Os::Linux, &Cpu::X86_64));
+
+        // a static library mixing CPUs matches neither
+        let info = inspect(&archive(&[("a.o", &x86_64), ("b.o", &aarch64)]))
+            .unwrap()
+            .unwrap();
+        assert!(!info.matches(&Os::Linux, &Cpu::X86_64));
+        assert!(!info.matches(&Os::Linux, &Cpu::Aarch64));
+        assert_eq!(info.to_string(), "ELF x86_64, ELF aarch64");
+    }
+
+    #[test]
+    fn skips_archives_of_llvm_bitcode() {
+        let bitcode = b"BC\xc0\xde\x35\x14\x00\x00";
+        assert!(inspect(&archive(&[("a.o", bitcode)])).unwrap().is_none());
+        let wrapped = [0xde, 0xc0, 0x17, 0x0b, 0, 0, 0, 0];
+        assert!(inspect(&archive(&[("a.o", &wrapped)])).unwrap().is_none());
+        // an object next to the bitcode is still checked
+        let info = inspect(&archive(&[
+            ("a.o", bitcode),
+            ("b.o", &elf_object(EM_X86_64)),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(info.slices.len(), 1);
+    }
+
+    #[test]
+    fn rejects_archives_without_objects() {
+        assert!(matches!(
+            inspect(b"!<arch>\n"),
+            Err(BinaryError::NoObjectFiles)
+        ));
+        assert!(matches!(
+            inspect(&archive(&[("README", b"not an object")])),
+            Err(BinaryError::NoObjectFiles)
+        ));
+    }
+
+    #[test]
+    fn glibc_versions() {
+        assert_eq!(parse_glibc_version(b"GLIBC_2.17"), Some((2, 17)));
+        assert_eq!(parse_glibc_version(b"GLIBC_2.2.5"), Some((2, 2)));
+        assert_eq!(parse_glibc_version(b"GLIBC_PRIVATE"), None);
+        assert_eq!(parse_glibc_version(b"GCC_3.0"), None);
+        // no .gnu.version_r section
+        assert_eq!(glibc_version(&elf_object(EM_X86_64)), None);
+    }
+
+    #[test]
+    fn macos_versions() {
+        assert_eq!(decode_macho_version(0x000a_0d02), (10, 13));
+        assert_eq!(
+            macos_version(&macho_dylib(CPU_TYPE_ARM64, Some((12, 3)))),
+            Some((12, 3))
+        );
+        assert_eq!(macos_version(&macho_dylib(CPU_TYPE_X86_64, None)), None);
+        assert_eq!(macos_version(&elf_object(EM_X86_64)), None);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::{static_link_name, PlatformDirectory, PlatformFile, Project};
+
+/// Where a CMake target finds its files, relative to the package prefix.
+enum CmakeTarget<'a> {
+    /// Prebuilt static libraries under lib/, the first one is the target's location.
+    Static(&'a [PlatformFile]),
+    /// C sources compiled into every target that links it.
+    Sources(Vec<&'a str>),
+}
+
+/// lib/pkgconfig/{name}.pc and lib/cmake/{name}/ files for a static release
+/// tarball, which has its headers in include/ and libraries in lib/.
+pub(crate) fn static_files(
+    project: &Project,
+    platform_dir: &PlatformDirectory,
+) -> Vec<PlatformFile> {
+    let libraries = &platform_dir.static_files;
+    let link_names: Vec<&str> = libraries
+        .iter()
+        .map(|library| static_link_name(&library.name))
+        .collect();
+    let libs = format!(
+        "-L${{libdir}} {}",
+        link_names
+            .iter()
+            .map(|name| format!("-l{name}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let (cflags, include_dirs) = if platform_dir.header_files.is_empty() {
+        ("", vec![])
+    } else {
+        ("-I${prefix}/include", vec!["include"])
+    };
+    package_files(
+        project,
+        templates::pc(project, "libdir=${prefix}/lib\n", cflags, &libs),
+        CmakeTarget::Static(libraries),
+        include_dirs,
+    )
+}
+
+/// lib/pkgconfig/{name}.pc and lib/cmake/{name}/ files for the amalgamation,
+/// which has its sources at their paths from the spec. pkg-config can't build
+/// sources, so they're listed in a `sources` variable instead.
+pub(crate) fn amalgamation_files(project: &Project, files: &[PlatformFile]) -> Vec<PlatformFile> {
+    let sources: Vec<&str> = files
+        .iter()
+        .map(|file| file.name.as_str())
+        .filter(|name| name.ends_with(".c"))
+        .collect();
+    let include_dirs: Vec<&str> = files
+        .iter()
+        .filter(|file| file.name.ends_with(".h"))
+        .map(|file| {
+            Path::new(&file.name)
+                .parent()
+                .and_then(Path::to_str)
+                .unwrap_or("")
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let variables = format!(
+        "sources={}\n",
+        sources
+            .iter()
+            .map(|source| format!("${{prefix}}/{source}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let cflags = include_dirs
+        .iter()
+        .map(|dir| prefixed("-I${prefix}", dir))
+        .collect::<Vec<_>>()
+        .join(" ");
+    package_files(
+        project,
+        templates::pc(project, &variables, &cflags, ""),
+        CmakeTarget::Sources(sources),
+        include_dirs,
+    )
+}
+
+// ${prefix} + "src" -> ${prefix}/src, ${prefix} + "" -> ${prefix}
+fn prefixed(prefix: &str, path: &str) -> String {
+    if path.is_empty() {
+        prefix.to_owned()
+    } else {
+        format!("{prefix}/{path}")
+    }
+}
+
+fn package_files(
+    project: &Project,
+    pc: String,
+    target: CmakeTarget,
+    include_dirs: Vec<&str>,
+) -> Vec<PlatformFile> {
+    let name = &project.spec.package.name;
+    vec![
+        PlatformFile::new(format!("lib/pkgconfig/{name}.pc"), pc, None),
+        PlatformFile::new(
+            format!("lib/cmake/{name}/{name}Config.cmake"),
+            templates::config_cmake(name, &target, &include_dirs),
+            None,
+        ),
+        PlatformFile::new(
+            format!("lib/cmake/{name}/{name}ConfigVersion.cmake"),
+            templates::config_version_cmake(project),
+            None,
+        ),
+    ]
+}
+
+mod templates {
+    use super::{prefixed, CmakeTarget};
+    use crate::Project;
+
+    pub(crate) fn pc(project: &Project, variables: &str, cflags: &str, libs: &str) -> String {
+        let package = &project.spec.package;
+        // fields are a single line each
+        let description = package.description.replace(['\r', '\n'], " ");
+        format!(
+            r#"prefix=${{pcfiledir}}/../..
+{variables}
+Name: {name}
+Description: {description}
+URL: {homepage}
+Version: {version}
+Cflags: {cflags}
+Libs: {libs}
+"#,
+            name = package.name,
+            homepage = package.homepage,
+            version = project.version,
+        )
+    }
+
+    pub(crate) fn config_cmake(name: &str, target: &CmakeTarget, include_dirs: &[&str]) -> String {
+        let prefix_var = format!(
+            "_{}_PREFIX",
+            name.to_ascii_uppercase().replace(['-', '.'], "_")
+        );
+        let prefix = format!("${{{prefix_var}}}");
+        let mut properties = vec![];
+        let kind = match target {
+            CmakeTarget::Static(libraries) => {
+                properties.push(format!(
+                    "IMPORTED_LOCATION \"{prefix}/lib/{}\"",
+                    libraries[0].name
+                ));
+                properties.push("IMPORTED_LINK_INTERFACE_LANGUAGES \"C\"".to_owned());
+                if libraries.len() > 1 {
+                    properties.push(format!(
+                        "INTERFACE_LINK_LIBRARIES \"{}\"",
+                        libraries[1..]
+                            .iter()
+                            .map(|library| format!("{prefix}/lib/{}", library.name))
+                            .collect::<Vec<_>>()
+                            .join(";")
+                    ));
+                }
+                "STATIC"
+            }
+            CmakeTarget::Sources(sources) => {
+                properties.push(format!(
+                    "INTERFACE_SOURCES \"{}\"",
+                    sources
+                        .iter()
+                        .map(|source| format!("{prefix}/{source}"))
+                        .collect::<Vec<_>>()
+                        .join(";")
+                ));
+                "INTERFACE"
+            }
+        };
+        if !include_dirs.is_empty() {
+            properties.push(format!(
+                "INTERFACE_INCLUDE_DIRECTORIES \"{}\"",
+                include_dirs
+                    .iter()
+                    .map(|dir| prefixed(&prefix, dir))
+                    .collect::<Vec<_>>()
+                    .join(";")
+            ));
+        }
+        let properties: String = properties
+            .iter()
+            .map(|property| format!("  {property}\n"))
+            .collect();
+        format!(
+            r#"# Generated by sqlite-dist.
+if(TARGET {name}::{name})
+  return()
+endif()
+
+get_filename_component({prefix_var} "${{CMAKE_CURRENT_LIST_DIR}}/../../.." ABSOLUTE)
+
+add_library({name}::{name} {kind} IMPORTED)
+set_target_properties({name}::{name} PROPERTIES
+{properties})
+
+unset({prefix_var})
+"#
+        )
+    }
+
+    // the same rules as write_basic_package_version_file(COMPATIBILITY SameMajorVersion)
+    pub(crate) fn config_version_cmake(project: &Project) -> String {
+        let version = &project.version;
+        format!(
+            r#"# Generated by sqlite-dist.
+set(PACKAGE_VERSION "{major}.{minor}.{patch}")
+
+if(PACKAGE_FIND_VERSION VERSION_GREATER PACKAGE_VERSION)
+  set(PACKAGE_VERSION_COMPATIBLE FALSE)
+elseif(PACKAGE_FIND_VERSION_MAJOR STREQUAL "{major}")
+  set(PACKAGE_VERSION_COMPATIBLE TRUE)
+  if(PACKAGE_FIND_VERSION STREQUAL PACKAGE_VERSION)
+    set(PACKAGE_VERSION_EXACT TRUE)
+  endif()
+else()
+  set(PACKAGE_VERSION_COMPATIBLE FALSE)
+endif()
+"#,
+            major = version.major,
+            minor = version.minor,
+            patch = version.patch,
+        )
+    }
+}
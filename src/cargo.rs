@@ -0,0 +1,345 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::spec::TargetCargo;
+use crate::{
+    binary, create_targz, platform_name, GeneratedAsset, GeneratedAssetKind, PlatformFile, Project,
+};
+
+/// A static release tarball the build script can link, and the library inside it.
+struct Archive<'a> {
+    platform: String,
+    library: String,
+    // the release URL, or the path inside the crate when vendored
+    source: String,
+    checksum: &'a str,
+}
+
+/// Writes a `{name}-sys` crate that statically links the platform's library from
+/// the static GitHub release tarballs, checked against their sha256. With
+/// `vendored = true` the tarballs are shipped inside the crate instead of
+/// downloaded. The source tree goes in cargo/{crate}/ and the packaged .crate
+/// next to it.
+pub(crate) fn write_cargo(
+    project: &Project,
+    cargo_path: &Path,
+    cargo_config: &TargetCargo,
+    gh_release_assets: &[GeneratedAsset],
+) -> io::Result<Vec<GeneratedAsset>> {
+    let mut archives = vec![];
+    let mut vendored_files = vec![];
+    let mut init_function = project.spec.package.entrypoint.clone();
+    for platform_dir in &project.platform_directories {
+        let Some(library) = platform_dir.static_files.first() else {
+            continue;
+        };
+        let Some((asset, release)) = gh_release_assets
+            .iter()
+            .find_map(|asset| match &asset.kind {
+                GeneratedAssetKind::GithubReleaseStatic(release)
+                    if release.platform == (platform_dir.os.clone(), platform_dir.cpu.clone()) =>
+                {
+                    Some((asset, release))
+                }
+                _ => None,
+            })
+        else {
+            continue;
+        };
+        let platform = platform_name(&platform_dir.os, &platform_dir.cpu);
+        let source = if cargo_config.vendored {
+            let path = format!("vendor/{}", asset.name);
+            vendored_files.push(PlatformFile::new(
+                path.clone(),
+                fs::read(&asset.path)?,
+                None,
+            ));
+            path
+        } else {
+            release.url.clone()
+        };
+        init_function.get_or_insert_with(|| {
+            binary::init_function(&library.name, &binary::init_symbols(&library.data))
+        });
+        archives.push(Archive {
+            platform,
+            library: library.name.clone(),
+            source,
+            checksum: &asset.checksum_sha256,
+        });
+    }
+    let Some(init_function) = init_function else {
+        println!("Warning: no static library found, skipping the cargo target");
+        return Ok(vec![]);
+    };
+
+    let crate_name = format!("{}-sys", project.spec.package.name);
+    let mut files = vec![
+        PlatformFile::new(
+            "Cargo.toml",
+            templates::cargo_toml(project, &crate_name, cargo_config.vendored),
+            None,
+        ),
+        PlatformFile::new(
+            "build.rs",
+            templates::build_rs(&project.spec.package.name, &archives, cargo_config.vendored),
+            None,
+        ),
+        PlatformFile::new(
+            "src/lib.rs",
+            templates::lib_rs(&project.spec.package.name, &crate_name, &init_function),
+            None,
+        ),
+    ];
+    files.extend(vendored_files);
+
+    let source_path = cargo_path.join(&crate_name);
+    for file in &files {
+        let path = source_path.join(&file.name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, &file.data)?;
+    }
+
+    // .crate files are tarballs with everything under {name}-{version}/
+    let package_dir = format!("{crate_name}-{}", project.version);
+    let crate_files: Vec<PlatformFile> = files
+        .into_iter()
+        .map(|file| PlatformFile::new(format!("{package_dir}/{}", file.name), file.data, None))
+        .collect();
+    Ok(vec![GeneratedAsset::from(
+        GeneratedAssetKind::Cargo,
+        &cargo_path.join(format!("{package_dir}.crate")),
+        &create_targz(
+            &crate_files.iter().collect::<Vec<_>>(),
+            project.source_date_epoch,
+        )?,
+    )?])
+}
+
+mod templates {
+    use super::Archive;
+    use crate::{static_link_name, Project};
+
+    pub(crate) fn cargo_toml(project: &Project, crate_name: &str, vendored: bool) -> String {
+        let package = &project.spec.package;
+        let string = |value: &str| toml::Value::String(value.to_owned()).to_string();
+        let authors = toml::Value::Array(
+            package
+                .authors
+                .iter()
+                .map(|author| toml::Value::String(author.clone()))
+                .collect(),
+        );
+        let (include, ureq) = if vendored {
+            (r#"["/Cargo.toml", "/build.rs", "/src", "/vendor"]"#, "")
+        } else {
+            (r#"["/Cargo.toml", "/build.rs", "/src"]"#, "\nureq = \"2\"")
+        };
+        format!(
+            r#"[package]
+name = "{crate_name}"
+version = "{version}"
+edition = "2021"
+rust-version = "1.82"
+authors = {authors}
+description = {description}
+license = {license}
+homepage = {homepage}
+repository = {repo}
+links = "{name}"
+build = "build.rs"
+include = {include}
+
+[build-dependencies]
+flate2 = "1"
+sha2 = "0.10"
+tar = "0.4"{ureq}
+"#,
+            version = project.version,
+            description = string(&package.description),
+            license = string(&package.license),
+            homepage = string(&package.homepage),
+            repo = string(&package.repo),
+            name = package.name,
+        )
+    }
+
+    pub(crate) fn build_rs(package_name: &str, archives: &[Archive], vendored: bool) -> String {
+        let archives: String = archives
+            .iter()
+            .map(|archive| {
+                format!(
+                    "    (\n        \"{}\",\n        \"{}\",\n        \"{}\",\n        \"{}\",\n    ),\n",
+                    archive.platform,
+                    static_link_name(&archive.library),
+                    archive.source,
+                    archive.checksum,
+                )
+            })
+            .collect();
+        let env_var = format!(
+            "{}_LIB_DIR",
+            package_name.to_ascii_uppercase().replace('-', "_")
+        );
+        let (fetch_import, fetch) = if vendored {
+            (
+                "use std::fs;",
+                r#"fn fetch(source: &str) -> Vec<u8> {
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    fs::read(manifest_dir.join(source)).unwrap_or_else(|err| panic!("reading {source}: {err}"))
+}"#,
+            )
+        } else {
+            (
+                "use std::io::Read;",
+                r#"fn fetch(source: &str) -> Vec<u8> {
+    let mut data = vec![];
+    ureq::get(source)
+        .call()
+        .and_then(|response| Ok(response.into_reader().read_to_end(&mut data)?))
+        .unwrap_or_else(|err| panic!("downloading {source}: {err}"));
+    data
+}"#,
+            )
+        };
+        format!(
+            r#"// Code generated by sqlite-dist. DO NOT EDIT.
+
+use std::env;
+{fetch_import}
+use std::path::{{Path, PathBuf}};
+
+use sha2::{{Digest, Sha256}};
+
+// (platform, library, static release tarball, sha256 from checksums.txt)
+const ARCHIVES: &[(&str, &str, &str, &str)] = &[
+{archives}];
+
+// the platform directory names sqlite-dist uses, ex linux-x86_64-musl
+fn platform() -> String {{
+    let os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    let target_abi = env::var("CARGO_CFG_TARGET_ABI").unwrap_or_default();
+    let cpu = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {{
+        "x86" => "i686".to_owned(),
+        "arm" => "armv7a".to_owned(),
+        arch => arch.to_owned(),
+    }};
+    match os.as_str() {{
+        "linux" if target_env == "musl" => format!("linux-{{cpu}}-musl"),
+        "ios" if target_abi == "sim" || cpu == "x86_64" => format!("iossimulator-{{cpu}}"),
+        os => format!("{{os}}-{{cpu}}"),
+    }}
+}}
+
+{fetch}
+
+// the name of the only static library in a directory, lib<name>.a or <name>.lib
+fn static_library(dir: &Path) -> Option<String> {{
+    let mut names = std::fs::read_dir(dir).ok()?.filter_map(|entry| {{
+        let file_name = entry.ok()?.file_name().into_string().ok()?;
+        let name = file_name
+            .strip_prefix("lib")
+            .and_then(|name| name.strip_suffix(".a"))
+            .or_else(|| file_name.strip_suffix(".lib"))?;
+        Some(name.to_owned())
+    }});
+    let name = names.next()?;
+    names.next().is_none().then_some(name)
+}}
+
+fn main() {{
+    println!("cargo:rerun-if-env-changed={env_var}");
+    let platform = platform();
+    let archive = ARCHIVES.iter().find(|(p, ..)| *p == platform);
+
+    let (library, lib_dir, include_dir) = match (env::var_os("{env_var}"), archive) {{
+        // a directory with the library and its headers, ex a platform directory
+        (Some(dir), _) => {{
+            let dir = PathBuf::from(dir);
+            let library = match archive {{
+                Some((_, library, ..)) => library.to_string(),
+                None => static_library(&dir).unwrap_or_else(|| {{
+                    panic!("{package_name} has no static build for {{platform}} and {env_var} ({{}}) doesn't have exactly one static library", dir.display())
+                }}),
+            }};
+            (library, dir.clone(), dir)
+        }}
+        (None, Some((_, library, source, checksum))) => {{
+            let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join(&platform);
+            if !out_dir.join(format!("lib/lib{{library}}.a")).exists() {{
+                let tarball = fetch(source);
+                let actual: String = Sha256::digest(&tarball)
+                    .iter()
+                    .map(|byte| format!("{{byte:02x}}"))
+                    .collect();
+                if actual != *checksum {{
+                    panic!("checksum mismatch for {{source}}: expected {{checksum}}, got {{actual}}");
+                }}
+                tar::Archive::new(flate2::read::GzDecoder::new(tarball.as_slice()))
+                    .unpack(&out_dir)
+                    .unwrap_or_else(|err| panic!("extracting {{source}}: {{err}}"));
+            }}
+            (library.to_string(), out_dir.join("lib"), out_dir.join("include"))
+        }}
+        (None, None) => {{
+            panic!("{package_name} has no static build for {{platform}}, set {env_var} to a directory with one");
+        }}
+    }};
+    println!("cargo:rustc-link-search=native={{}}", lib_dir.display());
+    println!("cargo:rustc-link-lib=static={{library}}");
+    // headers, for dependents as DEP_{links}_INCLUDE
+    println!("cargo:include={{}}", include_dir.display());
+}}
+"#,
+            links = package_name.to_ascii_uppercase().replace('-', "_"),
+        )
+    }
+
+    pub(crate) fn lib_rs(package_name: &str, crate_name: &str, init_function: &str) -> String {
+        let crate_ident = crate_name.replace('-', "_");
+        format!(
+            r#"// Code generated by sqlite-dist. DO NOT EDIT.
+
+//! Static build of the {package_name} SQLite extension. The library calls the
+//! SQLite API directly, so link a SQLite as well, ex rusqlite with the
+//! `bundled` feature, and register the extension for every new connection:
+//!
+//! ```ignore
+//! unsafe {{
+//!     rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
+//!         {crate_ident}::{init_function} as *const (),
+//!     )));
+//! }}
+//! ```
+#![no_std]
+
+use core::ffi::{{c_char, c_int}};
+
+/// An opaque SQLite connection, the same as `rusqlite::ffi::sqlite3`.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct sqlite3 {{
+    _private: [u8; 0],
+}}
+
+/// SQLite's API routines table, only used by dynamically loaded extensions.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct sqlite3_api_routines {{
+    _private: [u8; 0],
+}}
+
+unsafe extern "C" {{
+    /// The extension's init function, which registers it on `db`.
+    pub fn {init_function}(
+        db: *mut sqlite3,
+        pz_err_msg: *mut *mut c_char,
+        p_api: *const sqlite3_api_routines,
+    ) -> c_int;
+}}
+"#
+        )
+    }
+}
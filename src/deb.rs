@@ -0,0 +1,399 @@
+use std::io;
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use md5::{Digest, Md5};
+use tar::{EntryType, Header};
+
+use crate::{
+    warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os, PlatformDirectory,
+    Project,
+};
+
+// https://wiki.debian.org/Multiarch/Tuples
+fn debian_architecture(cpu: &Cpu) -> Option<(&'static str, &'static str)> {
+    match cpu {
+        Cpu::X86_64 => Some(("amd64", "x86_64-linux-gnu")),
+        Cpu::Aarch64 => Some(("arm64", "aarch64-linux-gnu")),
+        Cpu::I686 => Some(("i386", "i386-linux-gnu")),
+        Cpu::Armv7a => Some(("armhf", "arm-linux-gnueabihf")),
+        Cpu::Universal => None,
+    }
+}
+
+/// Debian versions sort "~" before anything, which is how semver pre-releases
+/// sort before their release.
+pub(crate) fn debian_version(version: &semver::Version) -> String {
+    let mut debian = format!("{}.{}.{}", version.major, version.minor, version.patch);
+    if !version.pre.is_empty() {
+        debian.push_str(&format!("~{}", version.pre));
+    }
+    if !version.build.is_empty() {
+        debian.push_str(&format!("+{}", version.build));
+    }
+    debian
+}
+
+/// A `!<arch>` archive of the members, in the common format dpkg reads.
+fn create_ar(members: &[(&str, &[u8])], mtime: u64) -> Vec<u8> {
+    let mut ar = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        ar.extend(
+            format!(
+                "{name:<16}{mtime:<12}{uid:<6}{gid:<6}{mode:<8o}{size:<10}`\n",
+                uid = 0,
+                gid = 0,
+                mode = 0o100644,
+                size = data.len()
+            )
+            .as_bytes(),
+        );
+        ar.extend(*data);
+        if data.len() % 2 == 1 {
+            ar.push(b'\n');
+        }
+    }
+    ar
+}
+
+/// A tar.gz of root-owned entries, with directories (names ending in /) 0755
+/// and files 0644, the modes dpkg installs them with.
+fn create_deb_targz(entries: &[(String, &[u8])], mtime: u64) -> io::Result<Vec<u8>> {
+    let mut tar = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+    for (name, data) in entries {
+        let mut header = Header::new_gnu();
+        if name.ends_with('/') {
+            header.set_entry_type(EntryType::Directory);
+            header.set_mode(0o755);
+        } else {
+            header.set_mode(0o644);
+        }
+        header.set_size(data.len() as u64);
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        tar.append_data(&mut header, name, *data)?;
+    }
+    tar.into_inner()?.finish()
+}
+
+/// Installed files under their absolute paths without the leading /, ex
+/// "usr/lib/sqlite3/sample0.so".
+struct Package<'a> {
+    name: String,
+    section: &'static str,
+    depends: Option<String>,
+    files: Vec<(String, &'a [u8])>,
+}
+
+fn create_deb(project: &Project, package: &Package, architecture: &str) -> io::Result<Vec<u8>> {
+    // dpkg doesn't create parent directories that aren't in the archive
+    let mut directories: Vec<String> = package
+        .files
+        .iter()
+        .flat_map(|(path, _)| Path::new(path).ancestors().skip(1))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| format!("./{}/", dir.display()))
+        .collect();
+    directories.sort();
+    directories.dedup();
+    let mut data_entries: Vec<(String, &[u8])> = directories
+        .into_iter()
+        .map(|dir| (dir, [].as_slice()))
+        .collect();
+    data_entries.extend(
+        package
+            .files
+            .iter()
+            .map(|(path, data)| (format!("./{path}"), *data)),
+    );
+
+    let md5sums: String = package
+        .files
+        .iter()
+        .map(|(path, data)| {
+            format!(
+                "{}  {path}\n",
+                base16ct::lower::encode_string(&Md5::digest(data))
+            )
+        })
+        .collect();
+    let installed_size: usize = package.files.iter().map(|(_, data)| data.len()).sum();
+    let control = templates::control(
+        project,
+        package,
+        architecture,
+        installed_size.div_ceil(1024),
+    );
+    let control_entries = [
+        ("./control".to_owned(), control.as_bytes()),
+        ("./md5sums".to_owned(), md5sums.as_bytes()),
+    ];
+
+    let mtime = project.source_date_epoch.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    });
+    Ok(create_ar(
+        &[
+            ("debian-binary", b"2.0\n"),
+            (
+                "control.tar.gz",
+                &create_deb_targz(&control_entries, mtime)?,
+            ),
+            ("data.tar.gz", &create_deb_targz(&data_entries, mtime)?),
+        ],
+        mtime,
+    ))
+}
+
+/// Writes a {name} .deb per linux platform directory with the loadable in
+/// /usr/lib/sqlite3/, and a {name}-dev .deb with the headers and static library.
+pub(crate) fn write_deb(project: &Project, deb_path: &Path) -> io::Result<Vec<GeneratedAsset>> {
+    let version = debian_version(&project.version);
+    let mut assets = vec![];
+    for platform_dir in &project.platform_directories {
+        let (architecture, triplet) =
+            match (&platform_dir.os, debian_architecture(&platform_dir.cpu)) {
+                (Os::Linux, Some(architecture)) => architecture,
+                // Debian is glibc-based, so musl builds are skipped too
+                (Os::Linux | Os::LinuxMusl, _) => {
+                    warn_unsupported_platform("deb", platform_dir);
+                    continue;
+                }
+                _ => continue,
+            };
+        for package in packages(project, platform_dir, triplet) {
+            if package.files.is_empty() {
+                continue;
+            }
+            assets.push(GeneratedAsset::from(
+                GeneratedAssetKind::Deb,
+                &deb_path.join(format!("{}_{version}_{architecture}.deb", package.name)),
+                &create_deb(project, &package, architecture)?,
+            )?);
+        }
+    }
+    if assets.is_empty() {
+        println!("Warning: no linux platform directory found, skipping the deb target");
+    }
+    Ok(assets)
+}
+
+fn packages<'a>(
+    project: &Project,
+    platform_dir: &'a PlatformDirectory,
+    triplet: &str,
+) -> [Package<'a>; 2] {
+    let name = &project.spec.package.name;
+    [
+        Package {
+            name: name.clone(),
+            section: "libs",
+            depends: None,
+            files: platform_dir
+                .loadable_files
+                .iter()
+                .map(|loadable| {
+                    (
+                        format!("usr/lib/sqlite3/{}", loadable.file.name),
+                        loadable.file.data.as_slice(),
+                    )
+                })
+                .collect(),
+        },
+        Package {
+            name: format!("{name}-dev"),
+            section: "libdevel",
+            depends: (!platform_dir.loadable_files.is_empty())
+                .then(|| format!("{name} (= {})", debian_version(&project.version))),
+            files: platform_dir
+                .header_files
+                .iter()
+                .map(|file| (format!("usr/include/{}", file.name), file.data.as_slice()))
+                .chain(platform_dir.static_files.iter().map(|file| {
+                    (
+                        format!("usr/lib/{triplet}/{}", file.name),
+                        file.data.as_slice(),
+                    )
+                }))
+                .collect(),
+        },
+    ]
+}
+
+mod templates {
+    use super::{debian_version, Package};
+    use crate::Project;
+
+    pub(crate) fn control(
+        project: &Project,
+        package: &Package,
+        architecture: &str,
+        installed_size: usize,
+    ) -> String {
+        let spec_package = &project.spec.package;
+        let maintainer = spec_package.authors.first().unwrap_or(&spec_package.name);
+        // the first line is the synopsis, the rest is indented with blank lines as " ."
+        let mut lines = spec_package.description.trim().lines();
+        let synopsis = lines.next().unwrap_or(&spec_package.name);
+        let extended: String = lines
+            .map(|line| match line.trim() {
+                "" => " .\n".to_owned(),
+                line => format!(" {line}\n"),
+            })
+            .collect();
+        let depends = match &package.depends {
+            Some(depends) => format!("Depends: {depends}\n"),
+            None => String::new(),
+        };
+        format!(
+            "Package: {name}
+Version: {version}
+Architecture: {architecture}
+Maintainer: {maintainer}
+Installed-Size: {installed_size}
+{depends}Section: {section}
+Priority: optional
+Homepage: {homepage}
+Description: {synopsis}
+{extended}",
+            name = package.name,
+            version = debian_version(&project.version),
+            section = package.section,
+            homepage = spec_package.homepage,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::tests::{elf_object, EM_X86_64};
+    use crate::tests::{platform_directory, project};
+    use flate2::read::GzDecoder;
+    use object::read::archive::ArchiveFile;
+    use semver::Version;
+    use tempdir::TempDir;
+
+    fn members(ar: &[u8]) -> Vec<(String, Vec<u8>)> {
+        ArchiveFile::parse(ar)
+            .unwrap()
+            .members()
+            .map(|member| {
+                let member = member.unwrap();
+                (
+                    String::from_utf8(member.name().to_vec()).unwrap(),
+                    member.data(ar).unwrap().to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    fn tar_entries(targz: &[u8]) -> Vec<(String, u32, u64)> {
+        let mut archive = tar::Archive::new(GzDecoder::new(targz));
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let header = entry.header();
+                (
+                    String::from_utf8(entry.path_bytes().to_vec()).unwrap(),
+                    header.mode().unwrap(),
+                    header.mtime().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn debian_versions() {
+        for (version, debian) in [
+            ("1.2.3", "1.2.3"),
+            // ~ sorts before the release
+            ("1.2.3-alpha.1", "1.2.3~alpha.1"),
+            ("1.2.3+build.5", "1.2.3+build.5"),
+            ("1.2.3-rc.1+build.5", "1.2.3~rc.1+build.5"),
+        ] {
+            assert_eq!(debian_version(&Version::parse(version).unwrap()), debian);
+        }
+    }
+
+    #[test]
+    fn ar_archives() {
+        let ar = create_ar(&[("debian-binary", b"2.0\n"), ("odd", b"abc")], 1700000000);
+        assert_eq!(
+            &ar[..68],
+            b"!<arch>\ndebian-binary   1700000000  0     0     100644  4         `\n"
+        );
+        // odd sized members are padded to an even offset
+        assert_eq!(ar.len(), 8 + 60 + 4 + 60 + 3 + 1);
+        assert_eq!(
+            members(&ar),
+            vec![
+                ("debian-binary".to_owned(), b"2.0\n".to_vec()),
+                ("odd".to_owned(), b"abc".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn deb_tarball_modes() {
+        let targz = create_deb_targz(
+            &[
+                ("./usr/".to_owned(), b"".as_slice()),
+                ("./usr/lib/sample0.so".to_owned(), b"loadable"),
+            ],
+            1700000000,
+        )
+        .unwrap();
+        // tar drops the leading ./
+        assert_eq!(
+            tar_entries(&targz),
+            vec![
+                ("usr/".to_owned(), 0o755, 1700000000),
+                ("usr/lib/sample0.so".to_owned(), 0o644, 1700000000),
+            ]
+        );
+    }
+
+    #[test]
+    fn deb_packages() {
+        let mut project = project(
+            "deb = {}",
+            vec![platform_directory(
+                Os::Linux,
+                Cpu::X86_64,
+                elf_object(EM_X86_64),
+            )],
+        );
+        project.source_date_epoch = Some(1700000000);
+        let dir = TempDir::new("sqlite-dist-deb").unwrap();
+        let assets = write_deb(&project, dir.path()).unwrap();
+        // no headers or static libraries, so no -dev package
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].name, "sqlite-sample_0.1.0_amd64.deb");
+
+        let deb = std::fs::read(&assets[0].path).unwrap();
+        let members = members(&deb);
+        let names: Vec<_> = members.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["debian-binary", "control.tar.gz", "data.tar.gz"]);
+        let data: Vec<_> = tar_entries(&members[2].1)
+            .into_iter()
+            .map(|(path, ..)| path)
+            .collect();
+        assert_eq!(
+            data,
+            [
+                "usr/",
+                "usr/lib/",
+                "usr/lib/sqlite3/",
+                "usr/lib/sqlite3/sample0.so"
+            ]
+        );
+    }
+}
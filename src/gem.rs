@@ -1,33 +1,14 @@
 use crate::spec::TargetGem;
-use crate::{Cpu, Os};
+use crate::{normalize_tar_header, warn_unsupported_platform, Cpu, Os};
 use crate::{GeneratedAsset, GeneratedAssetKind, Project};
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256, Sha512};
+use std::io::Write;
 use std::io::{self, Cursor};
 use std::path::Path;
-use std::{collections::HashMap, io::Write};
 use tar::{Builder, Header};
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Gemspec {
-    name: String,
-    version: String,
-    // https://guides.rubygems.org/specification-reference/#authors=
-    authors: Vec<String>,
-    // https://guides.rubygems.org/specification-reference/#email
-    email: Vec<String>,
-    homepage: String,
-    summary: String,
-    description: String,
-    licenses: Vec<String>,
-    // https://guides.rubygems.org/specification-reference/#metadata
-    metadata: HashMap<String, String>,
-    platform: String,
-    module_name: String,
-}
-
 fn gem_checksum_sha256(data: &[u8]) -> String {
     base16ct::lower::encode_string(&Sha256::digest(data))
 }
@@ -49,25 +30,25 @@ fn gem_metadata_list_helper(items: Vec<String>) -> String {
 }
 
 fn ruby_platform(os: &Os, cpu: &Cpu) -> String {
-    let os = match os {
-        Os::Macos => "darwin",
-        Os::Linux => "linux",
-        Os::Windows => "mingw32",
-        _ => unreachable!(
-            "Invalid gem OS {:?}  provided, should have been filtered out",
-            os,
-        ),
-    };
-    let cpu = match cpu {
-        Cpu::X86_64 => "x86_64",
-        Cpu::Aarch64 => "arm64",
+    match (os, cpu) {
+        (Os::Macos, Cpu::X86_64) => "x86_64-darwin",
+        (Os::Macos, Cpu::Aarch64) => "arm64-darwin",
+        (Os::Linux, Cpu::X86_64) => "x86_64-linux",
+        (Os::Linux, Cpu::Aarch64) => "aarch64-linux",
+        (Os::LinuxMusl, Cpu::X86_64) => "x86_64-linux-musl",
+        (Os::LinuxMusl, Cpu::Aarch64) => "aarch64-linux-musl",
+        (Os::Windows, Cpu::X86_64) => "x86_64-mingw32",
+        // RubyInstaller only ships ARM64 builds against the UCRT
+        (Os::Windows, Cpu::Aarch64) => "aarch64-mingw-ucrt",
+        (Os::Windows, Cpu::I686) => "x86-mingw32",
         _ => unreachable!(
-            "Invalid gem CPU {:?}  provided, should have been filtered out",
-            cpu
+            "Invalid gem platform {:?}-{:?} provided, should have been filtered out",
+            os, cpu
         ),
-    };
-    format!("{cpu}-{os}")
+    }
+    .to_owned()
 }
+
 #[allow(clippy::too_many_arguments)]
 fn gem_metadata_template(
     os: &Os,
@@ -81,9 +62,15 @@ fn gem_metadata_template(
     description: &str,
     summary: &str,
     homepage: &str,
+    source_date_epoch: Option<u64>,
 ) -> String {
     let ruby_platform = ruby_platform(os, cpu);
-    let date = chrono::offset::Local::now().format("%Y-%m-%d").to_string();
+    let date = match source_date_epoch
+        .and_then(|epoch| chrono::DateTime::from_timestamp(epoch.try_into().ok()?, 0))
+    {
+        Some(timestamp) => timestamp.format("%Y-%m-%d").to_string(),
+        None => chrono::offset::Local::now().format("%Y-%m-%d").to_string(),
+    };
     let authors = gem_metadata_list_helper(authors);
     let files = gem_metadata_list_helper(files);
     let licenses = gem_metadata_list_helper(licenses);
@@ -161,16 +148,18 @@ SHA512:
 pub struct Gem {
     library_tarball: Builder<GzEncoder<Vec<u8>>>,
     library_filenames: Vec<String>,
+    source_date_epoch: Option<u64>,
 }
 
 impl Gem {
-    pub fn new() -> Self {
+    pub fn new(source_date_epoch: Option<u64>) -> Self {
         let tar_gz: Vec<u8> = Vec::new();
         let enc = GzEncoder::new(tar_gz, Compression::default());
         let tar = tar::Builder::new(enc);
         Self {
             library_tarball: tar,
             library_filenames: vec![],
+            source_date_epoch,
         }
     }
     pub fn write_library_file(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
@@ -179,6 +168,9 @@ impl Gem {
         header.set_path(path)?;
         header.set_size(data.len() as u64);
         header.set_mode(0o777);
+        if let Some(source_date_epoch) = self.source_date_epoch {
+            normalize_tar_header(&mut header, source_date_epoch);
+        }
         header.set_cksum();
         self.library_tarball.append::<&[u8]>(&header, data)?;
         self.library_filenames.push(path.to_string());
@@ -198,6 +190,7 @@ impl Gem {
             &project.spec.package.description,
             &project.spec.package.description,
             "https://github.com/TODO",
+            self.source_date_epoch,
         );
         let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
         encoder.write_all(metadata.as_bytes())?;
@@ -214,6 +207,9 @@ impl Gem {
         {
             let mut tar = tar::Builder::new(Cursor::new(&mut gem_tar));
             let mut header = Header::new_gnu();
+            if let Some(source_date_epoch) = self.source_date_epoch {
+                normalize_tar_header(&mut header, source_date_epoch);
+            }
 
             let metadata_gz = self.metadata_gz(os, cpu, project)?;
             header.set_path("metadata.gz")?;
@@ -256,15 +252,20 @@ pub(crate) fn write_gems(
 ) -> io::Result<Vec<GeneratedAsset>> {
     let mut assets = vec![];
     for platform_dir in &project.platform_directories {
-        if !(matches!(platform_dir.os, Os::Linux | Os::Macos | Os::Windows)
-            && matches!(platform_dir.cpu, Cpu::X86_64 | Cpu::Aarch64))
-        {
-            continue;
+        match (&platform_dir.os, &platform_dir.cpu) {
+            (Os::Linux | Os::LinuxMusl | Os::Macos | Os::Windows, Cpu::X86_64 | Cpu::Aarch64)
+            | (Os::Windows, Cpu::I686) => (),
+            _ => {
+                warn_unsupported_platform("gem", platform_dir);
+                continue;
+            }
         }
-        let mut gem = Gem::new();
+        let mut gem = Gem::new(project.source_date_epoch);
         assert!(!platform_dir.loadable_files.is_empty());
         let loadable_name = platform_dir.loadable_files[0].file.name.clone();
         let entrypoint = &platform_dir.loadable_files[0].file_stem;
+        let init_function =
+            platform_dir.loadable_files[0].entrypoint(project.spec.package.entrypoint.as_deref());
 
         gem.write_library_file(
             format!("lib/{}", loadable_name).as_str(),
@@ -273,11 +274,17 @@ pub(crate) fn write_gems(
 
         gem.write_library_file(
             format!("lib/{}.rb", project.spec.package.name.replace('-', "_")).as_str(),
-            templates::lib_rb(&project.version, entrypoint, &gem_config.module_name).as_bytes(),
+            templates::lib_rb(
+                &project.version,
+                entrypoint,
+                init_function.as_deref(),
+                &gem_config.module_name,
+            )
+            .as_bytes(),
         )?;
         let (gem_name, data) = gem.complete(&platform_dir.os, &platform_dir.cpu, project)?;
         assets.push(GeneratedAsset::from(
-            GeneratedAssetKind::Gem((platform_dir.os.clone(), platform_dir.cpu.clone())),
+            GeneratedAssetKind::Gem,
             &gem_path.join(gem_name),
             &data,
         )?);
@@ -288,7 +295,24 @@ pub(crate) fn write_gems(
 mod templates {
     use semver::Version;
 
-    pub(crate) fn lib_rb(version: &Version, entrypoint: &str, module_name: &str) -> String {
+    pub(crate) fn lib_rb(
+        version: &Version,
+        entrypoint: &str,
+        init_function: Option<&str>,
+        module_name: &str,
+    ) -> String {
+        // Database#load_extension doesn't take an entrypoint, so go through SQL
+        let load = match init_function {
+            Some(init_function) => {
+                let init_function =
+                    serde_json::to_string(&serde_json::Value::String(init_function.to_owned()))
+                        .expect("String value should always serialize as JSON");
+                format!(
+                    r#"db.execute("select load_extension(?, ?)", [self.loadable_path, {init_function}])"#
+                )
+            }
+            None => "db.load_extension(self.loadable_path)".to_owned(),
+        };
         format!(
             r#"
 module {module_name}
@@ -298,7 +322,7 @@ module {module_name}
     File.expand_path('{entrypoint}', File.dirname(__FILE__))
   end
   def self.load(db)
-    db.load_extension(self.loadable_path)
+    {load}
   end
 end
 
@@ -306,3 +330,25 @@ end
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ruby_platforms() {
+        for (os, cpu, platform) in [
+            (Os::Macos, Cpu::X86_64, "x86_64-darwin"),
+            (Os::Macos, Cpu::Aarch64, "arm64-darwin"),
+            (Os::Linux, Cpu::X86_64, "x86_64-linux"),
+            (Os::Linux, Cpu::Aarch64, "aarch64-linux"),
+            (Os::LinuxMusl, Cpu::X86_64, "x86_64-linux-musl"),
+            (Os::LinuxMusl, Cpu::Aarch64, "aarch64-linux-musl"),
+            (Os::Windows, Cpu::X86_64, "x86_64-mingw32"),
+            (Os::Windows, Cpu::Aarch64, "aarch64-mingw-ucrt"),
+            (Os::Windows, Cpu::I686, "x86-mingw32"),
+        ] {
+            assert_eq!(ruby_platform(&os, &cpu), platform);
+        }
+    }
+}
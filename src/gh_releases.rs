@@ -1,11 +1,13 @@
-use crate::PlatformDirectory;
+use crate::{build_config, PlatformDirectory};
 use crate::{
-    create_targz, GeneratedAsset, GeneratedAssetKind, GithubRelease, PlatformFile, Project,
+    create_targz, platform_name, GeneratedAsset, GeneratedAssetKind, GithubRelease, PlatformFile,
+    Project,
 };
 use std::io;
 use std::path::Path;
 
 fn create_loadable_github_release_asset(
+    project: &Project,
     platform_directory: &PlatformDirectory,
 ) -> io::Result<Vec<u8>> {
     create_targz(
@@ -14,30 +16,51 @@ fn create_loadable_github_release_asset(
             .iter()
             .map(|l| &l.file)
             .collect::<Vec<&PlatformFile>>(),
+        project.source_date_epoch,
     )
 }
 
 fn create_static_github_release_asset(
+    project: &Project,
     platform_directory: &PlatformDirectory,
 ) -> Option<io::Result<Vec<u8>>> {
-    let mut targets = vec![];
-    targets.extend(&platform_directory.static_files);
-    targets.extend(&platform_directory.header_files);
-    if !targets.is_empty() {
-        Some(create_targz(&targets))
-    } else {
-        None
+    if platform_directory.static_files.is_empty() && platform_directory.header_files.is_empty() {
+        return None;
+    }
+    // the usual include/ and lib/ layout, so the tarball can be extracted into a prefix
+    let mut files: Vec<PlatformFile> = platform_directory
+        .header_files
+        .iter()
+        .map(|file| PlatformFile {
+            name: format!("include/{}", file.name),
+            ..file.clone()
+        })
+        .chain(
+            platform_directory
+                .static_files
+                .iter()
+                .map(|file| PlatformFile {
+                    name: format!("lib/{}", file.name),
+                    ..file.clone()
+                }),
+        )
+        .collect();
+    if !platform_directory.static_files.is_empty() {
+        files.extend(build_config::static_files(project, platform_directory));
     }
+    Some(create_targz(
+        &files.iter().collect::<Vec<_>>(),
+        project.source_date_epoch,
+    ))
 }
 
 fn github_release_artifact_name(
     name: &str,
     version: &str,
-    os: &str,
-    cpu: &str,
+    platform: &str,
     artifact_type: &str,
 ) -> String {
-    format!("{name}-{version}-{artifact_type}-{os}-{cpu}.tar.gz")
+    format!("{name}-{version}-{artifact_type}-{platform}.tar.gz")
 }
 
 fn github_release_artifact_name_loadable(
@@ -46,9 +69,8 @@ fn github_release_artifact_name_loadable(
 ) -> String {
     let name = project.spec.package.name.as_str();
     let version = project.version.to_string();
-    let os = platform_dir.os.to_string();
-    let cpu = platform_dir.cpu.to_string();
-    github_release_artifact_name(name, &version, &os, &cpu, "loadable")
+    let platform = platform_name(&platform_dir.os, &platform_dir.cpu);
+    github_release_artifact_name(name, &version, &platform, "loadable")
 }
 fn github_release_artifact_name_static(
     project: &Project,
@@ -56,9 +78,8 @@ fn github_release_artifact_name_static(
 ) -> String {
     let name = project.spec.package.name.as_str();
     let version = project.version.to_string();
-    let os = platform_dir.os.to_string();
-    let cpu = platform_dir.cpu.to_string();
-    github_release_artifact_name(name, &version, &os, &cpu, "static")
+    let platform = platform_name(&platform_dir.os, &platform_dir.cpu);
+    github_release_artifact_name(name, &version, &platform, "static")
 }
 
 pub(crate) fn write_platform_files(
@@ -68,19 +89,26 @@ pub(crate) fn write_platform_files(
     let mut loadable_assets = vec![];
     let mut static_assets = vec![];
 
-    for platform_dir in &project.platform_directories {
-        let ghl = create_loadable_github_release_asset(platform_dir)?;
-        let lname = github_release_artifact_name_loadable(project, platform_dir);
-        loadable_assets.push(GeneratedAsset::from(
-            GeneratedAssetKind::GithubReleaseLoadable(GithubRelease {
-                url: project.release_download_url(&lname),
-                platform: (platform_dir.os.clone(), platform_dir.cpu.clone()),
-            }),
-            &ghreleases.join(lname),
-            &ghl,
-        )?);
+    for platform_dir in project
+        .platform_directories
+        .iter()
+        .chain(&project.macos_universal)
+    {
+        // static-only directories, like iOS ones, have no loadable asset
+        if !platform_dir.loadable_files.is_empty() {
+            let ghl = create_loadable_github_release_asset(project, platform_dir)?;
+            let lname = github_release_artifact_name_loadable(project, platform_dir);
+            loadable_assets.push(GeneratedAsset::from(
+                GeneratedAssetKind::GithubReleaseLoadable(GithubRelease {
+                    url: project.release_download_url(&lname),
+                    platform: (platform_dir.os.clone(), platform_dir.cpu.clone()),
+                }),
+                &ghreleases.join(lname),
+                &ghl,
+            )?);
+        }
 
-        if let Some(ghs) = create_static_github_release_asset(platform_dir) {
+        if let Some(ghs) = create_static_github_release_asset(project, platform_dir) {
             let sname = github_release_artifact_name_static(project, platform_dir);
             static_assets.push(GeneratedAsset::from(
                 GeneratedAssetKind::GithubReleaseStatic(GithubRelease {
@@ -0,0 +1,368 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+use zip::result::ZipError;
+
+use crate::spec::TargetGo;
+use crate::{
+    binary, create_zip, warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind,
+    LoadablePlatformFile, Os, PlatformFile, Project,
+};
+
+#[derive(Error, Debug)]
+pub enum GoError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Zipfile error: {0}")]
+    ZipError(#[from] ZipError),
+    #[error("no linux, macos or windows platform directory has a loadable library")]
+    NoLibraries,
+    #[error("version {0} needs a module path ending in /v{1}")]
+    MajorVersion(String, u64),
+}
+
+/// One embedded loadable, selected by GOOS, GOARCH and the custom `musl` tag.
+struct GoPlatform<'a> {
+    goos: &'static str,
+    goarch: &'static str,
+    musl: bool,
+    loadable: &'a LoadablePlatformFile,
+}
+
+impl GoPlatform<'_> {
+    fn name(&self) -> String {
+        let musl = if self.musl { "_musl" } else { "" };
+        format!("{}_{}{musl}", self.goos, self.goarch)
+    }
+    fn constraint(&self) -> String {
+        match (self.goos, self.musl) {
+            ("linux", true) => format!("linux && {} && musl", self.goarch),
+            ("linux", false) => format!("linux && {} && !musl", self.goarch),
+            (goos, _) => format!("{goos} && {}", self.goarch),
+        }
+    }
+}
+
+fn go_platform(os: &Os, cpu: &Cpu) -> Option<(&'static str, &'static str, bool)> {
+    let (goos, musl) = match os {
+        Os::Linux => ("linux", false),
+        // Go has no musl target, so musl builds are picked with `-tags musl`
+        Os::LinuxMusl => ("linux", true),
+        Os::Macos => ("darwin", false),
+        Os::Windows => ("windows", false),
+        _ => return None,
+    };
+    let goarch = match cpu {
+        Cpu::X86_64 => "amd64",
+        Cpu::Aarch64 => "arm64",
+        Cpu::I686 if *os == Os::Windows => "386",
+        _ => return None,
+    };
+    Some((goos, goarch, musl))
+}
+
+// sqlite-sample -> sqlitesample
+fn go_package_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .skip_while(char::is_ascii_digit)
+        .collect()
+}
+
+// module paths are case-insensitive on the proxy, so uppercase letters are escaped
+fn escape_module_path(module: &str) -> String {
+    module
+        .chars()
+        .map(|c| {
+            if c.is_ascii_uppercase() {
+                format!("!{}", c.to_ascii_lowercase())
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Writes a Go module with every desktop loadable embedded behind build
+/// constraints under go/module/, and the same module in the GOPROXY layout
+/// under go/proxy/, ready to be served with GOPROXY=file://.
+pub(crate) fn write_go(
+    project: &Project,
+    go_path: &Path,
+    go_config: &TargetGo,
+) -> Result<Vec<GeneratedAsset>, GoError> {
+    let major = project.version.major;
+    if major >= 2 && !go_config.module.ends_with(&format!("/v{major}")) {
+        return Err(GoError::MajorVersion(project.version.to_string(), major));
+    }
+
+    let mut platforms = vec![];
+    for platform_dir in &project.platform_directories {
+        let Some((goos, goarch, musl)) = go_platform(&platform_dir.os, &platform_dir.cpu) else {
+            warn_unsupported_platform("go", platform_dir);
+            continue;
+        };
+        if let Some(loadable) = platform_dir.loadable_files.first() {
+            platforms.push(GoPlatform {
+                goos,
+                goarch,
+                musl,
+                loadable,
+            });
+        }
+    }
+    let Some(first) = platforms.first() else {
+        return Err(GoError::NoLibraries);
+    };
+
+    let package = &project.spec.package;
+    let package_name = go_package_name(&package.name);
+    let entrypoint = package.entrypoint.clone().unwrap_or_else(|| {
+        binary::init_function(&first.loadable.file.name, &first.loadable.init_symbols)
+    });
+
+    let mut files = vec![
+        PlatformFile::new("go.mod", templates::go_mod(&go_config.module), None),
+        PlatformFile::new(
+            format!("{package_name}.go"),
+            templates::package_go(
+                &package.name,
+                &package_name,
+                &project.version.to_string(),
+                &entrypoint,
+            ),
+            None,
+        ),
+        PlatformFile::new(
+            "loadable_other.go",
+            templates::loadable_other_go(&package_name, &platforms),
+            None,
+        ),
+    ];
+    for platform in &platforms {
+        let embed_path = format!("native/{}/{}", platform.name(), platform.loadable.file.name);
+        files.push(PlatformFile::new(
+            format!("loadable_{}.go", platform.name()),
+            templates::loadable_go(&package_name, platform, &embed_path),
+            None,
+        ));
+        files.push(PlatformFile::new(
+            embed_path,
+            platform.loadable.file.data.clone(),
+            None,
+        ));
+    }
+
+    let module_path = go_path.join("module");
+    for file in &files {
+        let path = module_path.join(&file.name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, &file.data)?;
+    }
+
+    // https://go.dev/ref/mod#goproxy-protocol
+    let version = format!("v{}", project.version);
+    let zip_files: Vec<PlatformFile> = files
+        .iter()
+        .map(|file| {
+            PlatformFile::new(
+                format!("{}@{version}/{}", go_config.module, file.name),
+                file.data.clone(),
+                None,
+            )
+        })
+        .collect();
+    let proxy_path = go_path
+        .join("proxy")
+        .join(escape_module_path(&go_config.module))
+        .join("@v");
+    fs::create_dir_all(&proxy_path)?;
+    fs::write(proxy_path.join("list"), format!("{version}\n"))?;
+    Ok(vec![
+        GeneratedAsset::from(
+            GeneratedAssetKind::Go,
+            &proxy_path.join(format!("{version}.zip")),
+            &create_zip(&zip_files, project.source_date_epoch)?,
+        )?,
+        GeneratedAsset::from(
+            GeneratedAssetKind::Go,
+            &proxy_path.join(format!("{version}.mod")),
+            templates::go_mod(&go_config.module).as_bytes(),
+        )?,
+        GeneratedAsset::from(
+            GeneratedAssetKind::Go,
+            &proxy_path.join(format!("{version}.info")),
+            templates::info_json(&version, project.source_date_epoch).as_bytes(),
+        )?,
+    ])
+}
+
+mod templates {
+    use super::GoPlatform;
+
+    pub(crate) fn go_mod(module: &str) -> String {
+        // go:build constraint lines need Go 1.17
+        format!("module {module}\n\ngo 1.17\n")
+    }
+
+    pub(crate) fn info_json(version: &str, source_date_epoch: Option<u64>) -> String {
+        let time = source_date_epoch
+            .and_then(|epoch| chrono::DateTime::from_timestamp(epoch.try_into().ok()?, 0))
+            .unwrap_or_else(chrono::Utc::now)
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        serde_json::json!({ "Version": version, "Time": time }).to_string()
+    }
+
+    pub(crate) fn loadable_go(
+        package_name: &str,
+        platform: &GoPlatform,
+        embed_path: &str,
+    ) -> String {
+        format!(
+            r#"// Code generated by sqlite-dist. DO NOT EDIT.
+
+//go:build {constraint}
+
+package {package_name}
+
+import _ "embed"
+
+//go:embed {embed_path}
+var loadable []byte
+
+const loadableName = "{file_name}"
+"#,
+            constraint = platform.constraint(),
+            file_name = platform.loadable.file.name,
+        )
+    }
+
+    pub(crate) fn loadable_other_go(package_name: &str, platforms: &[GoPlatform]) -> String {
+        let constraint = platforms
+            .iter()
+            .map(|platform| format!("!({})", platform.constraint()))
+            .collect::<Vec<_>>()
+            .join(" && ");
+        format!(
+            r#"// Code generated by sqlite-dist. DO NOT EDIT.
+
+//go:build {constraint}
+
+package {package_name}
+
+var loadable []byte
+
+const loadableName = ""
+"#
+        )
+    }
+
+    pub(crate) fn package_go(
+        name: &str,
+        package_name: &str,
+        version: &str,
+        entrypoint: &str,
+    ) -> String {
+        format!(
+            r#"// Code generated by sqlite-dist. DO NOT EDIT.
+
+// Package {package_name} embeds the {name} SQLite extension for the
+// platforms it's built for. Build with -tags musl on musl-based Linux.
+//
+// The extension is a native library, so it can only be loaded by cgo drivers
+// like github.com/mattn/go-sqlite3. Pure Go drivers like modernc.org/sqlite
+// can't load it.
+package {package_name}
+
+import (
+	"errors"
+	"os"
+	"path/filepath"
+	"runtime"
+	"sync"
+)
+
+// Version of {name} embedded in this module.
+const Version = "{version}"
+
+// Entrypoint is the extension's init function.
+const Entrypoint = "{entrypoint}"
+
+var (
+	pathOnce sync.Once
+	path     string
+	pathErr  error
+)
+
+// Path writes the extension for the running platform to the user cache
+// directory, on the first call, and returns its location.
+func Path() (string, error) {{
+	pathOnce.Do(func() {{
+		path, pathErr = extract()
+	}})
+	return path, pathErr
+}}
+
+func extract() (string, error) {{
+	if len(loadable) == 0 {{
+		return "", errors.New("{name} has no build for " + runtime.GOOS + "/" + runtime.GOARCH)
+	}}
+	dir, err := os.UserCacheDir()
+	if err != nil {{
+		dir = os.TempDir()
+	}}
+	dir = filepath.Join(dir, "{name}", Version)
+	if err := os.MkdirAll(dir, 0o755); err != nil {{
+		return "", err
+	}}
+	target := filepath.Join(dir, loadableName)
+	if info, err := os.Stat(target); err == nil && info.Size() == int64(len(loadable)) {{
+		return target, nil
+	}}
+	// other processes may load the file while it's written, so rename it into place
+	tmp, err := os.CreateTemp(dir, loadableName+".*")
+	if err != nil {{
+		return "", err
+	}}
+	_, err = tmp.Write(loadable)
+	if closeErr := tmp.Close(); err == nil {{
+		err = closeErr
+	}}
+	if err == nil {{
+		err = os.Rename(tmp.Name(), target)
+	}}
+	if err != nil {{
+		os.Remove(tmp.Name())
+		return "", err
+	}}
+	return target, nil
+}}
+
+// ExtensionLoader is implemented by *sqlite3.SQLiteConn from
+// github.com/mattn/go-sqlite3.
+type ExtensionLoader interface {{
+	LoadExtension(lib string, entry string) error
+}}
+
+// Load loads the extension into a connection, ex in a mattn/go-sqlite3
+// ConnectHook:
+//
+//	sql.Register("sqlite3_{package_name}", &sqlite3.SQLiteDriver{{
+//		ConnectHook: func(conn *sqlite3.SQLiteConn) error {{
+//			return {package_name}.Load(conn)
+//		}},
+//	}})
+func Load(conn ExtensionLoader) error {{
+	p, err := Path()
+	if err != nil {{
+		return err
+	}}
+	return conn.LoadExtension(p, Entrypoint)
+}}
+"#
+        )
+    }
+}
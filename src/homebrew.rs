@@ -0,0 +1,240 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::spec::camel_case;
+use crate::{binary, Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
+
+/// A release tarball and its sha256, as a formula url or resource.
+struct Download<'a> {
+    url: &'a str,
+    sha256: &'a str,
+}
+
+/// The downloads for one on_macos/on_linux + on_arm/on_intel block.
+struct Bottle<'a> {
+    os: &'static str,
+    arch: &'static str,
+    loadable: Download<'a>,
+    // the static tarball, for its headers
+    static_: Option<Download<'a>>,
+}
+
+fn download<'a>(
+    gh_release_assets: &'a [GeneratedAsset],
+    platform: &(Os, Cpu),
+    loadable: bool,
+) -> Option<Download<'a>> {
+    gh_release_assets
+        .iter()
+        .find_map(|asset| match &asset.kind {
+            GeneratedAssetKind::GithubReleaseLoadable(release)
+                if loadable && release.platform == *platform =>
+            {
+                Some(Download {
+                    url: &release.url,
+                    sha256: &asset.checksum_sha256,
+                })
+            }
+            GeneratedAssetKind::GithubReleaseStatic(release)
+                if !loadable && release.platform == *platform =>
+            {
+                Some(Download {
+                    url: &release.url,
+                    sha256: &asset.checksum_sha256,
+                })
+            }
+            _ => None,
+        })
+}
+
+/// Writes Formula/{name}.rb for a Homebrew tap, installing the macOS and Linux
+/// loadable release tarballs into lib/ and the static tarballs' headers into
+/// include/.
+pub(crate) fn write_homebrew(
+    project: &Project,
+    homebrew_path: &Path,
+    gh_release_assets: &[GeneratedAsset],
+) -> io::Result<Vec<GeneratedAsset>> {
+    let mut bottles = vec![];
+    for (os, homebrew_os) in [(Os::Macos, "macos"), (Os::Linux, "linux")] {
+        for (cpu, homebrew_arch) in [(Cpu::Aarch64, "arm"), (Cpu::X86_64, "intel")] {
+            let platform = (os.clone(), cpu);
+            // the universal build covers both macOS architectures
+            let loadable = download(gh_release_assets, &platform, true)
+                .or_else(|| download(gh_release_assets, &(os.clone(), Cpu::Universal), true));
+            if let Some(loadable) = loadable {
+                bottles.push(Bottle {
+                    os: homebrew_os,
+                    arch: homebrew_arch,
+                    loadable,
+                    static_: download(gh_release_assets, &platform, false),
+                });
+            }
+        }
+    }
+    if bottles.is_empty() {
+        println!("Warning: no macos or linux loadable found, skipping the homebrew target");
+        return Ok(vec![]);
+    }
+
+    let entrypoint = project.spec.package.entrypoint.clone().unwrap_or_else(|| {
+        project
+            .platform_directories
+            .iter()
+            .filter(|platform_dir| matches!(platform_dir.os, Os::Macos | Os::Linux))
+            .find_map(|platform_dir| platform_dir.loadable_files.first())
+            .map(|loadable| binary::init_function(&loadable.file.name, &loadable.init_symbols))
+            .unwrap_or_default()
+    });
+
+    let formula_path = homebrew_path.join("Formula");
+    fs::create_dir_all(&formula_path)?;
+    Ok(vec![GeneratedAsset::from(
+        GeneratedAssetKind::Homebrew,
+        &formula_path.join(format!("{}.rb", project.spec.package.name)),
+        templates::formula_rb(project, &bottles, &entrypoint).as_bytes(),
+    )?])
+}
+
+mod templates {
+    use super::{camel_case, Bottle, Download};
+    use crate::Project;
+
+    pub(super) fn ruby_string(value: &str) -> String {
+        // "#{" interpolates in Ruby strings, and JSON escapes are valid Ruby ones
+        serde_json::Value::String(value.to_owned())
+            .to_string()
+            .replace('#', "\\#")
+    }
+
+    fn download_lines(download: &Download, indent: &str) -> String {
+        format!(
+            "{indent}url \"{}\"\n{indent}sha256 \"{}\"\n",
+            download.url, download.sha256
+        )
+    }
+
+    fn os_block(os: &str, bottles: &[&Bottle]) -> String {
+        if bottles.is_empty() {
+            return String::new();
+        }
+        let arches: String = bottles
+            .iter()
+            .map(|bottle| {
+                let resource = match &bottle.static_ {
+                    Some(static_) => format!(
+                        "\n      resource \"static\" do\n{}      end\n",
+                        download_lines(static_, "        ")
+                    ),
+                    None => String::new(),
+                };
+                format!(
+                    "    on_{} do\n{}{resource}    end\n",
+                    bottle.arch,
+                    download_lines(&bottle.loadable, "      ")
+                )
+            })
+            .collect();
+        format!("\n  on_{os} do\n{arches}  end\n")
+    }
+
+    pub(crate) fn formula_rb(project: &Project, bottles: &[Bottle], entrypoint: &str) -> String {
+        let package = &project.spec.package;
+        // brew audit rejects descriptions ending in a period
+        let desc = package.description.trim().trim_end_matches('.');
+        let platforms: String = ["macos", "linux"]
+            .into_iter()
+            .map(|os| {
+                os_block(
+                    os,
+                    &bottles
+                        .iter()
+                        .filter(|bottle| bottle.os == os)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        format!(
+            r##"# Generated by sqlite-dist.
+class {class_name} < Formula
+  desc {desc}
+  homepage {homepage}
+  version "{version}"
+  license {license}
+
+  depends_on "sqlite" => :test
+{platforms}
+  def install
+    lib.install Dir["*.{{dylib,so}}"]
+    resources.each do |r|
+      r.stage {{ include.install Dir["include/*"] }}
+    end
+  end
+
+  test do
+    loadable = Dir[lib/"*.{{dylib,so}}"].first
+    output = pipe_output("#{{Formula["sqlite"].opt_bin}}/sqlite3", ".load #{{loadable}} {entrypoint}\nselect 'ok';")
+    assert_equal "ok", output.strip
+  end
+end
+"##,
+            class_name = camel_case(&package.name),
+            desc = ruby_string(desc),
+            homepage = ruby_string(&package.homepage),
+            version = project.version,
+            license = ruby_string(&package.license),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{project, release_asset};
+    use tempdir::TempDir;
+
+    #[test]
+    fn ruby_strings() {
+        assert_eq!(
+            templates::ruby_string("say \"hi\" #{name}"),
+            r#""say \"hi\" \#{name}""#
+        );
+    }
+
+    #[test]
+    fn formula_blocks() {
+        let mut project = project("homebrew = {}", vec![]);
+        project.spec.package.entrypoint = Some("sqlite3_sample_init".to_owned());
+        let assets = [
+            release_asset(true, (Os::Macos, Cpu::Universal), "aaaa"),
+            release_asset(true, (Os::Linux, Cpu::X86_64), "bbbb"),
+            release_asset(false, (Os::Linux, Cpu::X86_64), "cccc"),
+            release_asset(true, (Os::Windows, Cpu::X86_64), "dddd"),
+        ];
+        let dir = TempDir::new("sqlite-dist-homebrew").unwrap();
+        write_homebrew(&project, dir.path(), &assets).unwrap();
+        let formula = fs::read_to_string(dir.path().join("Formula/sqlite-sample.rb")).unwrap();
+
+        assert!(formula.contains("class SqliteSample < Formula\n"));
+        assert!(formula.contains("  desc \"A sample SQLite extension\"\n"));
+        // the universal tarball covers both macOS architectures
+        assert_eq!(formula.matches("sha256 \"aaaa\"").count(), 2);
+        assert!(formula.contains(
+            "  on_linux do\n    on_intel do\n      url \"https://github.com/asg017/sqlite-sample/releases/download/v0.1.0/sqlite-sample-0.1.0-loadable-linux-x86_64.tar.gz\"\n      sha256 \"bbbb\"\n\n      resource \"static\" do\n        url \"https://github.com/asg017/sqlite-sample/releases/download/v0.1.0/sqlite-sample-0.1.0-static-linux-x86_64.tar.gz\"\n        sha256 \"cccc\"\n      end\n    end\n  end\n"
+        ));
+        assert!(!formula.contains("dddd"));
+        assert!(formula.contains(".load #{loadable} sqlite3_sample_init\\n"));
+    }
+
+    #[test]
+    fn skipped_without_macos_or_linux_releases() {
+        let project = project("homebrew = {}", vec![]);
+        let assets = [release_asset(true, (Os::Windows, Cpu::X86_64), "dddd")];
+        let dir = TempDir::new("sqlite-dist-homebrew").unwrap();
+        assert!(write_homebrew(&project, dir.path(), &assets)
+            .unwrap()
+            .is_empty());
+        assert!(!dir.path().join("Formula").exists());
+    }
+}
@@ -0,0 +1,457 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use thiserror::Error;
+
+use crate::spec::{camel_case, Spec};
+
+pub const SPEC_FILE_NAME: &str = "sqlite-dist.toml";
+
+/// Platform directories created by `init --input`, matching what
+/// `PlatformDirectory::from_path` expects.
+const DEFAULT_PLATFORMS: [&str; 5] = [
+    "macos-x86_64",
+    "macos-aarch64",
+    "linux-x86_64",
+    "linux-aarch64",
+    "windows-x86_64",
+];
+
+#[derive(Error, Debug)]
+pub enum InitError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("{0} already exists, pass --force to overwrite it")]
+    AlreadyExists(String),
+    #[error("generated spec is invalid: {0}")]
+    InvalidTemplate(#[from] toml::de::Error),
+}
+
+/// `[package]` values pre-filled from existing project metadata.
+#[derive(Default)]
+struct PackageDefaults {
+    name: Option<String>,
+    description: Option<String>,
+    license: Option<String>,
+    homepage: Option<String>,
+    repo: Option<String>,
+    authors: Vec<String>,
+}
+
+impl PackageDefaults {
+    /// Fill in any missing values from `other`.
+    fn merge(&mut self, other: PackageDefaults) {
+        self.name = self.name.take().or(other.name);
+        self.description = self.description.take().or(other.description);
+        self.license = self.license.take().or(other.license);
+        self.homepage = self.homepage.take().or(other.homepage);
+        self.repo = self.repo.take().or(other.repo);
+        if self.authors.is_empty() {
+            self.authors = other.authors;
+        }
+    }
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn toml_str(value: &toml::Value, key: &str) -> Option<String> {
+    value.get(key)?.as_str().map(str::to_owned)
+}
+
+// Cargo.toml authors are "Name <email>", only the name is kept
+fn strip_email(author: &str) -> String {
+    author
+        .split_once('<')
+        .map_or(author, |(name, _)| name)
+        .trim()
+        .to_owned()
+}
+
+fn from_cargo_toml(dir: &Path) -> PackageDefaults {
+    let Some(package) = read_toml(&dir.join("Cargo.toml")).and_then(|v| v.get("package").cloned())
+    else {
+        return PackageDefaults::default();
+    };
+    PackageDefaults {
+        name: toml_str(&package, "name"),
+        description: toml_str(&package, "description"),
+        license: toml_str(&package, "license"),
+        homepage: toml_str(&package, "homepage"),
+        repo: toml_str(&package, "repository"),
+        authors: package
+            .get("authors")
+            .and_then(|a| a.as_array())
+            .map(|authors| {
+                authors
+                    .iter()
+                    .filter_map(|a| a.as_str())
+                    .map(strip_email)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn from_package_json(dir: &Path) -> PackageDefaults {
+    let Some(package) = fs::read_to_string(dir.join("package.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+    else {
+        return PackageDefaults::default();
+    };
+    let string = |key: &str| package.get(key)?.as_str().map(str::to_owned);
+    // "repository" and "author" can either be a string or an object
+    let repo = package.get("repository").and_then(|r| {
+        r.as_str()
+            .or_else(|| r.get("url")?.as_str())
+            .map(normalize_git_url)
+    });
+    let author = package.get("author").and_then(|a| {
+        a.as_str()
+            .map(strip_email)
+            .or_else(|| a.get("name")?.as_str().map(str::to_owned))
+    });
+    PackageDefaults {
+        name: string("name"),
+        description: string("description"),
+        license: string("license"),
+        homepage: string("homepage"),
+        repo,
+        authors: author.into_iter().collect(),
+    }
+}
+
+fn from_pyproject_toml(dir: &Path) -> PackageDefaults {
+    let Some(project) =
+        read_toml(&dir.join("pyproject.toml")).and_then(|v| v.get("project").cloned())
+    else {
+        return PackageDefaults::default();
+    };
+    let urls = project.get("urls");
+    let url = |key: &str| urls.and_then(|urls| toml_str(urls, key));
+    // license = "MIT" or license = { text = "MIT" }
+    let license = project.get("license").and_then(|l| {
+        l.as_str()
+            .or_else(|| l.get("text")?.as_str())
+            .map(str::to_owned)
+    });
+    PackageDefaults {
+        name: toml_str(&project, "name"),
+        description: toml_str(&project, "description"),
+        license,
+        homepage: url("Homepage").or_else(|| url("homepage")),
+        repo: url("Repository")
+            .or_else(|| url("repository"))
+            .or_else(|| url("Source")),
+        authors: project
+            .get("authors")
+            .and_then(|a| a.as_array())
+            .map(|authors| authors.iter().filter_map(|a| toml_str(a, "name")).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// git@github.com:owner/repo.git -> https://github.com/owner/repo
+fn normalize_git_url(url: &str) -> String {
+    let url = url.trim().trim_start_matches("git+");
+    let url = match url.strip_prefix("git@") {
+        Some(rest) => format!("https://{}", rest.replacen(':', "/", 1)),
+        None => url.replace("git://", "https://"),
+    };
+    url.trim_end_matches(".git").to_owned()
+}
+
+fn from_git_remote(dir: &Path) -> PackageDefaults {
+    let repo = Command::new("git")
+        .args(["config", "--get", "remote.origin.url"])
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|url| normalize_git_url(&url))
+        .filter(|url| !url.is_empty());
+    PackageDefaults {
+        name: repo
+            .as_ref()
+            .and_then(|repo| repo.rsplit('/').next())
+            .map(str::to_owned),
+        repo,
+        ..Default::default()
+    }
+}
+
+fn detect_package_defaults(dir: &Path) -> PackageDefaults {
+    let mut defaults = from_cargo_toml(dir);
+    defaults.merge(from_package_json(dir));
+    defaults.merge(from_pyproject_toml(dir));
+    defaults.merge(from_git_remote(dir));
+    defaults
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_owned()).to_string()
+}
+
+fn spec_template(defaults: &PackageDefaults, fallback_name: &str) -> String {
+    let name = defaults.name.as_deref().unwrap_or(fallback_name);
+    let repo = defaults
+        .repo
+        .clone()
+        .unwrap_or_else(|| format!("https://github.com/TODO/{name}"));
+    let homepage = quote(defaults.homepage.as_deref().unwrap_or(&repo));
+    let description = quote(
+        defaults
+            .description
+            .as_deref()
+            .unwrap_or("TODO description of the SQLite extension"),
+    );
+    let license = quote(defaults.license.as_deref().unwrap_or("MIT OR Apache-2.0"));
+    let authors = if defaults.authors.is_empty() {
+        vec![quote("TODO")]
+    } else {
+        defaults.authors.iter().map(|a| quote(a)).collect()
+    }
+    .join(", ");
+    let module_name = quote(&camel_case(name));
+    let source_name = name.to_owned();
+    let (name, repo) = (quote(name), quote(&repo));
+
+    format!(
+        r#"# sqlite-dist configuration. Build with:
+#
+#   sqlite-dist sqlite-dist.toml --input dist/ --output out/ --version 0.1.0
+#
+# The input directory contains one $OS-$CPU directory per platform (ex
+# linux-x86_64, macos-aarch64, windows-x86_64, or linux-x86_64-musl for Alpine)
+# holding the loadable extension (.so/.dylib/.dll), and optionally static
+# libraries (.a) and headers (.h).
+
+[package]
+name = {name}
+description = {description}
+license = {license}
+homepage = {homepage}
+repo = {repo}
+authors = [{authors}]
+# Format of the git tag of a release, defaults to the version itself.
+# git_tag_format = "v$VERSION"
+
+[targets]
+# .tar.gz archives of the loadable and static builds for GitHub releases,
+# plus checksums.txt and install.sh
+github_releases = {{}}
+
+# sqlpkg.json for sqlpkg, requires github_releases
+sqlpkg = {{}}
+
+# spm.json for the sqlite package manager, requires github_releases
+spm = {{}}
+
+# Source files bundled into .tar.gz/.zip archives, requires github_releases.
+# merge = true combines them into a single {source_name}.c and {source_name}.h
+# amalgamation = {{ include = ["{source_name}.c", "{source_name}.h"], merge = false }}
+
+# Python wheels for PyPI. extra_init_py is appended to the generated __init__.py,
+# pyodide_abi sets the ABI of the wheel built from the pyodide/ input directory.
+pip = {{}}
+# pip = {{ extra_init_py = "extra_init.py", pyodide_abi = "pyodide_2024_0" }}
+
+# Datasette plugin wheel, requires pip
+datasette = {{}}
+
+# sqlite-utils plugin wheel, requires pip
+sqlite_utils = {{}}
+
+# npm packages, one per platform plus a top-level package
+npm = {{}}
+
+# Ruby gems, one per platform
+gem = {{ module_name = {module_name} }}
+
+# Apple XCFramework zip of the static libraries in the ios-*, iossimulator-*
+# and macos-* platform directories
+# xcframework = {{ module_name = {module_name} }}
+
+# Package.swift with a binaryTarget for the XCFramework zip and a Swift
+# load(into:) helper, requires xcframework and github_releases
+# swift_package = {{}}
+
+# Android .aar of the android-* loadables with a Maven POM
+# android = {{ group_id = "io.github.example", min_sdk_version = 21 }}
+
+# Maven repository with a .jar of the linux, macos and windows loadables and a
+# loader class for sqlite-jdbc
+# maven = {{ group_id = "io.github.example" }}
+
+# NuGet package with runtimes/<rid>/native/ loadables and a C# helper for
+# Microsoft.Data.Sqlite
+# nuget = {{}}
+
+# Go module embedding the linux, macos and windows loadables, in both a source
+# tree and the GOPROXY layout
+# go = {{ module = "github.com/example/{source_name}/bindings/go" }}
+
+# {source_name}-sys crate whose build script links the static release
+# tarballs, requires github_releases
+# cargo = {{ vendored = false }}
+
+# Homebrew tap with a Formula/{source_name}.rb for the macos and linux
+# loadables, requires github_releases
+# homebrew = {{}}
+
+# flake.nix and default.nix packaging the macos and linux release tarballs,
+# requires github_releases
+# nix = {{}}
+
+# Debian packages for the linux loadables, with a -dev package for the headers
+# and static libraries
+# deb = {{}}
+
+# RPM packages for the linux loadables
+# rpm = {{}}
+
+# Alpine packages and an APKINDEX.tar.gz for the linux musl loadables, signed
+# when private_key points to an abuild RSA key
+# apk = {{}}
+"#
+    )
+}
+
+pub(crate) fn init(dir: &Path, input_dir: Option<&Path>, force: bool) -> Result<(), InitError> {
+    let spec_path = dir.join(SPEC_FILE_NAME);
+    if spec_path.exists() && !force {
+        return Err(InitError::AlreadyExists(spec_path.display().to_string()));
+    }
+
+    fs::create_dir_all(dir)?;
+    let fallback_name = dir
+        .canonicalize()?
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("sqlite-extension")
+        .to_owned();
+    let template = spec_template(&detect_package_defaults(dir), &fallback_name);
+    // the template must always round-trip through the spec parser
+    toml::from_str::<Spec>(&template)?;
+
+    fs::write(&spec_path, template)?;
+    println!("Wrote {}", spec_path.display());
+
+    if let Some(input_dir) = input_dir {
+        for platform in DEFAULT_PLATFORMS {
+            let platform_dir = input_dir.join(platform);
+            fs::create_dir_all(&platform_dir)?;
+            println!("Created {}", platform_dir.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn project_directory(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new("sqlite-dist-init").unwrap();
+        for (path, contents) in files {
+            fs::write(dir.path().join(path), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn normalizes_git_urls() {
+        for url in [
+            "git@github.com:asg017/sqlite-vec.git",
+            "git+https://github.com/asg017/sqlite-vec.git",
+            "git://github.com/asg017/sqlite-vec.git",
+            "https://github.com/asg017/sqlite-vec\n",
+        ] {
+            assert_eq!(
+                normalize_git_url(url),
+                "https://github.com/asg017/sqlite-vec"
+            );
+        }
+        assert_eq!(strip_email("Alex Garcia <alex@example.com>"), "Alex Garcia");
+        assert_eq!(strip_email("Alex Garcia"), "Alex Garcia");
+    }
+
+    #[test]
+    fn detects_defaults_from_project_metadata() {
+        let dir = project_directory(&[
+            (
+                "Cargo.toml",
+                r#"[package]
+name = "sqlite-vec"
+authors = ["Alex Garcia <alex@example.com>"]
+"#,
+            ),
+            (
+                "package.json",
+                r#"{"name": "ignored", "description": "vector search", "repository": {"url": "git+https://github.com/asg017/sqlite-vec.git"}}"#,
+            ),
+            (
+                "pyproject.toml",
+                r#"[project]
+license = { text = "MIT" }
+urls = { Homepage = "https://alexgarcia.xyz/sqlite-vec" }
+"#,
+            ),
+        ]);
+        let defaults = detect_package_defaults(dir.path());
+        assert_eq!(defaults.name.as_deref(), Some("sqlite-vec"));
+        assert_eq!(defaults.description.as_deref(), Some("vector search"));
+        assert_eq!(defaults.license.as_deref(), Some("MIT"));
+        assert_eq!(
+            defaults.homepage.as_deref(),
+            Some("https://alexgarcia.xyz/sqlite-vec")
+        );
+        assert_eq!(
+            defaults.repo.as_deref(),
+            Some("https://github.com/asg017/sqlite-vec")
+        );
+        assert_eq!(defaults.authors, vec!["Alex Garcia"]);
+    }
+
+    #[test]
+    fn spec_template_parses() {
+        let defaults = PackageDefaults {
+            description: Some("quotes \" and \\ backslashes".to_owned()),
+            ..Default::default()
+        };
+        let spec: Spec = toml::from_str(&spec_template(&defaults, "sqlite-vec")).unwrap();
+        assert_eq!(spec.package.name, "sqlite-vec");
+        assert_eq!(spec.package.description, "quotes \" and \\ backslashes");
+        assert_eq!(spec.package.repo, "https://github.com/TODO/sqlite-vec");
+        assert_eq!(spec.package.homepage, "https://github.com/TODO/sqlite-vec");
+        assert!(spec.targets.gem.is_some());
+        assert!(spec.targets.deb.is_none());
+    }
+
+    #[test]
+    fn init_does_not_overwrite_without_force() {
+        let dir = project_directory(&[(SPEC_FILE_NAME, "# existing\n")]);
+        assert!(matches!(
+            init(dir.path(), None, false),
+            Err(InitError::AlreadyExists(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(dir.path().join(SPEC_FILE_NAME)).unwrap(),
+            "# existing\n"
+        );
+
+        let input = dir.path().join("dist");
+        init(dir.path(), Some(&input), true).unwrap();
+        toml::from_str::<Spec>(&fs::read_to_string(dir.path().join(SPEC_FILE_NAME)).unwrap())
+            .unwrap();
+        for platform in DEFAULT_PLATFORMS {
+            assert!(input.join(platform).is_dir());
+        }
+    }
+}
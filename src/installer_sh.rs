@@ -1,7 +1,7 @@
 pub(crate) mod templates {
     use std::collections::HashSet;
 
-    use crate::{Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
+    use crate::{platform_name, Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
 
     struct Case {
         os: Os,
@@ -16,10 +16,9 @@ pub(crate) mod templates {
             .iter()
             .filter_map(|asset| match &asset.kind {
                 GeneratedAssetKind::GithubReleaseLoadable(gh_release)
-                | GeneratedAssetKind::GithubReleaseStatic(gh_release) => Some(format!(
-                    "{}-{}",
-                    gh_release.platform.0.to_string(),
-                    gh_release.platform.1.to_string()
+                | GeneratedAssetKind::GithubReleaseStatic(gh_release) => Some(platform_name(
+                    &gh_release.platform.0,
+                    &gh_release.platform.1,
                 )),
                 _ => None,
             })
@@ -50,8 +49,19 @@ pub(crate) mod templates {
             })
             .collect();
 
-        let usage = part_usage(&project.spec.package.name, project.version.to_string().as_str(), &targets);
-        let current_target = part_current_target();
+        let usage = part_usage(
+            &project.spec.package.name,
+            project.version.to_string().as_str(),
+            &targets,
+        );
+        let has_macos_universal = assets.iter().any(|asset| {
+            matches!(
+                &asset.kind,
+                GeneratedAssetKind::GithubReleaseLoadable(gh_release)
+                    if gh_release.platform == (Os::Macos, Cpu::Universal)
+            )
+        });
+        let current_target = part_current_target(has_macos_universal);
         let process_arguments = part_process_arguments();
         let main = part_main(cases);
         format!(
@@ -105,23 +115,43 @@ EOF
 "#
         )
     }
-    fn part_current_target() -> String {
-        r#"
-current_target() {
+    fn part_current_target(has_macos_universal: bool) -> String {
+        // the universal build also works from Rosetta shells, where uname lies
+        let (macos_x86_64, macos_aarch64) = if has_macos_universal {
+            ("macos-universal", "macos-universal")
+        } else {
+            ("macos-x86_64", "macos-aarch64")
+        };
+        format!(
+            r#"
+current_target() {{
   if [ "$OS" = "Windows_NT" ]; then
-    # TODO disambiguate between x86 and arm windows
-    target="windows-x86_64"
+    # PROCESSOR_ARCHITEW6432 is set when a 32-bit shell runs on 64-bit Windows
+    case "${{PROCESSOR_ARCHITEW6432:-$PROCESSOR_ARCHITECTURE}}" in
+    ARM64) target="windows-aarch64" ;;
+    x86) target="windows-i686" ;;
+    *) target="windows-x86_64" ;;
+    esac
     return 0
   fi
   case $(uname -sm) in
-  "Darwin x86_64") target=macos-x86_64 ;;
-  "Darwin arm64") target=macos-aarch64 ;;
+  "Darwin x86_64") target={macos_x86_64} ;;
+  "Darwin arm64") target={macos_aarch64} ;;
   "Linux x86_64") target=linux-x86_64 ;;
+  "Linux aarch64") target=linux-aarch64 ;;
   *) target=$(uname -sm);;
   esac
-}
+  # musl-based distributions like Alpine get their own builds
+  case "$target" in
+  linux-*)
+    if ldd --version 2>&1 | grep -q musl; then
+      target="$target-musl"
+    fi
+    ;;
+  esac
+}}
 "#
-        .to_owned()
+        )
     }
     fn part_process_arguments() -> String {
         (r#"
@@ -174,12 +204,11 @@ process_arguments() {
 
     fn case(case: &Case) -> String {
         format!(
-            r#"    "{os}-{cpu}-{t}")
+            r#"    "{platform}-{t}")
       url="{url}"
       checksum="{checksum}"
       ;;"#,
-            os = case.os.to_string(),
-            cpu = case.cpu.to_string(),
+            platform = platform_name(&case.os, &case.cpu),
             t = case.type_,
             url = case.url,
             checksum = case.checksum
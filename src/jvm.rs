@@ -0,0 +1,915 @@
+// Just enough of a JVM class file writer for the small generated loader classes,
+// since there's no javac to rely on at build time.
+
+use std::collections::HashMap;
+
+pub(crate) const ACC_PUBLIC: u16 = 0x0001;
+pub(crate) const ACC_PRIVATE: u16 = 0x0002;
+pub(crate) const ACC_STATIC: u16 = 0x0008;
+pub(crate) const ACC_FINAL: u16 = 0x0010;
+pub(crate) const ACC_SUPER: u16 = 0x0020;
+pub(crate) const ACC_SYNCHRONIZED: u16 = 0x0020;
+
+// Java 5 class files are checked by the type-inferring verifier, so methods
+// don't need StackMapTable attributes. Android's D8 and every JVM accept them.
+const MAJOR_VERSION: u16 = 49;
+
+#[derive(Default)]
+struct ConstantPool {
+    entries: Vec<Vec<u8>>,
+    indices: HashMap<Vec<u8>, u16>,
+}
+
+impl ConstantPool {
+    fn add(&mut self, entry: Vec<u8>) -> u16 {
+        if let Some(index) = self.indices.get(&entry) {
+            return *index;
+        }
+        let index = self.entries.len() as u16 + 1;
+        self.entries.push(entry.clone());
+        self.indices.insert(entry, index);
+        index
+    }
+    fn utf8(&mut self, value: &str) -> u16 {
+        let mut entry = vec![1];
+        entry.extend((value.len() as u16).to_be_bytes());
+        entry.extend(value.as_bytes());
+        self.add(entry)
+    }
+    fn with_indices(&mut self, tag: u8, indices: &[u16]) -> u16 {
+        let mut entry = vec![tag];
+        for index in indices {
+            entry.extend(index.to_be_bytes());
+        }
+        self.add(entry)
+    }
+    fn class(&mut self, name: &str) -> u16 {
+        let name = self.utf8(name);
+        self.with_indices(7, &[name])
+    }
+    fn string(&mut self, value: &str) -> u16 {
+        let value = self.utf8(value);
+        self.with_indices(8, &[value])
+    }
+    fn member(&mut self, tag: u8, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class = self.class(class);
+        let name = self.utf8(name);
+        let descriptor = self.utf8(descriptor);
+        let name_and_type = self.with_indices(12, &[name, descriptor]);
+        self.with_indices(tag, &[class, name_and_type])
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.entries.len() as u16 + 1).to_be_bytes().to_vec();
+        for entry in &self.entries {
+            bytes.extend(entry);
+        }
+        bytes
+    }
+}
+
+/// A jump target inside a method, bound to an offset with `Code::bind`.
+#[derive(Clone, Copy)]
+pub(crate) struct Label(usize);
+
+/// Bytecode of one method. max_stack and max_locals are given by the caller.
+pub(crate) struct Code<'a> {
+    pool: &'a mut ConstantPool,
+    this_class: &'a str,
+    bytes: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    // offset of each jump instruction and the label it goes to
+    jumps: Vec<(usize, Label)>,
+}
+
+impl Code<'_> {
+    fn op(&mut self, op: u8) -> &mut Self {
+        self.bytes.push(op);
+        self
+    }
+    fn op_u16(&mut self, op: u8, index: u16) -> &mut Self {
+        self.bytes.push(op);
+        self.bytes.extend(index.to_be_bytes());
+        self
+    }
+    fn member_class<'c>(&'c self, class: Option<&'c str>) -> &'c str {
+        class.unwrap_or(self.this_class)
+    }
+
+    pub(crate) fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+    pub(crate) fn bind(&mut self, label: Label) -> &mut Self {
+        self.labels[label.0] = Some(self.bytes.len());
+        self
+    }
+    fn jump(&mut self, op: u8, label: Label) -> &mut Self {
+        self.jumps.push((self.bytes.len(), label));
+        self.op_u16(op, 0)
+    }
+    pub(crate) fn ifeq(&mut self, label: Label) -> &mut Self {
+        self.jump(0x99, label)
+    }
+    pub(crate) fn ifne(&mut self, label: Label) -> &mut Self {
+        self.jump(0x9a, label)
+    }
+    pub(crate) fn ifnull(&mut self, label: Label) -> &mut Self {
+        self.jump(0xc6, label)
+    }
+    pub(crate) fn ifnonnull(&mut self, label: Label) -> &mut Self {
+        self.jump(0xc7, label)
+    }
+    pub(crate) fn goto(&mut self, label: Label) -> &mut Self {
+        self.jump(0xa7, label)
+    }
+
+    pub(crate) fn aload(&mut self, local: u8) -> &mut Self {
+        self.bytes.extend([0x19, local]);
+        self
+    }
+    pub(crate) fn astore(&mut self, local: u8) -> &mut Self {
+        self.bytes.extend([0x3a, local]);
+        self
+    }
+    /// Pushes a small int constant, -1 to 5.
+    pub(crate) fn iconst(&mut self, value: i8) -> &mut Self {
+        assert!((-1..=5).contains(&value));
+        self.op((0x03 + value) as u8)
+    }
+    pub(crate) fn aconst_null(&mut self) -> &mut Self {
+        self.op(0x01)
+    }
+    pub(crate) fn ldc_string(&mut self, value: &str) -> &mut Self {
+        let index = self.pool.string(value);
+        self.op_u16(0x13, index)
+    }
+    /// Pushes the Class object of `class`, or of the class being written.
+    pub(crate) fn ldc_class(&mut self, class: Option<&str>) -> &mut Self {
+        let class = self.member_class(class).to_owned();
+        let index = self.pool.class(&class);
+        self.op_u16(0x13, index)
+    }
+    pub(crate) fn new_object(&mut self, class: &str) -> &mut Self {
+        let index = self.pool.class(class);
+        self.op_u16(0xbb, index)
+    }
+    pub(crate) fn anewarray(&mut self, class: &str) -> &mut Self {
+        let index = self.pool.class(class);
+        self.op_u16(0xbd, index)
+    }
+    pub(crate) fn dup(&mut self) -> &mut Self {
+        self.op(0x59)
+    }
+    pub(crate) fn pop(&mut self) -> &mut Self {
+        self.op(0x57)
+    }
+    pub(crate) fn pop2(&mut self) -> &mut Self {
+        self.op(0x58)
+    }
+
+    /// Static fields and methods default to the class being written when
+    /// `class` is None.
+    pub(crate) fn getstatic(
+        &mut self,
+        class: Option<&str>,
+        name: &str,
+        descriptor: &str,
+    ) -> &mut Self {
+        let class = self.member_class(class).to_owned();
+        let index = self.pool.member(9, &class, name, descriptor);
+        self.op_u16(0xb2, index)
+    }
+    pub(crate) fn putstatic(
+        &mut self,
+        class: Option<&str>,
+        name: &str,
+        descriptor: &str,
+    ) -> &mut Self {
+        let class = self.member_class(class).to_owned();
+        let index = self.pool.member(9, &class, name, descriptor);
+        self.op_u16(0xb3, index)
+    }
+    pub(crate) fn getfield(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.member(9, class, name, descriptor);
+        self.op_u16(0xb4, index)
+    }
+    pub(crate) fn invokevirtual(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.member(10, class, name, descriptor);
+        self.op_u16(0xb6, index)
+    }
+    pub(crate) fn invokespecial(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.member(10, class, name, descriptor);
+        self.op_u16(0xb7, index)
+    }
+    pub(crate) fn invokestatic(
+        &mut self,
+        class: Option<&str>,
+        name: &str,
+        descriptor: &str,
+    ) -> &mut Self {
+        let class = self.member_class(class).to_owned();
+        let index = self.pool.member(10, &class, name, descriptor);
+        self.op_u16(0xb8, index)
+    }
+    /// `arguments` counts the receiver, and two for long or double arguments.
+    pub(crate) fn invokeinterface(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        arguments: u8,
+    ) -> &mut Self {
+        let index = self.pool.member(11, class, name, descriptor);
+        self.op_u16(0xb9, index);
+        self.bytes.extend([arguments, 0]);
+        self
+    }
+    /// `String.concat(String)` on the top two strings of the stack.
+    pub(crate) fn concat(&mut self) -> &mut Self {
+        self.invokevirtual(
+            "java/lang/String",
+            "concat",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+        )
+    }
+
+    pub(crate) fn athrow(&mut self) -> &mut Self {
+        self.op(0xbf)
+    }
+    pub(crate) fn areturn(&mut self) -> &mut Self {
+        self.op(0xb0)
+    }
+    pub(crate) fn return_(&mut self) -> &mut Self {
+        self.op(0xb1)
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for (offset, label) in &self.jumps {
+            let target = self.labels[label.0].expect("jump to an unbound label");
+            let relative = (target as i64 - *offset as i64) as i16;
+            self.bytes[offset + 1..offset + 3].copy_from_slice(&relative.to_be_bytes());
+        }
+        self.bytes
+    }
+}
+
+pub(crate) struct ClassFile {
+    pool: ConstantPool,
+    name: String,
+    access: u16,
+    this_class: u16,
+    super_class: u16,
+    fields: Vec<u8>,
+    field_count: u16,
+    methods: Vec<u8>,
+    method_count: u16,
+}
+
+impl ClassFile {
+    /// `name` is the binary class name, ex "com/example/Loader".
+    pub(crate) fn new(access: u16, name: &str) -> Self {
+        let mut pool = ConstantPool::default();
+        let this_class = pool.class(name);
+        let super_class = pool.class("java/lang/Object");
+        Self {
+            pool,
+            name: name.to_owned(),
+            access: access | ACC_SUPER,
+            this_class,
+            super_class,
+            fields: vec![],
+            field_count: 0,
+            methods: vec![],
+            method_count: 0,
+        }
+    }
+
+    /// A `static final String` constant, inlined by javac into callers.
+    pub(crate) fn string_constant(&mut self, access: u16, name: &str, value: &str) {
+        let name = self.pool.utf8(name);
+        let descriptor = self.pool.utf8("Ljava/lang/String;");
+        let attribute = self.pool.utf8("ConstantValue");
+        let value = self.pool.string(value);
+        self.fields
+            .extend((access | ACC_STATIC | ACC_FINAL).to_be_bytes());
+        for value in [name, descriptor, 1, attribute] {
+            self.fields.extend(value.to_be_bytes());
+        }
+        self.fields.extend(2u32.to_be_bytes());
+        self.fields.extend(value.to_be_bytes());
+        self.field_count += 1;
+    }
+
+    pub(crate) fn field(&mut self, access: u16, name: &str, descriptor: &str) {
+        let name = self.pool.utf8(name);
+        let descriptor = self.pool.utf8(descriptor);
+        for value in [access, name, descriptor, 0] {
+            self.fields.extend(value.to_be_bytes());
+        }
+        self.field_count += 1;
+    }
+
+    /// `exceptions` are the checked exceptions declared in a throws clause, which
+    /// javac needs to see to allow catching them.
+    pub(crate) fn method(
+        &mut self,
+        (access, name, descriptor): (u16, &str, &str),
+        exceptions: &[&str],
+        max_stack: u16,
+        max_locals: u16,
+        body: impl FnOnce(&mut Code),
+    ) {
+        let name = self.pool.utf8(name);
+        let descriptor = self.pool.utf8(descriptor);
+        let attribute = self.pool.utf8("Code");
+        let mut code = Code {
+            pool: &mut self.pool,
+            this_class: &self.name,
+            bytes: vec![],
+            labels: vec![],
+            jumps: vec![],
+        };
+        body(&mut code);
+        let bytes = code.finish();
+
+        let attribute_count = if exceptions.is_empty() { 1 } else { 2 };
+        for value in [access, name, descriptor, attribute_count, attribute] {
+            self.methods.extend(value.to_be_bytes());
+        }
+        // max_stack, max_locals, code, no exception table, no attributes
+        self.methods.extend((12 + bytes.len() as u32).to_be_bytes());
+        self.methods.extend(max_stack.to_be_bytes());
+        self.methods.extend(max_locals.to_be_bytes());
+        self.methods.extend((bytes.len() as u32).to_be_bytes());
+        self.methods.extend(bytes);
+        self.methods.extend([0, 0, 0, 0]);
+        if !exceptions.is_empty() {
+            let attribute = self.pool.utf8("Exceptions");
+            self.methods.extend(attribute.to_be_bytes());
+            self.methods
+                .extend((2 + 2 * exceptions.len() as u32).to_be_bytes());
+            self.methods.extend((exceptions.len() as u16).to_be_bytes());
+            for exception in exceptions {
+                let class = self.pool.class(exception);
+                self.methods.extend(class.to_be_bytes());
+            }
+        }
+        self.method_count += 1;
+    }
+
+    /// The private no-argument constructor of a class that's never instantiated.
+    pub(crate) fn private_constructor(&mut self) {
+        self.method((ACC_PRIVATE, "<init>", "()V"), &[], 1, 1, |code| {
+            code.aload(0)
+                .invokespecial("java/lang/Object", "<init>", "()V")
+                .return_();
+        });
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0];
+        bytes.extend(MAJOR_VERSION.to_be_bytes());
+        bytes.extend(self.pool.to_bytes());
+        for value in [self.access, self.this_class, self.super_class, 0] {
+            bytes.extend(value.to_be_bytes());
+        }
+        bytes.extend(self.field_count.to_be_bytes());
+        bytes.extend(&self.fields);
+        bytes.extend(self.method_count.to_be_bytes());
+        bytes.extend(&self.methods);
+        bytes.extend([0, 0]);
+        bytes
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A constant pool entry, with indexes into the pool for the non-Utf8 ones.
+    #[derive(Debug, PartialEq)]
+    enum Constant {
+        Utf8(String),
+        Refs(u8, Vec<u16>),
+    }
+
+    struct Reader<'a>(&'a [u8]);
+
+    impl Reader<'_> {
+        fn bytes(&mut self, len: usize) -> &[u8] {
+            let (bytes, rest) = self.0.split_at(len);
+            self.0 = rest;
+            bytes
+        }
+        fn u8(&mut self) -> u8 {
+            self.bytes(1)[0]
+        }
+        fn u16(&mut self) -> u16 {
+            u16::from_be_bytes(self.bytes(2).try_into().unwrap())
+        }
+        fn u32(&mut self) -> u32 {
+            u32::from_be_bytes(self.bytes(4).try_into().unwrap())
+        }
+        /// (name index, data) of each attribute
+        fn attributes(&mut self) -> Vec<(u16, Vec<u8>)> {
+            (0..self.u16())
+                .map(|_| {
+                    let name = self.u16();
+                    let len = self.u32() as usize;
+                    (name, self.bytes(len).to_vec())
+                })
+                .collect()
+        }
+    }
+
+    pub(crate) struct Member {
+        pub(crate) access: u16,
+        name: u16,
+        descriptor: u16,
+        attributes: Vec<(u16, Vec<u8>)>,
+    }
+
+    pub(crate) struct Parsed {
+        pool: Vec<Constant>,
+        this_class: u16,
+        super_class: u16,
+        fields: Vec<Member>,
+        methods: Vec<Member>,
+    }
+
+    /// A method body checked by `Parsed::code`, with the offset of each
+    /// instruction and the instruction written like javap does, ex
+    /// "invokestatic java/lang/System.getProperty(Ljava/lang/String;)Ljava/lang/String;".
+    pub(crate) struct MethodCode {
+        pub(crate) max_stack: u16,
+        pub(crate) max_locals: u16,
+        pub(crate) instructions: Vec<(usize, String)>,
+    }
+
+    impl MethodCode {
+        pub(crate) fn texts(&self) -> Vec<&str> {
+            self.instructions
+                .iter()
+                .map(|(_, text)| text.as_str())
+                .collect()
+        }
+        /// Index of the first instruction starting with `prefix`.
+        pub(crate) fn find(&self, prefix: &str) -> usize {
+            self.instructions
+                .iter()
+                .position(|(_, text)| text.starts_with(prefix))
+                .unwrap_or_else(|| panic!("no {prefix} instruction"))
+        }
+        /// Index of the instruction a jump goes to.
+        pub(crate) fn target(&self, jump: usize) -> usize {
+            let (_, text) = &self.instructions[jump];
+            let offset: usize = text.rsplit(' ').next().unwrap().parse().unwrap();
+            self.instructions
+                .iter()
+                .position(|(at, _)| *at == offset)
+                .unwrap()
+        }
+    }
+
+    // stack slots taken by the arguments and by the return value of a descriptor
+    fn descriptor_slots(descriptor: &str) -> (u16, u16) {
+        let slots = |ty: &str| match ty {
+            "V" => 0,
+            "J" | "D" => 2,
+            _ => 1,
+        };
+        let (arguments, ret) = descriptor
+            .strip_prefix('(')
+            .and_then(|d| d.split_once(')'))
+            .unwrap_or(("", descriptor));
+        let mut count = 0;
+        let mut chars = arguments.chars();
+        while let Some(mut c) = chars.next() {
+            let array = c == '[';
+            while c == '[' {
+                c = chars.next().unwrap();
+            }
+            if c == 'L' {
+                chars.by_ref().find(|&c| c == ';');
+            }
+            count += if array { 1 } else { slots(&c.to_string()) };
+        }
+        (count, slots(ret))
+    }
+
+    impl Parsed {
+        fn constant(&self, index: u16) -> &Constant {
+            &self.pool[index as usize - 1]
+        }
+        fn utf8(&self, index: u16) -> &str {
+            match self.constant(index) {
+                Constant::Utf8(value) => value,
+                other => panic!("{other:?} is not a Utf8 constant"),
+            }
+        }
+        /// The Utf8 a Class or String constant points to.
+        fn named(&self, index: u16, tag: u8) -> &str {
+            match self.constant(index) {
+                Constant::Refs(t, refs) if *t == tag => self.utf8(refs[0]),
+                other => panic!("{other:?} is not a constant with tag {tag}"),
+            }
+        }
+        /// "class.name" and the descriptor of a Fieldref or Methodref constant.
+        fn member_ref(&self, index: u16, tag: u8) -> (String, &str) {
+            let Constant::Refs(t, refs) = self.constant(index) else {
+                panic!("{index} is not a member reference");
+            };
+            assert_eq!(*t, tag, "constant {index} has the wrong tag");
+            let Constant::Refs(12, name_and_type) = self.constant(refs[1]) else {
+                panic!("{} is not a NameAndType", refs[1]);
+            };
+            (
+                format!("{}.{}", self.named(refs[0], 7), self.utf8(name_and_type[0])),
+                self.utf8(name_and_type[1]),
+            )
+        }
+        fn attribute<'m>(&self, member: &'m Member, name: &str) -> &'m [u8] {
+            &member
+                .attributes
+                .iter()
+                .find(|(index, _)| self.utf8(*index) == name)
+                .unwrap_or_else(|| panic!("no {name} attribute"))
+                .1
+        }
+
+        pub(crate) fn this_class(&self) -> &str {
+            self.named(self.this_class, 7)
+        }
+        /// Name and ConstantValue of every `static final String` field.
+        pub(crate) fn string_constants(&self) -> Vec<(&str, &str)> {
+            self.fields
+                .iter()
+                .filter(|field| field.access & ACC_FINAL != 0)
+                .map(|field| {
+                    let value = self.attribute(field, "ConstantValue");
+                    (
+                        self.utf8(field.name),
+                        self.named(u16::from_be_bytes([value[0], value[1]]), 8),
+                    )
+                })
+                .collect()
+        }
+        pub(crate) fn method(&self, name: &str) -> &Member {
+            self.methods
+                .iter()
+                .find(|method| self.utf8(method.name) == name)
+                .unwrap_or_else(|| panic!("no {name} method"))
+        }
+        pub(crate) fn method_names(&self) -> Vec<&str> {
+            self.methods.iter().map(|m| self.utf8(m.name)).collect()
+        }
+        pub(crate) fn exceptions(&self, method: &Member) -> Vec<&str> {
+            let Some((_, data)) = method
+                .attributes
+                .iter()
+                .find(|(index, _)| self.utf8(*index) == "Exceptions")
+            else {
+                return vec![];
+            };
+            let mut reader = Reader(data);
+            (0..reader.u16())
+                .map(|_| self.named(reader.u16(), 7))
+                .collect()
+        }
+
+        /// Decodes a method's Code attribute and walks every path through it,
+        /// checking that jumps land on instructions, that the stack never
+        /// underflows and has the same depth wherever paths meet, that no path
+        /// runs off the end, and that max_stack and max_locals are exactly what
+        /// the body uses.
+        pub(crate) fn code(&self, method: &Member) -> MethodCode {
+            let mut reader = Reader(self.attribute(method, "Code"));
+            let max_stack = reader.u16();
+            let max_locals = reader.u16();
+            let len = reader.u32() as usize;
+            let bytes = reader.bytes(len).to_vec();
+            assert_eq!(reader.u16(), 0, "exception table");
+            assert!(reader.attributes().is_empty());
+
+            // (offset, text, stack effect, local, jump target, ends the path)
+            let mut decoded = vec![];
+            let mut offset = 0;
+            while offset < bytes.len() {
+                let op = bytes[offset];
+                let u16_at = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+                let index = || u16_at(offset + 1);
+                let (text, len, effect, local, target, ends): (String, _, i32, _, _, _) = match op {
+                    0x01 => ("aconst_null".into(), 1, 1, None, None, false),
+                    0x02..=0x08 => (format!("iconst {}", op as i32 - 3), 1, 1, None, None, false),
+                    0x13 => match self.constant(index()) {
+                        Constant::Refs(8, _) => (
+                            format!("ldc {:?}", self.named(index(), 8)),
+                            3,
+                            1,
+                            None,
+                            None,
+                            false,
+                        ),
+                        _ => (
+                            format!("ldc class {}", self.named(index(), 7)),
+                            3,
+                            1,
+                            None,
+                            None,
+                            false,
+                        ),
+                    },
+                    0x19 | 0x3a => {
+                        let (name, effect) = if op == 0x19 {
+                            ("aload", 1)
+                        } else {
+                            ("astore", -1)
+                        };
+                        let local = bytes[offset + 1];
+                        (
+                            format!("{name} {local}"),
+                            2,
+                            effect,
+                            Some(local),
+                            None,
+                            false,
+                        )
+                    }
+                    0x57 => ("pop".into(), 1, -1, None, None, false),
+                    0x58 => ("pop2".into(), 1, -2, None, None, false),
+                    0x59 => ("dup".into(), 1, 1, None, None, false),
+                    0x99 | 0x9a | 0xa7 | 0xc6 | 0xc7 => {
+                        let name = match op {
+                            0x99 => "ifeq",
+                            0x9a => "ifne",
+                            0xa7 => "goto",
+                            0xc6 => "ifnull",
+                            _ => "ifnonnull",
+                        };
+                        let target = (offset as i64 + index() as i16 as i64) as usize;
+                        let effect = if op == 0xa7 { 0 } else { -1 };
+                        (
+                            format!("{name} {target}"),
+                            3,
+                            effect,
+                            None,
+                            Some(target),
+                            op == 0xa7,
+                        )
+                    }
+                    0xb0 => ("areturn".into(), 1, -1, None, None, true),
+                    0xb1 => ("return".into(), 1, 0, None, None, true),
+                    0xb2..=0xb4 => {
+                        let (member, descriptor) = self.member_ref(index(), 9);
+                        let size = descriptor_slots(descriptor).1 as i32;
+                        let (name, effect) = match op {
+                            0xb2 => ("getstatic", size),
+                            0xb3 => ("putstatic", -size),
+                            _ => ("getfield", size - 1),
+                        };
+                        (format!("{name} {member}"), 3, effect, None, None, false)
+                    }
+                    0xb6..=0xb9 => {
+                        let (member, descriptor) =
+                            self.member_ref(index(), if op == 0xb9 { 11 } else { 10 });
+                        let (arguments, ret) = descriptor_slots(descriptor);
+                        let receiver = if op == 0xb8 { 0 } else { 1 };
+                        let len = if op == 0xb9 {
+                            assert_eq!(
+                                bytes[offset + 3] as u16,
+                                arguments + 1,
+                                "invokeinterface count of {member}"
+                            );
+                            5
+                        } else {
+                            3
+                        };
+                        let name = [
+                            "invokevirtual",
+                            "invokespecial",
+                            "invokestatic",
+                            "invokeinterface",
+                        ][op as usize - 0xb6];
+                        (
+                            format!("{name} {member}{descriptor}"),
+                            len,
+                            ret as i32 - arguments as i32 - receiver,
+                            None,
+                            None,
+                            false,
+                        )
+                    }
+                    0xbb => (
+                        format!("new {}", self.named(index(), 7)),
+                        3,
+                        1,
+                        None,
+                        None,
+                        false,
+                    ),
+                    0xbd => (
+                        format!("anewarray {}", self.named(index(), 7)),
+                        3,
+                        0,
+                        None,
+                        None,
+                        false,
+                    ),
+                    0xbf => ("athrow".into(), 1, -1, None, None, true),
+                    op => panic!("unexpected opcode {op:#x} at {offset}"),
+                };
+                decoded.push((offset, text, effect, local, target, ends));
+                offset += len;
+            }
+
+            let at = |offset: usize| {
+                decoded
+                    .iter()
+                    .position(|instruction| instruction.0 == offset)
+                    .unwrap_or_else(|| panic!("jump to {offset}, not an instruction"))
+            };
+            let mut depths: Vec<Option<i32>> = vec![None; decoded.len()];
+            let mut pending = vec![(0, 0)];
+            let mut deepest = 0;
+            while let Some((index, depth)) = pending.pop() {
+                match depths[index] {
+                    Some(seen) => {
+                        assert_eq!(seen, depth, "stack depth differs at {}", decoded[index].1);
+                        continue;
+                    }
+                    None => depths[index] = Some(depth),
+                }
+                let (_, text, effect, _, target, ends) = &decoded[index];
+                // instructions pop before they push, so the stack is deepest before or after one
+                let after = depth + effect;
+                assert!(after >= 0, "stack underflow at {text}");
+                deepest = deepest.max(depth).max(after);
+                if let Some(target) = target {
+                    pending.push((at(*target), after));
+                }
+                if !ends {
+                    assert!(index + 1 < decoded.len(), "{text} runs off the end");
+                    pending.push((index + 1, after));
+                }
+            }
+            let (arguments, _) = descriptor_slots(self.utf8(method.descriptor));
+            let receiver = u16::from(method.access & ACC_STATIC == 0);
+            let locals = decoded
+                .iter()
+                .filter_map(|instruction| instruction.3)
+                .map(|local| local as u16 + 1)
+                .max()
+                .unwrap_or(0)
+                .max(arguments + receiver);
+            assert_eq!(max_stack as i32, deepest, "max_stack");
+            assert_eq!(max_locals, locals, "max_locals");
+            MethodCode {
+                max_stack,
+                max_locals,
+                instructions: decoded.into_iter().map(|i| (i.0, i.1)).collect(),
+            }
+        }
+    }
+
+    fn members(reader: &mut Reader) -> Vec<Member> {
+        (0..reader.u16())
+            .map(|_| Member {
+                access: reader.u16(),
+                name: reader.u16(),
+                descriptor: reader.u16(),
+                attributes: reader.attributes(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn parse(class: &[u8]) -> Parsed {
+        let mut reader = Reader(class);
+        assert_eq!(reader.u32(), 0xcafebabe);
+        assert_eq!(reader.u16(), 0);
+        assert_eq!(reader.u16(), MAJOR_VERSION);
+        let pool = (1..reader.u16())
+            .map(|_| match reader.u8() {
+                1 => {
+                    let len = reader.u16() as usize;
+                    Constant::Utf8(String::from_utf8(reader.bytes(len).to_vec()).unwrap())
+                }
+                tag @ (7 | 8) => Constant::Refs(tag, vec![reader.u16()]),
+                tag @ (9..=12) => Constant::Refs(tag, vec![reader.u16(), reader.u16()]),
+                tag => panic!("unexpected constant tag {tag}"),
+            })
+            .collect();
+        let _access = reader.u16();
+        let this_class = reader.u16();
+        let super_class = reader.u16();
+        assert_eq!(reader.u16(), 0, "no interfaces");
+        let fields = members(&mut reader);
+        let methods = members(&mut reader);
+        assert!(reader.attributes().is_empty());
+        assert!(reader.0.is_empty(), "trailing bytes");
+        Parsed {
+            pool,
+            this_class,
+            super_class,
+            fields,
+            methods,
+        }
+    }
+
+    #[test]
+    fn constant_pool_entries_are_shared() {
+        let mut pool = ConstantPool::default();
+        let class = pool.class("com/example/Test");
+        assert_eq!(pool.class("com/example/Test"), class);
+        assert_eq!(pool.utf8("com/example/Test"), class - 1);
+        let string = pool.string("com/example/Test");
+        assert_ne!(string, class);
+        // the count is one more than the number of entries
+        assert_eq!(pool.to_bytes()[..2], [0, 4]);
+    }
+
+    #[test]
+    fn writes_a_class() {
+        let mut class = ClassFile::new(ACC_PUBLIC | ACC_FINAL, "com/example/Test");
+        class.string_constant(ACC_PUBLIC, "NAME", "test");
+        class.method(
+            (
+                ACC_PUBLIC | ACC_STATIC,
+                "orDefault",
+                "(Ljava/lang/String;)Ljava/lang/String;",
+            ),
+            &["java/io/IOException"],
+            2,
+            1,
+            |code| {
+                let top = code.label();
+                let fallback = code.label();
+                code.bind(top)
+                    .aload(0)
+                    .ifnull(fallback)
+                    .aload(0)
+                    .areturn()
+                    .bind(fallback)
+                    .ldc_string("fallback")
+                    .astore(0)
+                    .goto(top);
+            },
+        );
+        let parsed = parse(&class.to_bytes());
+
+        assert_eq!(parsed.named(parsed.this_class, 7), "com/example/Test");
+        assert_eq!(parsed.named(parsed.super_class, 7), "java/lang/Object");
+
+        let [field] = &parsed.fields[..] else {
+            panic!("expected one field");
+        };
+        assert_eq!(field.access, ACC_PUBLIC | ACC_STATIC | ACC_FINAL);
+        assert_eq!(parsed.utf8(field.name), "NAME");
+        assert_eq!(parsed.utf8(field.descriptor), "Ljava/lang/String;");
+        let value = parsed.attribute(field, "ConstantValue");
+        assert_eq!(
+            parsed.named(u16::from_be_bytes([value[0], value[1]]), 8),
+            "test"
+        );
+
+        let [method] = &parsed.methods[..] else {
+            panic!("expected one method");
+        };
+        assert_eq!(parsed.utf8(method.name), "orDefault");
+        let mut code = Reader(parsed.attribute(method, "Code"));
+        assert_eq!(code.u16(), 2, "max_stack");
+        assert_eq!(code.u16(), 1, "max_locals");
+        let len = code.u32() as usize;
+        let bytes = code.bytes(len).to_vec();
+        assert_eq!(code.u16(), 0, "exception table");
+        assert!(code.attributes().is_empty());
+
+        let ldc = u16::from_be_bytes([bytes[9], bytes[10]]);
+        assert_eq!(parsed.named(ldc, 8), "fallback");
+        assert_eq!(
+            bytes,
+            [
+                0x19, 0, // 0: aload 0
+                0xc6, 0, 6, // 2: ifnull +6 -> 8
+                0x19, 0,    // 5: aload 0
+                0xb0, // 7: areturn
+                0x13, bytes[9], bytes[10], // 8: ldc_w "fallback"
+                0x3a, 0, // 11: astore 0
+                0xa7, 0xff, 0xf3, // 13: goto -13 -> 0
+            ]
+        );
+
+        let mut exceptions = Reader(parsed.attribute(method, "Exceptions"));
+        assert_eq!(exceptions.u16(), 1);
+        assert_eq!(parsed.named(exceptions.u16(), 7), "java/io/IOException");
+    }
+
+    #[test]
+    #[should_panic(expected = "jump to an unbound label")]
+    fn jumps_need_a_bound_label() {
+        let mut class = ClassFile::new(ACC_PUBLIC, "com/example/Test");
+        class.method((ACC_STATIC, "f", "()V"), &[], 0, 0, |code| {
+            let label = code.label();
+            code.goto(label);
+        });
+    }
+}
@@ -0,0 +1,562 @@
+// The loader classes bundled in the .jar and .aar. They're only written as
+// bytecode, so the Java API they expose is described on each function.
+
+use crate::jvm::{
+    ClassFile, Code, ACC_FINAL, ACC_PRIVATE, ACC_PUBLIC, ACC_STATIC, ACC_SYNCHRONIZED,
+};
+
+const STRING: &str = "Ljava/lang/String;";
+
+/// A `public final class` with `public static final String` constants and a
+/// private constructor, the part both loaders share.
+fn loader_class(class_name: &str, constants: &[(&str, &str)]) -> ClassFile {
+    let mut class = ClassFile::new(ACC_PUBLIC | ACC_FINAL, class_name);
+    for (name, value) in constants {
+        class.string_constant(ACC_PUBLIC, name, value);
+    }
+    class.private_constructor();
+    class
+}
+
+// the os.arch values each native/ CPU directory is picked for
+const ARCH_ALIASES: [(&str, &[&str]); 3] = [
+    ("x86_64", &["amd64", "x86_64"]),
+    ("aarch64", &["aarch64", "arm64"]),
+    ("i686", &["x86", "i386", "i686"]),
+];
+
+// os.name is lowercased and matched by prefix, then linux is told apart from
+// linux-musl by the musl dynamic loader
+fn platform_method(code: &mut Code) {
+    let get_property = "(Ljava/lang/String;)Ljava/lang/String;";
+    code.ldc_string("os.name")
+        .invokestatic(Some("java/lang/System"), "getProperty", get_property)
+        .invokevirtual("java/lang/String", "toLowerCase", "()Ljava/lang/String;")
+        .astore(0)
+        .ldc_string("os.arch")
+        .invokestatic(Some("java/lang/System"), "getProperty", get_property)
+        .astore(1);
+
+    let arch_done = code.label();
+    let arch_labels: Vec<_> = ARCH_ALIASES.iter().map(|_| code.label()).collect();
+    for ((_, aliases), label) in ARCH_ALIASES.iter().zip(&arch_labels) {
+        for alias in *aliases {
+            code.aload(1)
+                .ldc_string(alias)
+                .invokevirtual("java/lang/String", "equals", "(Ljava/lang/Object;)Z")
+                .ifne(*label);
+        }
+    }
+    code.goto(arch_done);
+    for ((arch, _), label) in ARCH_ALIASES.iter().zip(&arch_labels) {
+        code.bind(*label).ldc_string(arch).astore(1).goto(arch_done);
+    }
+    code.bind(arch_done);
+
+    let os_done = code.label();
+    for (prefix, os) in [("windows", "windows"), ("mac", "macos")] {
+        let next = code.label();
+        code.aload(0)
+            .ldc_string(prefix)
+            .invokevirtual("java/lang/String", "startsWith", "(Ljava/lang/String;)Z")
+            .ifeq(next)
+            .ldc_string(os)
+            .astore(0)
+            .goto(os_done)
+            .bind(next);
+    }
+    let glibc = code.label();
+    code.new_object("java/io/File")
+        .dup()
+        .ldc_string("/lib/ld-musl-")
+        .aload(1)
+        .concat()
+        .ldc_string(".so.1")
+        .concat()
+        .invokespecial("java/io/File", "<init>", "(Ljava/lang/String;)V")
+        .invokevirtual("java/io/File", "exists", "()Z")
+        .ifeq(glibc)
+        .ldc_string("linux-musl")
+        .astore(0)
+        .goto(os_done)
+        .bind(glibc)
+        .ldc_string("linux")
+        .astore(0)
+        .bind(os_done)
+        .aload(0)
+        .ldc_string("/")
+        .concat()
+        .aload(1)
+        .concat()
+        .areturn();
+}
+
+/// The loader class of the .jar, `file_names` being the loadable of each
+/// native/ platform directory, ex ("linux/x86_64", "sample0.so"):
+///
+/// - `String ENTRYPOINT`, the extension's init function
+/// - `static String platform()`, the native/ directory for the running JVM
+/// - `static synchronized String getLoadablePath() throws IOException`, which
+///   extracts the loadable to a temporary directory on the first call
+/// - `static void load(Connection) throws IOException, SQLException`, which runs
+///   `select load_extension(?, ?)` on a connection that allows it, ex one
+///   opened with sqlite-jdbc's `SQLiteConfig.enableLoadExtension(true)`
+pub(crate) fn jar_loader(
+    package_name: &str,
+    class_name: &str,
+    entrypoint: &str,
+    file_names: &[(String, String)],
+) -> Vec<u8> {
+    let mut class = loader_class(class_name, &[("ENTRYPOINT", entrypoint)]);
+    class.field(ACC_PRIVATE | ACC_STATIC, "loadablePath", STRING);
+
+    class.method(
+        (ACC_PUBLIC | ACC_STATIC, "platform", "()Ljava/lang/String;"),
+        &[],
+        4,
+        2,
+        platform_method,
+    );
+
+    class.method(
+        (
+            ACC_PRIVATE | ACC_STATIC,
+            "fileName",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+        ),
+        &[],
+        2,
+        1,
+        |code| {
+            for (platform, file_name) in file_names {
+                let next = code.label();
+                code.aload(0)
+                    .ldc_string(platform)
+                    .invokevirtual("java/lang/String", "equals", "(Ljava/lang/Object;)Z")
+                    .ifeq(next)
+                    .ldc_string(file_name)
+                    .areturn()
+                    .bind(next);
+            }
+            code.aconst_null().areturn();
+        },
+    );
+
+    // locals: 0 platform, 1 fileName, 2 directory, 3 path, 4 input, 5 resource
+    class.method(
+        (
+            ACC_PUBLIC | ACC_STATIC | ACC_SYNCHRONIZED,
+            "getLoadablePath",
+            "()Ljava/lang/String;",
+        ),
+        &["java/io/IOException"],
+        4,
+        6,
+        |code| {
+            let extract = code.label();
+            let found = code.label();
+            let bundled = code.label();
+            code.getstatic(None, "loadablePath", STRING)
+                .ifnull(extract)
+                .getstatic(None, "loadablePath", STRING)
+                .areturn()
+                .bind(extract)
+                .invokestatic(None, "platform", "()Ljava/lang/String;")
+                .astore(0)
+                .aload(0)
+                .invokestatic(None, "fileName", "(Ljava/lang/String;)Ljava/lang/String;")
+                .astore(1)
+                .aload(1)
+                .ifnonnull(found)
+                .new_object("java/io/IOException")
+                .dup()
+                .ldc_string(&format!("{package_name} has no build for "))
+                .aload(0)
+                .concat()
+                .invokespecial("java/io/IOException", "<init>", "(Ljava/lang/String;)V")
+                .athrow()
+                .bind(found)
+                .ldc_string("/native/")
+                .aload(0)
+                .concat()
+                .ldc_string("/")
+                .concat()
+                .aload(1)
+                .concat()
+                .astore(5)
+                .ldc_class(None)
+                .aload(5)
+                .invokevirtual(
+                    "java/lang/Class",
+                    "getResourceAsStream",
+                    "(Ljava/lang/String;)Ljava/io/InputStream;",
+                )
+                .astore(4)
+                .aload(4)
+                .ifnonnull(bundled)
+                .new_object("java/io/IOException")
+                .dup()
+                .ldc_string(&format!("{package_name} jar is missing "))
+                .aload(5)
+                .concat()
+                .invokespecial("java/io/IOException", "<init>", "(Ljava/lang/String;)V")
+                .athrow()
+                .bind(bundled)
+                .ldc_string(package_name)
+                .iconst(0)
+                .anewarray("java/nio/file/attribute/FileAttribute")
+                .invokestatic(
+                    Some("java/nio/file/Files"),
+                    "createTempDirectory",
+                    "(Ljava/lang/String;[Ljava/nio/file/attribute/FileAttribute;)Ljava/nio/file/Path;",
+                )
+                .astore(2)
+                .aload(2)
+                .aload(1)
+                .invokeinterface(
+                    "java/nio/file/Path",
+                    "resolve",
+                    "(Ljava/lang/String;)Ljava/nio/file/Path;",
+                    2,
+                )
+                .astore(3)
+                .aload(4)
+                .aload(3)
+                .iconst(0)
+                .anewarray("java/nio/file/CopyOption")
+                .invokestatic(
+                    Some("java/nio/file/Files"),
+                    "copy",
+                    "(Ljava/io/InputStream;Ljava/nio/file/Path;[Ljava/nio/file/CopyOption;)J",
+                )
+                .pop2()
+                .aload(4)
+                .invokevirtual("java/io/InputStream", "close", "()V");
+            // deleteOnExit runs in reverse order, so the file goes before its directory
+            for local in [2, 3] {
+                code.aload(local)
+                    .invokeinterface("java/nio/file/Path", "toFile", "()Ljava/io/File;", 1)
+                    .invokevirtual("java/io/File", "deleteOnExit", "()V");
+            }
+            code.aload(3)
+                .invokeinterface("java/nio/file/Path", "toString", "()Ljava/lang/String;", 1)
+                .putstatic(None, "loadablePath", STRING)
+                .getstatic(None, "loadablePath", STRING)
+                .areturn();
+        },
+    );
+
+    class.method(
+        (ACC_PUBLIC | ACC_STATIC, "load", "(Ljava/sql/Connection;)V"),
+        &["java/io/IOException", "java/sql/SQLException"],
+        3,
+        2,
+        |code| {
+            let set_string = "(ILjava/lang/String;)V";
+            code.aload(0)
+                .ldc_string("select load_extension(?, ?)")
+                .invokeinterface(
+                    "java/sql/Connection",
+                    "prepareStatement",
+                    "(Ljava/lang/String;)Ljava/sql/PreparedStatement;",
+                    2,
+                )
+                .astore(1)
+                .aload(1)
+                .iconst(1)
+                .invokestatic(None, "getLoadablePath", "()Ljava/lang/String;")
+                .invokeinterface("java/sql/PreparedStatement", "setString", set_string, 3)
+                .aload(1)
+                .iconst(2)
+                .ldc_string(entrypoint)
+                .invokeinterface("java/sql/PreparedStatement", "setString", set_string, 3)
+                .aload(1)
+                .invokeinterface("java/sql/PreparedStatement", "execute", "()Z", 1)
+                .pop()
+                .aload(1)
+                .invokeinterface("java/sql/PreparedStatement", "close", "()V", 1)
+                .return_();
+        },
+    );
+    class.to_bytes()
+}
+
+/// The loader class of the .aar, for the loadable Android extracts as
+/// lib<library_name>.so:
+///
+/// - `String LIBRARY_NAME`, as passed to `System.loadLibrary()`
+/// - `String ENTRYPOINT`, the extension's init function
+/// - `static String getLibraryPath(Context)`, the absolute path to pass to
+///   load_extension(), ex to requery's `SQLiteCustomExtension`
+pub(crate) fn aar_loader(class_name: &str, library_name: &str, entrypoint: &str) -> Vec<u8> {
+    let mut class = loader_class(
+        class_name,
+        &[("LIBRARY_NAME", library_name), ("ENTRYPOINT", entrypoint)],
+    );
+    class.method(
+        (
+            ACC_PUBLIC | ACC_STATIC,
+            "getLibraryPath",
+            "(Landroid/content/Context;)Ljava/lang/String;",
+        ),
+        &[],
+        2,
+        1,
+        |code| {
+            code.aload(0)
+                .invokevirtual(
+                    "android/content/Context",
+                    "getApplicationInfo",
+                    "()Landroid/content/pm/ApplicationInfo;",
+                )
+                .getfield(
+                    "android/content/pm/ApplicationInfo",
+                    "nativeLibraryDir",
+                    STRING,
+                )
+                .ldc_string(&format!("/lib{library_name}.so"))
+                .concat()
+                .areturn();
+        },
+    );
+    class.to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::tests::parse;
+
+    const GET_RESOURCE: &str = "invokevirtual java/lang/Class.getResourceAsStream(Ljava/lang/String;)Ljava/io/InputStream;";
+    const CONCAT: &str =
+        "invokevirtual java/lang/String.concat(Ljava/lang/String;)Ljava/lang/String;";
+    const IO_EXCEPTION: &str = "invokespecial java/io/IOException.<init>(Ljava/lang/String;)V";
+
+    fn jar() -> Vec<u8> {
+        jar_loader(
+            "sqlite-sample",
+            "com/example/SqliteSample",
+            "sqlite3_sample_init",
+            &[
+                ("linux/x86_64".to_owned(), "sample0.so".to_owned()),
+                ("macos/aarch64".to_owned(), "sample0.dylib".to_owned()),
+            ],
+        )
+    }
+
+    #[test]
+    fn jar_loader_methods() {
+        let class = parse(&jar());
+        assert_eq!(class.this_class(), "com/example/SqliteSample");
+        assert_eq!(
+            class.string_constants(),
+            [("ENTRYPOINT", "sqlite3_sample_init")]
+        );
+        assert_eq!(
+            class.method_names(),
+            ["<init>", "platform", "fileName", "getLoadablePath", "load"]
+        );
+        for name in class.method_names() {
+            class.code(class.method(name));
+        }
+        assert!(class.exceptions(class.method("platform")).is_empty());
+        assert_eq!(
+            class.exceptions(class.method("getLoadablePath")),
+            ["java/io/IOException"]
+        );
+        assert_eq!(
+            class.exceptions(class.method("load")),
+            ["java/io/IOException", "java/sql/SQLException"]
+        );
+    }
+
+    #[test]
+    fn jar_loader_file_names() {
+        let class = parse(&jar());
+        let code = class.code(class.method("fileName"));
+        assert_eq!((code.max_stack, code.max_locals), (2, 1));
+        let equals = "invokevirtual java/lang/String.equals(Ljava/lang/Object;)Z";
+        assert_eq!(
+            code.texts(),
+            [
+                "aload 0",
+                "ldc \"linux/x86_64\"",
+                equals,
+                "ifeq 15",
+                "ldc \"sample0.so\"",
+                "areturn",
+                "aload 0",
+                "ldc \"macos/aarch64\"",
+                equals,
+                "ifeq 30",
+                "ldc \"sample0.dylib\"",
+                "areturn",
+                "aconst_null",
+                "areturn",
+            ]
+        );
+        assert_eq!(code.target(3), 6);
+        assert_eq!(code.target(9), 12);
+    }
+
+    #[test]
+    fn jar_loader_platform() {
+        let class = parse(&jar());
+        let code = class.code(class.method("platform"));
+        assert_eq!((code.max_stack, code.max_locals), (4, 2));
+        let texts = code.texts();
+        for alias in ARCH_ALIASES.iter().flat_map(|(_, aliases)| *aliases) {
+            let ldc = code.find(&format!("ldc {alias:?}"));
+            // a match jumps to storing the native/ CPU directory in local 1
+            let target = code.target(ldc + 2);
+            assert!(texts[target].starts_with("ldc "));
+            assert_eq!(texts[target + 1], "astore 1");
+        }
+        let musl = code.find("ldc \"/lib/ld-musl-\"");
+        assert_eq!(
+            texts[musl - 2..musl + 7],
+            [
+                "new java/io/File",
+                "dup",
+                "ldc \"/lib/ld-musl-\"",
+                "aload 1",
+                CONCAT,
+                "ldc \".so.1\"",
+                CONCAT,
+                "invokespecial java/io/File.<init>(Ljava/lang/String;)V",
+                "invokevirtual java/io/File.exists()Z",
+            ]
+        );
+        assert_eq!(
+            texts[musl + 8..musl + 10],
+            ["ldc \"linux-musl\"", "astore 0"]
+        );
+        let glibc = code.target(musl + 7);
+        assert_eq!(texts[glibc..glibc + 2], ["ldc \"linux\"", "astore 0"]);
+        assert_eq!(
+            texts[texts.len() - 6..],
+            ["aload 0", "ldc \"/\"", CONCAT, "aload 1", CONCAT, "areturn"]
+        );
+    }
+
+    #[test]
+    fn jar_loader_throws_for_a_missing_resource() {
+        let class = parse(&jar());
+        let code = class.code(class.method("getLoadablePath"));
+        assert_eq!((code.max_stack, code.max_locals), (4, 6));
+        let texts = code.texts();
+
+        let no_build = code.find("ifnonnull");
+        assert_eq!(
+            texts[no_build + 1..no_build + 8],
+            [
+                "new java/io/IOException",
+                "dup",
+                "ldc \"sqlite-sample has no build for \"",
+                "aload 0",
+                CONCAT,
+                IO_EXCEPTION,
+                "athrow",
+            ]
+        );
+        assert_eq!(code.target(no_build), no_build + 8);
+
+        let get_resource = code.find(GET_RESOURCE);
+        assert_eq!(
+            texts[get_resource - 2..get_resource],
+            ["ldc class com/example/SqliteSample", "aload 5"]
+        );
+        let check = get_resource + 3;
+        assert_eq!(texts[get_resource + 1..check], ["astore 4", "aload 4"]);
+        assert!(texts[check].starts_with("ifnonnull "));
+        assert_eq!(
+            texts[check + 1..check + 8],
+            [
+                "new java/io/IOException",
+                "dup",
+                "ldc \"sqlite-sample jar is missing \"",
+                "aload 5",
+                CONCAT,
+                IO_EXCEPTION,
+                "athrow",
+            ]
+        );
+        // the stream is only used once it's known not to be null
+        let bundled = code.target(check);
+        assert_eq!(bundled, check + 8);
+        assert_eq!(texts[bundled], "ldc \"sqlite-sample\"");
+        assert!(texts[bundled..].contains(&"aload 4"));
+    }
+
+    #[test]
+    fn jar_loader_load() {
+        let class = parse(&jar());
+        let code = class.code(class.method("load"));
+        assert_eq!((code.max_stack, code.max_locals), (3, 2));
+        let set_string =
+            "invokeinterface java/sql/PreparedStatement.setString(ILjava/lang/String;)V";
+        assert_eq!(
+            code.texts(),
+            [
+                "aload 0",
+                "ldc \"select load_extension(?, ?)\"",
+                "invokeinterface java/sql/Connection.prepareStatement(Ljava/lang/String;)Ljava/sql/PreparedStatement;",
+                "astore 1",
+                "aload 1",
+                "iconst 1",
+                "invokestatic com/example/SqliteSample.getLoadablePath()Ljava/lang/String;",
+                set_string,
+                "aload 1",
+                "iconst 2",
+                "ldc \"sqlite3_sample_init\"",
+                set_string,
+                "aload 1",
+                "invokeinterface java/sql/PreparedStatement.execute()Z",
+                "pop",
+                "aload 1",
+                "invokeinterface java/sql/PreparedStatement.close()V",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn aar_loader_methods() {
+        let class = parse(&aar_loader(
+            "com/example/SqliteSample",
+            "sample0",
+            "sqlite3_sample_init",
+        ));
+        assert_eq!(class.this_class(), "com/example/SqliteSample");
+        assert_eq!(
+            class.string_constants(),
+            [
+                ("LIBRARY_NAME", "sample0"),
+                ("ENTRYPOINT", "sqlite3_sample_init")
+            ]
+        );
+        assert_eq!(class.method_names(), ["<init>", "getLibraryPath"]);
+        let constructor = class.code(class.method("<init>"));
+        assert_eq!(
+            constructor.texts(),
+            [
+                "aload 0",
+                "invokespecial java/lang/Object.<init>()V",
+                "return"
+            ]
+        );
+        let code = class.code(class.method("getLibraryPath"));
+        assert_eq!((code.max_stack, code.max_locals), (2, 1));
+        assert_eq!(
+            code.texts(),
+            [
+                "aload 0",
+                "invokevirtual android/content/Context.getApplicationInfo()Landroid/content/pm/ApplicationInfo;",
+                "getfield android/content/pm/ApplicationInfo.nativeLibraryDir",
+                "ldc \"/libsample0.so\"",
+                CONCAT,
+                "areturn",
+            ]
+        );
+    }
+}
@@ -1,36 +1,65 @@
 mod amalgamation;
+mod android;
+mod apk;
+mod binary;
+mod build_config;
+mod cargo;
+mod deb;
 mod gem;
 mod gh_releases;
+mod go;
+mod homebrew;
+mod init;
 mod installer_sh;
+mod jvm;
+mod jvm_loader;
 mod manifest;
+mod maven;
+mod nix;
 mod npm;
+mod nuget;
 mod pip;
+mod publish;
+mod rpm;
 mod spec;
 mod spm;
 mod sqlpkg;
+mod swift_package;
+mod universal;
+mod validate;
+mod xcframework;
 
-use clap::{builder::OsStr, value_parser, Arg, ArgMatches, Command};
+use chrono::{Datelike, Timelike};
+use clap::{builder::OsStr, value_parser, Arg, ArgAction, ArgMatches, Command};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use manifest::write_manifest;
 use npm::NpmBuildError;
 use pip::PipBuildError;
+use publish::{PublishError, Registries};
 use semver::Version;
 use serde::{Serialize, Serializer};
 use sha2::{Digest, Sha256};
 use spec::Spec;
 use std::{
+    fmt,
     fs::{self, File},
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 use tar::Header;
+use zip::result::ZipError;
+use zip::write::FileOptions;
 
 struct Project {
     version: Version,
     spec: Spec,
     spec_directory: PathBuf,
     platform_directories: Vec<PlatformDirectory>,
+    // fat binaries combined from macos-x86_64 and macos-aarch64, when both exist
+    macos_universal: Option<PlatformDirectory>,
+    // set for reproducible builds, the timestamp stamped into every archive entry
+    source_date_epoch: Option<u64>,
 }
 
 impl Project {
@@ -47,16 +76,18 @@ impl Project {
 struct PlatformDirectory {
     os: Os,
     cpu: Cpu,
-    _path: PathBuf,
+    path: PathBuf,
     loadable_files: Vec<LoadablePlatformFile>,
     static_files: Vec<PlatformFile>,
     header_files: Vec<PlatformFile>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Os {
     Macos,
     Linux,
+    // musl-based Linux distributions like Alpine, from `linux-$CPU-musl` directories
+    LinuxMusl,
     Windows,
     Android,
     Ios,
@@ -72,25 +103,28 @@ impl Serialize for Os {
     }
 }
 
-impl ToString for Os {
-    fn to_string(&self) -> String {
-        match self {
-            Os::Macos => "macos".to_owned(),
-            Os::Linux => "linux".to_owned(),
-            Os::Windows => "windows".to_owned(),
-            Os::Android => "android".to_owned(),
-            Os::Ios => "ios".to_owned(),
-            Os::IosSimulator => "iossimulator".to_owned(),
-        }
+impl fmt::Display for Os {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Os::Macos => "macos",
+            Os::Linux => "linux",
+            Os::LinuxMusl => "linux-musl",
+            Os::Windows => "windows",
+            Os::Android => "android",
+            Os::Ios => "ios",
+            Os::IosSimulator => "iossimulator",
+        })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Cpu {
     X86_64,
     Aarch64,
     I686,
     Armv7a,
+    // x86_64 + arm64 fat Mach-O, only built from the two macOS platform directories
+    Universal,
 }
 
 impl Serialize for Cpu {
@@ -102,17 +136,53 @@ impl Serialize for Cpu {
     }
 }
 
-impl ToString for Cpu {
-    fn to_string(&self) -> String {
-        match self {
-            Cpu::X86_64 => "x86_64".to_owned(),
-            Cpu::Aarch64 => "aarch64".to_owned(),
-            Cpu::I686 => "i686".to_owned(),
-            Cpu::Armv7a => "armv7a".to_owned(),
-        }
+impl fmt::Display for Cpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Cpu::X86_64 => "x86_64",
+            Cpu::Aarch64 => "aarch64",
+            Cpu::I686 => "i686",
+            Cpu::Armv7a => "armv7a",
+            Cpu::Universal => "universal",
+        })
+    }
+}
+
+/// Name of a platform as used in platform directories, GitHub release assets
+/// and install.sh targets, like "macos-aarch64" or "linux-x86_64-musl".
+fn platform_name(os: &Os, cpu: &Cpu) -> String {
+    match os {
+        Os::LinuxMusl => format!("linux-{cpu}-musl"),
+        _ => format!("{os}-{cpu}"),
     }
 }
 
+/// Escapes a value for XML text or a quoted attribute, like a POM or nuspec field.
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Name of a static library as passed to the linker, like "sqlite_sample" for
+/// "libsqlite_sample.a".
+fn static_link_name(file_name: &str) -> &str {
+    let name = file_name.strip_suffix(".a").unwrap_or(file_name);
+    name.strip_prefix("lib").unwrap_or(name)
+}
+
+/// Reports a platform directory that a target has no package for, instead of
+/// leaving it out of the output silently.
+fn warn_unsupported_platform(target: &str, platform_dir: &PlatformDirectory) {
+    println!(
+        "Warning: skipping {} for the {target} target, which doesn't support it",
+        platform_name(&platform_dir.os, &platform_dir.cpu)
+    );
+}
+
 #[derive(Debug, Clone)]
 struct GithubRelease {
     url: String,
@@ -127,8 +197,8 @@ enum AssetPipWheel {
 
 #[derive(Debug, Clone)]
 enum GeneratedAssetKind {
-    Npm(Option<(Os, Cpu)>),
-    Gem((Os, Cpu)),
+    Npm,
+    Gem,
     Pip(AssetPipWheel),
     Datasette,
     SqliteUtils,
@@ -137,26 +207,64 @@ enum GeneratedAssetKind {
     Sqlpkg,
     Spm,
     Amalgamation,
+    Xcframework,
+    SwiftPackage,
+    Android,
+    Maven,
+    Nuget,
+    Go,
+    Cargo,
+    Homebrew,
+    Nix,
+    Deb,
+    Rpm,
+    Apk,
     Manifest,
 }
 
-impl ToString for GeneratedAssetKind {
-    fn to_string(&self) -> String {
+impl fmt::Display for GeneratedAssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GeneratedAssetKind::Npm => "npm",
+            GeneratedAssetKind::Gem => "gem",
+            GeneratedAssetKind::Pip(AssetPipWheel::Standard(_)) => "pip",
+            // Pyodide wheels are installed with micropip from a URL, PyPI rejects their platform tag
+            GeneratedAssetKind::Pip(AssetPipWheel::Pyodide) => "pip-pyodide",
+            GeneratedAssetKind::Datasette => "datasette",
+            GeneratedAssetKind::SqliteUtils => "sqlite-utils",
+            GeneratedAssetKind::GithubReleaseLoadable(_) => "github-release-loadable",
+            GeneratedAssetKind::GithubReleaseStatic(_) => "github-release-static",
+            GeneratedAssetKind::Sqlpkg => "sqlpkg",
+            GeneratedAssetKind::Spm => "spm",
+            GeneratedAssetKind::Amalgamation => "amalgamation",
+            GeneratedAssetKind::Xcframework => "xcframework",
+            GeneratedAssetKind::SwiftPackage => "swift-package",
+            GeneratedAssetKind::Android => "android",
+            GeneratedAssetKind::Maven => "maven",
+            GeneratedAssetKind::Nuget => "nuget",
+            GeneratedAssetKind::Go => "go",
+            GeneratedAssetKind::Cargo => "cargo",
+            GeneratedAssetKind::Homebrew => "homebrew",
+            GeneratedAssetKind::Nix => "nix",
+            GeneratedAssetKind::Deb => "deb",
+            GeneratedAssetKind::Rpm => "rpm",
+            GeneratedAssetKind::Apk => "apk",
+            GeneratedAssetKind::Manifest => "sqlite-dist-manifest",
+        })
+    }
+}
+impl GeneratedAssetKind {
+    /// The platform directory a per-platform asset was built from.
+    fn platform(&self) -> Option<&(Os, Cpu)> {
         match self {
-            GeneratedAssetKind::Npm(_) => "npm".to_owned(),
-            GeneratedAssetKind::Gem(_) => "gem".to_owned(),
-            GeneratedAssetKind::Pip(_) => "pip".to_owned(),
-            GeneratedAssetKind::Datasette => "datasette".to_owned(),
-            GeneratedAssetKind::SqliteUtils => "sqlite-utils".to_owned(),
-            GeneratedAssetKind::GithubReleaseLoadable(_) => "github-release-loadable".to_owned(),
-            GeneratedAssetKind::GithubReleaseStatic(_) => "github-release-static".to_owned(),
-            GeneratedAssetKind::Sqlpkg => "sqlpkg".to_owned(),
-            GeneratedAssetKind::Spm => "spm".to_owned(),
-            GeneratedAssetKind::Amalgamation => "amalgamation".to_owned(),
-            GeneratedAssetKind::Manifest => "sqlite-dist-manifest".to_owned(),
+            GeneratedAssetKind::Pip(AssetPipWheel::Standard(platform)) => Some(platform),
+            GeneratedAssetKind::GithubReleaseLoadable(release)
+            | GeneratedAssetKind::GithubReleaseStatic(release) => Some(&release.platform),
+            _ => None,
         }
     }
 }
+
 impl Serialize for GeneratedAssetKind {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -199,6 +307,33 @@ struct PlatformFile {
 struct LoadablePlatformFile {
     file_stem: String,
     file: PlatformFile,
+    // exported sqlite3_*_init symbols
+    init_symbols: Vec<String>,
+}
+
+impl LoadablePlatformFile {
+    fn new(file_stem: String, file: PlatformFile) -> Self {
+        let init_symbols = binary::init_symbols(&file.data);
+        Self {
+            file_stem,
+            file,
+            init_symbols,
+        }
+    }
+
+    /// The entrypoint to pass when loading this extension, or None when SQLite
+    /// will find the init function on its own. An explicit `package.entrypoint`
+    /// always wins, otherwise the first exported `sqlite3_*_init` symbol is used.
+    fn entrypoint(&self, explicit: Option<&str>) -> Option<String> {
+        if let Some(entrypoint) = explicit {
+            return Some(entrypoint.to_owned());
+        }
+        let defaults = binary::default_init_symbols(&self.file.name);
+        if self.init_symbols.iter().any(|s| defaults.contains(s)) {
+            return None;
+        }
+        self.init_symbols.first().cloned()
+    }
 }
 
 impl PlatformFile {
@@ -217,16 +352,66 @@ impl PlatformFile {
 
 use thiserror::Error;
 
-fn create_targz(files: &[&PlatformFile]) -> io::Result<Vec<u8>> {
+/// Earliest timestamp a zip entry can store (1980-01-01), used for reproducible
+/// builds when SOURCE_DATE_EPOCH isn't set.
+const REPRODUCIBLE_DEFAULT_EPOCH: u64 = 315532800;
+
+/// Gives a tar entry a fixed mtime, root ownership and 0644 permissions, so
+/// archives don't depend on the host that built them.
+fn normalize_tar_header(header: &mut Header, source_date_epoch: u64) {
+    header.set_mtime(source_date_epoch);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mode(0o644);
+    // only fails when the name is too long, and these are empty
+    let _ = header.set_username("");
+    let _ = header.set_groupname("");
+}
+
+/// Zip entry options, with a fixed last modified time for reproducible builds.
+fn zip_file_options(source_date_epoch: Option<u64>) -> FileOptions {
+    let options = FileOptions::default();
+    let Some(timestamp) = source_date_epoch
+        .and_then(|epoch| chrono::DateTime::from_timestamp(epoch.try_into().ok()?, 0))
+    else {
+        return options;
+    };
+    let date_time = zip::DateTime::from_date_and_time(
+        timestamp.year().try_into().unwrap_or(1980),
+        timestamp.month() as u8,
+        timestamp.day() as u8,
+        timestamp.hour() as u8,
+        timestamp.minute() as u8,
+        timestamp.second() as u8,
+    )
+    .unwrap_or_default();
+    options
+        .last_modified_time(date_time)
+        .unix_permissions(0o644)
+}
+
+fn create_zip(files: &[PlatformFile], source_date_epoch: Option<u64>) -> Result<Vec<u8>, ZipError> {
+    let mut zipfile = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    for file in files {
+        let options = zip_file_options(source_date_epoch)
+            .compression_method(zip::CompressionMethod::Deflated);
+        zipfile.start_file(&file.name, options)?;
+        zipfile.write_all(&file.data)?;
+    }
+    Ok(zipfile.finish()?.into_inner())
+}
+
+fn create_targz(files: &[&PlatformFile], source_date_epoch: Option<u64>) -> io::Result<Vec<u8>> {
     let mut tar_gz = Vec::new();
     {
         let enc = GzEncoder::new(&mut tar_gz, Compression::default());
         let mut tar = tar::Builder::new(enc);
         for file in files {
             let mut header = Header::new_gnu();
-            header.set_path(file.name.clone())?;
             header.set_size(file.data.len() as u64);
-            if let Some(metadata) = &file.metadata {
+            if let Some(source_date_epoch) = source_date_epoch {
+                normalize_tar_header(&mut header, source_date_epoch);
+            } else if let Some(metadata) = &file.metadata {
                 header.set_metadata(metadata);
             } else {
                 header.set_mode(0o700);
@@ -237,8 +422,8 @@ fn create_targz(files: &[&PlatformFile]) -> io::Result<Vec<u8>> {
                         .as_secs(),
                 );
             }
-            header.set_cksum();
-            tar.append::<&[u8]>(&header, file.data.as_ref())?;
+            // sets the path and checksum, with a GNU long name entry for paths over 100 bytes
+            tar.append_data(&mut header, &file.name, file.data.as_slice())?;
         }
         tar.finish()?;
     };
@@ -254,12 +439,20 @@ pub enum PlatformDirectoryError {
     MissingDirectoryName,
     #[error("directory or file name must contains only valid UTF-8 characters")]
     InvalidCharacters,
-    #[error("directory {0} is not a valid platform directory. The format must be $OS-$CPU.")]
+    #[error("directory {0} is not a valid platform directory. The format must be $OS-$CPU, or linux-$CPU-musl.")]
     InvalidDirectoryName(String),
-    #[error("Invalid operating system '{0}'. Must be one of 'macos', 'linux', or 'windows'")]
+    #[error("Invalid operating system '{0}'. Must be one of 'macos', 'linux', 'windows', 'android', 'ios', or 'iossimulator', with a '-musl' suffix for linux musl builds")]
     InvalidOsValue(String),
-    #[error("Invalid CPU name '{0}'. Must be one of 'x86_64' or 'aarch64'")]
+    #[error("Invalid CPU name '{0}'. Must be one of 'x86_64', 'aarch64', 'i686', or 'armv7a'")]
     InvalidCpuValue(String),
+    #[error("{0} is not a valid ELF, Mach-O, or PE/COFF binary: {1}")]
+    InvalidBinary(String, binary::BinaryError),
+    #[error("{file} is built for {found}, which does not match its {expected} platform directory")]
+    BinaryPlatformMismatch {
+        file: String,
+        expected: String,
+        found: String,
+    },
 }
 
 impl PlatformDirectory {
@@ -270,13 +463,13 @@ impl PlatformDirectory {
 
         let dirname = base_path
             .components()
-            .last()
+            .next_back()
             .ok_or(PlatformDirectoryError::MissingDirectoryName)?
             .as_os_str()
             .to_str()
             .ok_or(PlatformDirectoryError::InvalidCharacters)?;
         let mut s = dirname.split('-');
-        let os = match s
+        let mut os = match s
             .next()
             .ok_or_else(|| PlatformDirectoryError::InvalidDirectoryName(dirname.to_owned()))?
         {
@@ -298,15 +491,27 @@ impl PlatformDirectory {
             "armv7a" => Cpu::Armv7a,
             cpu => return Err(PlatformDirectoryError::InvalidCpuValue(cpu.to_owned())),
         };
+        match (&os, s.next()) {
+            (_, None) => (),
+            (Os::Linux, Some("musl")) => os = Os::LinuxMusl,
+            _ => {
+                return Err(PlatformDirectoryError::InvalidDirectoryName(
+                    dirname.to_owned(),
+                ))
+            }
+        }
         if s.next().is_some() {
             return Err(PlatformDirectoryError::InvalidDirectoryName(
                 dirname.to_owned(),
             ));
         }
 
-        let dir = fs::read_dir(&base_path)?;
-        for entry in dir {
-            let entry_path = entry?.path();
+        // sorted, so archive entries don't depend on the file system's order
+        let mut entry_paths = fs::read_dir(&base_path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+        entry_paths.sort();
+        for entry_path in entry_paths {
             match entry_path.extension().and_then(|e| e.to_str()) {
                 Some("so") | Some("dll") | Some("dylib") => {
                     let name = entry_path
@@ -323,14 +528,14 @@ impl PlatformDirectory {
                         .to_str()
                         .ok_or(PlatformDirectoryError::InvalidCharacters)?
                         .to_string();
-                    loadable_files.push(LoadablePlatformFile {
+                    loadable_files.push(LoadablePlatformFile::new(
                         file_stem,
-                        file: PlatformFile {
+                        PlatformFile {
                             name: name.to_string(),
                             data,
                             metadata,
                         },
-                    });
+                    ));
                 }
                 Some("a") => {
                     let name = entry_path
@@ -367,10 +572,28 @@ impl PlatformDirectory {
                 }
             }
         }
+        for file in loadable_files.iter().map(|l| &l.file).chain(&static_files) {
+            let Some(info) = binary::inspect(&file.data)
+                .map_err(|err| PlatformDirectoryError::InvalidBinary(file.name.clone(), err))?
+            else {
+                println!(
+                    "Warning: {} only contains LLVM bitcode, its platform can't be checked",
+                    file.name
+                );
+                continue;
+            };
+            if !info.matches(&os, &cpu) {
+                return Err(PlatformDirectoryError::BinaryPlatformMismatch {
+                    file: file.name.clone(),
+                    expected: dirname.to_owned(),
+                    found: info.to_string(),
+                });
+            }
+        }
         Ok(PlatformDirectory {
             os,
             cpu,
-            _path: base_path,
+            path: base_path,
             loadable_files,
             static_files,
             header_files,
@@ -378,6 +601,39 @@ impl PlatformDirectory {
     }
 }
 
+/// Entries of the `--input` directory: the special `wasm32-emscripten` and
+/// `pyodide` directories, and every other entry as a `$OS-$CPU` platform directory.
+struct InputDirectory {
+    emscripten_dir: Option<PathBuf>,
+    pyodide_dir: Option<PathBuf>,
+    platform_entries: Vec<PathBuf>,
+}
+
+impl InputDirectory {
+    fn read(input_dir: &Path) -> io::Result<Self> {
+        let mut entries = fs::read_dir(input_dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+        entries.sort();
+
+        let emscripten_dir = entries
+            .iter()
+            .position(|entry| entry.file_name() == Some(&OsStr::from("wasm32-emscripten")))
+            .map(|item| entries.remove(item));
+
+        let pyodide_dir = entries
+            .iter()
+            .position(|entry| entry.file_name() == Some(&OsStr::from("pyodide")))
+            .map(|item| entries.remove(item));
+
+        Ok(Self {
+            emscripten_dir,
+            pyodide_dir,
+            platform_entries: entries,
+        })
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum BuildError {
     #[error("`{0}` is a required argument")]
@@ -391,14 +647,28 @@ pub enum BuildError {
 
     #[error("Invalid platform directory: {0}")]
     PlayformDirectoryError(#[from] PlatformDirectoryError),
+    #[error("Error building macOS universal binaries: {0}")]
+    UniversalError(#[from] universal::UniversalError),
 
     #[error("Error building a pip package: {0}")]
     PipBuildEror(#[from] PipBuildError),
     #[error("Error building an npm package: {0}")]
     NpmBuildEror(#[from] NpmBuildError),
+    #[error("Error building an XCFramework: {0}")]
+    XcframeworkError(#[from] xcframework::XcframeworkError),
+    #[error("Error building an Android library: {0}")]
+    AndroidError(#[from] android::AndroidError),
+    #[error("Error building a Maven package: {0}")]
+    MavenError(#[from] maven::MavenError),
+    #[error("Error building a NuGet package: {0}")]
+    NugetError(#[from] nuget::NugetError),
+    #[error("Error building a Go module: {0}")]
+    GoError(#[from] go::GoError),
+    #[error("Error building an Alpine package: {0}")]
+    ApkError(#[from] apk::ApkError),
 }
 
-fn build(matches: ArgMatches) -> Result<(), BuildError> {
+fn build(matches: &ArgMatches) -> Result<(), BuildError> {
     // Get the values of arguments
     let input_dir = matches
         .get_one::<PathBuf>("input")
@@ -424,48 +694,18 @@ fn build(matches: ArgMatches) -> Result<(), BuildError> {
         }
     };
 
-    if spec.targets.sqlpkg.is_some() && spec.targets.github_releases.is_none() {
-        return Err(BuildError::SpecError(
-            "sqlpkg target requires the github_releases target".to_owned(),
-        ));
-    }
-    if spec.targets.spm.is_some() && spec.targets.github_releases.is_none() {
-        return Err(BuildError::SpecError(
-            "spm target requires the github_releases target".to_owned(),
-        ));
-    }
-    if spec.targets.datasette.is_some() && spec.targets.pip.is_none() {
-        return Err(BuildError::SpecError(
-            "datasette target requires the pip target".to_owned(),
-        ));
-    }
-    if spec.targets.sqlite_utils.is_some() && spec.targets.pip.is_none() {
-        return Err(BuildError::SpecError(
-            "sqlite_utils target requires the pip target".to_owned(),
-        ));
+    if let Some(error) = spec.dependency_errors().into_iter().next() {
+        return Err(BuildError::SpecError(error));
     }
 
-    let mut entries = fs::read_dir(input_dir)?
-        .map(|entry| {
-            Ok(entry
-                .map_err(|_| {
-                    BuildError::SpecError("Could not read entry in input directory".to_owned())
-                })?
-                .path())
-        })
-        .collect::<Result<Vec<PathBuf>, BuildError>>()?;
-
-    let emscripten_dir = entries
-        .iter()
-        .position(|entry| entry.file_name() == Some(&OsStr::from("wasm32-emscripten")))
-        .map(|item| entries.remove(item));
+    let InputDirectory {
+        emscripten_dir,
+        pyodide_dir,
+        platform_entries,
+    } = InputDirectory::read(input_dir)
+        .map_err(|_| BuildError::SpecError("Could not read entry in input directory".to_owned()))?;
 
-    let pyodide_dir = entries
-        .iter()
-        .position(|entry| entry.file_name() == Some(&OsStr::from("pyodide")))
-        .map(|item| entries.remove(item));
-
-    let platform_directories: Result<Vec<PlatformDirectory>, BuildError> = entries
+    let platform_directories: Result<Vec<PlatformDirectory>, BuildError> = platform_entries
         .iter()
         .map(|entry| {
             PlatformDirectory::from_path(entry.to_owned())
@@ -473,12 +713,24 @@ fn build(matches: ArgMatches) -> Result<(), BuildError> {
         })
         .collect();
     let platform_directories = platform_directories?;
+    let macos_universal = universal::macos_universal(&platform_directories)?;
+
+    let source_date_epoch = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => Some(epoch.trim().parse::<u64>().map_err(|_| {
+            BuildError::SpecError(format!("invalid SOURCE_DATE_EPOCH value '{epoch}'"))
+        })?),
+        Err(_) => matches
+            .get_flag("reproducible")
+            .then_some(REPRODUCIBLE_DEFAULT_EPOCH),
+    };
 
     let project = Project {
         version,
         spec,
         spec_directory: input_file.parent().unwrap().to_path_buf(),
         platform_directories,
+        macos_universal,
+        source_date_epoch,
     };
 
     let mut generated_assets: Vec<GeneratedAsset> = vec![];
@@ -509,13 +761,40 @@ fn build(matches: ArgMatches) -> Result<(), BuildError> {
             )?);
         };
 
+        if let Some(cargo_config) = &project.spec.targets.cargo {
+            let cargo_path = output_dir.join("cargo");
+            std::fs::create_dir(&cargo_path)?;
+            generated_assets.extend(cargo::write_cargo(
+                &project,
+                &cargo_path,
+                cargo_config,
+                &gh_release_assets,
+            )?);
+        };
+
+        if project.spec.targets.homebrew.is_some() {
+            let homebrew_path = output_dir.join("homebrew");
+            std::fs::create_dir(&homebrew_path)?;
+            generated_assets.extend(homebrew::write_homebrew(
+                &project,
+                &homebrew_path,
+                &gh_release_assets,
+            )?);
+        };
+
+        if project.spec.targets.nix.is_some() {
+            let nix_path = output_dir.join("nix");
+            std::fs::create_dir(&nix_path)?;
+            generated_assets.extend(nix::write_nix(&project, &nix_path, &gh_release_assets)?);
+        };
+
         generated_assets.extend(gh_release_assets);
     };
 
     if project.spec.targets.pip.is_some() {
         let pip_path = output_dir.join("pip");
         std::fs::create_dir(&pip_path)?;
-        generated_assets.extend(pip::write_base_packages(&project, &pip_path)?);
+        generated_assets.extend(pip::write_base_packages(&project, &pip_path, &pyodide_dir)?);
         if project.spec.targets.datasette.is_some() {
             let datasette_path = output_dir.join("datasette");
             std::fs::create_dir(&datasette_path)?;
@@ -541,6 +820,62 @@ fn build(matches: ArgMatches) -> Result<(), BuildError> {
         std::fs::create_dir(&gem_path)?;
         generated_assets.extend(gem::write_gems(&project, &gem_path, gem_config)?);
     };
+    if let Some(xcframework_config) = &project.spec.targets.xcframework {
+        let xcframework_path = output_dir.join("xcframework");
+        std::fs::create_dir(&xcframework_path)?;
+        let xcframework_assets =
+            xcframework::write_xcframework(&project, &xcframework_path, xcframework_config)?;
+        if project.spec.targets.swift_package.is_some() {
+            let swift_package_path = output_dir.join("swift_package");
+            std::fs::create_dir(&swift_package_path)?;
+            generated_assets.extend(swift_package::write_swift_package(
+                &project,
+                &swift_package_path,
+                &xcframework_config.module_name(&project.spec.package.name),
+                &xcframework_assets[0],
+            )?);
+        }
+        generated_assets.extend(xcframework_assets);
+    };
+    if let Some(android_config) = &project.spec.targets.android {
+        let android_path = output_dir.join("android");
+        std::fs::create_dir(&android_path)?;
+        generated_assets.extend(android::write_android(
+            &project,
+            &android_path,
+            android_config,
+        )?);
+    };
+    if let Some(maven_config) = &project.spec.targets.maven {
+        let maven_path = output_dir.join("maven");
+        std::fs::create_dir(&maven_path)?;
+        generated_assets.extend(maven::write_maven(&project, &maven_path, maven_config)?);
+    };
+    if let Some(nuget_config) = &project.spec.targets.nuget {
+        let nuget_path = output_dir.join("nuget");
+        std::fs::create_dir(&nuget_path)?;
+        generated_assets.extend(nuget::write_nuget(&project, &nuget_path, nuget_config)?);
+    };
+    if let Some(go_config) = &project.spec.targets.go {
+        let go_path = output_dir.join("go");
+        std::fs::create_dir(&go_path)?;
+        generated_assets.extend(go::write_go(&project, &go_path, go_config)?);
+    };
+    if project.spec.targets.deb.is_some() {
+        let deb_path = output_dir.join("deb");
+        std::fs::create_dir(&deb_path)?;
+        generated_assets.extend(deb::write_deb(&project, &deb_path)?);
+    };
+    if project.spec.targets.rpm.is_some() {
+        let rpm_path = output_dir.join("rpm");
+        std::fs::create_dir(&rpm_path)?;
+        generated_assets.extend(rpm::write_rpm(&project, &rpm_path)?);
+    };
+    if let Some(apk_config) = &project.spec.targets.apk {
+        let apk_path = output_dir.join("apk");
+        std::fs::create_dir(&apk_path)?;
+        generated_assets.extend(apk::write_apk(&project, &apk_path, apk_config)?);
+    };
 
     let github_releases_checksums_txt = generated_assets
         .iter()
@@ -551,6 +886,7 @@ fn build(matches: ArgMatches) -> Result<(), BuildError> {
                     | GeneratedAssetKind::GithubReleaseStatic(_)
                     | GeneratedAssetKind::Sqlpkg
                     | GeneratedAssetKind::Spm
+                    | GeneratedAssetKind::Xcframework
             )
         })
         .map(|ga| format!("{} {}", ga.name, ga.checksum_sha256))
@@ -561,10 +897,51 @@ fn build(matches: ArgMatches) -> Result<(), BuildError> {
     File::create(output_dir.join("install.sh"))?.write_all(
         crate::installer_sh::templates::install_sh(&project, &generated_assets).as_bytes(),
     )?;
-    write_manifest(output_dir, &generated_assets)?;
+    write_manifest(output_dir, &project, &generated_assets)?;
     Ok(())
 }
 
+fn init_command(matches: &ArgMatches) -> Result<(), init::InitError> {
+    let dir = matches
+        .get_one::<PathBuf>("dir")
+        .expect("dir has a default value");
+    init::init(
+        dir,
+        matches.get_one::<PathBuf>("input").map(PathBuf::as_path),
+        matches.get_flag("force"),
+    )
+}
+
+fn validate_command(matches: &ArgMatches) -> Result<(), validate::ValidateError> {
+    let input_file = matches
+        .get_one::<PathBuf>("file")
+        .expect("file is a required argument");
+    let input_dir = matches
+        .get_one::<PathBuf>("input")
+        .expect("input is a required argument");
+    validate::validate(input_file, input_dir)
+}
+
+fn publish_command(matches: &ArgMatches) -> Result<(), PublishError> {
+    let dir = matches
+        .get_one::<PathBuf>("dir")
+        .expect("dir is a required argument");
+    let url = |name: &str| {
+        matches
+            .get_one::<String>(name)
+            .expect("registry URLs have default values")
+            .clone()
+    };
+    let registries = Registries {
+        github_api: url("github-api-url"),
+        github_uploads: url("github-uploads-url"),
+        npm: url("npm-registry-url"),
+        rubygems: url("rubygems-url"),
+        pypi: url("pypi-url"),
+    };
+    publish::publish(dir, &registries, matches.get_flag("dry-run"))
+}
+
 fn main() {
     let matches = Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -599,14 +976,284 @@ fn main() {
                 .index(1)
                 .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            Arg::new("reproducible")
+                .long("reproducible")
+                .help("Build byte-for-byte reproducible archives, with timestamps from SOURCE_DATE_EPOCH (implied when it is set)")
+                .action(ArgAction::SetTrue),
+        )
         .disable_version_flag(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("init")
+                .about("Write a commented sqlite-dist.toml for a new extension")
+                .arg(
+                    Arg::new("dir")
+                        .value_name("DIR")
+                        .help("Project directory to write sqlite-dist.toml into")
+                        .index(1)
+                        .default_value(".")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("input")
+                        .long("input")
+                        .value_name("INPUT_DIR")
+                        .help("Also create empty $OS-$CPU platform directories in INPUT_DIR")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Overwrite an existing sqlite-dist.toml")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check a sqlite-dist.toml spec and input directory without building anything")
+                .arg(
+                    Arg::new("input")
+                        .long("input")
+                        .value_name("INPUT_DIR")
+                        .help("Sets the input directory")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .help("Sets the input file")
+                        .required(true)
+                        .index(1)
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("publish")
+                .about("Upload the artifacts listed in sqlite-dist-manifest.json to their registries")
+                .arg(
+                    Arg::new("dir")
+                        .value_name("OUTPUT_DIR")
+                        .help("Output directory of a previous build, containing sqlite-dist-manifest.json")
+                        .required(true)
+                        .index(1)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Print what would be uploaded without uploading anything")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("github-api-url")
+                        .long("github-api-url")
+                        .value_name("URL")
+                        .default_value(publish::DEFAULT_GITHUB_API_URL),
+                )
+                .arg(
+                    Arg::new("github-uploads-url")
+                        .long("github-uploads-url")
+                        .value_name("URL")
+                        .default_value(publish::DEFAULT_GITHUB_UPLOADS_URL),
+                )
+                .arg(
+                    Arg::new("npm-registry-url")
+                        .long("npm-registry-url")
+                        .value_name("URL")
+                        .default_value(publish::DEFAULT_NPM_REGISTRY_URL),
+                )
+                .arg(
+                    Arg::new("rubygems-url")
+                        .long("rubygems-url")
+                        .value_name("URL")
+                        .default_value(publish::DEFAULT_RUBYGEMS_URL),
+                )
+                .arg(
+                    Arg::new("pypi-url")
+                        .long("pypi-url")
+                        .value_name("URL")
+                        .default_value(publish::DEFAULT_PYPI_URL),
+                ),
+        )
         .get_matches();
 
-    match build(matches) {
+    let result = match matches.subcommand() {
+        Some(("init", sub_matches)) => {
+            init_command(sub_matches).map_err(|error| format!("Init error: {error}"))
+        }
+        Some(("validate", sub_matches)) => {
+            validate_command(sub_matches).map_err(|error| format!("Validation error: {error}"))
+        }
+        Some(("publish", sub_matches)) => {
+            publish_command(sub_matches).map_err(|error| format!("Publish error: {error}"))
+        }
+        _ => build(&matches).map_err(|error| format!("Build error: {error}")),
+    };
+    match result {
         Ok(_) => std::process::exit(0),
         Err(error) => {
-            eprintln!("Build error: {error}");
+            eprintln!("{error}");
             std::process::exit(1);
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use tempdir::TempDir;
+
+    /// A platform directory with a single loadable, sample0.so/.dylib/.dll.
+    pub(crate) fn platform_directory(os: Os, cpu: Cpu, loadable: Vec<u8>) -> PlatformDirectory {
+        let extension = match os {
+            Os::Macos | Os::Ios | Os::IosSimulator => "dylib",
+            Os::Windows => "dll",
+            _ => "so",
+        };
+        PlatformDirectory {
+            path: PathBuf::from(platform_name(&os, &cpu)),
+            os,
+            cpu,
+            loadable_files: vec![LoadablePlatformFile::new(
+                "sample0".to_owned(),
+                PlatformFile::new(format!("sample0.{extension}"), loadable, None),
+            )],
+            static_files: vec![],
+            header_files: vec![],
+        }
+    }
+
+    /// The GitHub release asset of a platform's loadable or static tarball.
+    pub(crate) fn release_asset(
+        loadable: bool,
+        platform: (Os, Cpu),
+        checksum: &str,
+    ) -> GeneratedAsset {
+        let kind = if loadable { "loadable" } else { "static" };
+        let release = GithubRelease {
+            url: format!(
+                "https://github.com/asg017/sqlite-sample/releases/download/v0.1.0/sqlite-sample-0.1.0-{kind}-{}.tar.gz",
+                platform_name(&platform.0, &platform.1)
+            ),
+            platform,
+        };
+        GeneratedAsset {
+            kind: if loadable {
+                GeneratedAssetKind::GithubReleaseLoadable(release)
+            } else {
+                GeneratedAssetKind::GithubReleaseStatic(release)
+            },
+            name: String::new(),
+            path: String::new(),
+            checksum_sha256: checksum.to_owned(),
+            size: 0,
+        }
+    }
+
+    /// A sqlite-sample 0.1.0 project with the given `[targets]` table.
+    pub(crate) fn project(targets: &str, platform_directories: Vec<PlatformDirectory>) -> Project {
+        let spec = format!(
+            r#"
+[package]
+name = "sqlite-sample"
+license = "MIT"
+homepage = "https://example.com"
+repo = "https://github.com/asg017/sqlite-sample"
+description = "A sample SQLite extension"
+authors = ["Alex Garcia"]
+
+[targets]
+{targets}
+"#
+        );
+        Project {
+            version: Version::new(0, 1, 0),
+            spec: toml::from_str(&spec).unwrap(),
+            spec_directory: PathBuf::from("."),
+            platform_directories,
+            macos_universal: None,
+            source_date_epoch: None,
+        }
+    }
+
+    // 2023-11-14 22:13:20 UTC
+    const EPOCH: u64 = 1700000000;
+
+    fn files() -> Vec<PlatformFile> {
+        let dir = TempDir::new("sqlite-dist-archive").unwrap();
+        let path = dir.path().join("sample0.so");
+        fs::write(&path, b"loadable").unwrap();
+        vec![
+            PlatformFile::new("sample0.so", "loadable", Some(fs::metadata(&path).unwrap())),
+            PlatformFile::new("sample0.h", "header", None),
+        ]
+    }
+
+    #[test]
+    fn reproducible_targz() {
+        let files = files();
+        let targz = create_targz(&files.iter().collect::<Vec<_>>(), Some(EPOCH)).unwrap();
+        // the host's metadata for sample0.so is ignored
+        let without_metadata: Vec<PlatformFile> = files
+            .iter()
+            .map(|file| PlatformFile::new(file.name.clone(), file.data.clone(), None))
+            .collect();
+        assert_eq!(
+            create_targz(&without_metadata.iter().collect::<Vec<_>>(), Some(EPOCH)).unwrap(),
+            targz
+        );
+
+        let mut archive = tar::Archive::new(GzDecoder::new(targz.as_slice()));
+        let mut names = vec![];
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let header = entry.header();
+            assert_eq!(header.mtime().unwrap(), EPOCH);
+            assert_eq!(header.uid().unwrap(), 0);
+            assert_eq!(header.gid().unwrap(), 0);
+            assert_eq!(header.mode().unwrap(), 0o644);
+            assert_eq!(header.username().unwrap(), Some(""));
+            names.push(entry.path().unwrap().display().to_string());
+        }
+        assert_eq!(names, ["sample0.so", "sample0.h"]);
+    }
+
+    #[test]
+    fn reproducible_zip() {
+        let zip = create_zip(&files(), Some(EPOCH)).unwrap();
+        assert_eq!(create_zip(&files(), Some(EPOCH)).unwrap(), zip);
+
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(zip)).unwrap();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).unwrap();
+            let modified = file.last_modified();
+            assert_eq!(
+                (
+                    modified.year(),
+                    modified.month(),
+                    modified.day(),
+                    modified.hour(),
+                    modified.minute(),
+                    modified.second()
+                ),
+                (2023, 11, 14, 22, 13, 20)
+            );
+            assert_eq!(file.unix_mode(), Some(0o100644));
+        }
+    }
+
+    #[test]
+    fn zip_timestamps_start_in_1980() {
+        let zip = create_zip(&files(), Some(REPRODUCIBLE_DEFAULT_EPOCH)).unwrap();
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(zip)).unwrap();
+        let modified = archive.by_index(0).unwrap().last_modified();
+        assert_eq!(
+            (modified.year(), modified.month(), modified.day()),
+            (1980, 1, 1)
+        );
+    }
+}
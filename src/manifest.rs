@@ -1,34 +1,83 @@
-use crate::{GeneratedAsset, GeneratedAssetKind};
+use crate::{platform_name, GeneratedAsset, GeneratedAssetKind, Project};
 use serde::{Deserialize, Serialize};
 use std::io::Result;
 use std::path::Path;
 
+pub const MANIFEST_FILE_NAME: &str = "sqlite-dist-manifest.json";
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ManifestBuildInfo {
-    sqlite_dist_version: String,
+    pub sqlite_dist_version: String,
+    pub name: String,
+    pub version: String,
+    pub git_tag: String,
+    pub repo: String,
 }
 
-#[derive(Serialize)]
-pub struct Manifest<'a> {
-    build_info: ManifestBuildInfo,
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestArtifact {
+    pub kind: String,
+    pub name: String,
+    // relative to the directory that contains the manifest
+    pub path: String,
+    pub checksum_sha256: String,
+    pub size: usize,
+    // platform directory name, ex "linux-x86_64", for assets built for a single platform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+}
 
-    artifacts: &'a [GeneratedAsset],
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    pub build_info: ManifestBuildInfo,
+
+    pub artifacts: Vec<ManifestArtifact>,
 }
 
 pub(crate) fn write_manifest(
     manifest_dir: &Path,
+    project: &Project,
     generated_assets: &[GeneratedAsset],
 ) -> Result<GeneratedAsset> {
     let manifest = Manifest {
         build_info: ManifestBuildInfo {
-            sqlite_dist_version: "TODO".to_owned(),
+            sqlite_dist_version: env!("CARGO_PKG_VERSION").to_owned(),
+            name: project.spec.package.name.clone(),
+            version: project.version.to_string(),
+            git_tag: project.spec.package.git_tag(&project.version),
+            repo: project.spec.package.repo.clone(),
         },
-        artifacts: generated_assets,
+        artifacts: generated_assets
+            .iter()
+            .map(|asset| {
+                let path = Path::new(&asset.path);
+                ManifestArtifact {
+                    kind: asset.kind.to_string(),
+                    name: asset.name.clone(),
+                    path: path
+                        .strip_prefix(manifest_dir)
+                        .unwrap_or(path)
+                        .to_string_lossy()
+                        .into_owned(),
+                    checksum_sha256: asset.checksum_sha256.clone(),
+                    size: asset.size,
+                    platform: asset
+                        .kind
+                        .platform()
+                        .map(|(os, cpu)| platform_name(os, cpu)),
+                }
+            })
+            .collect(),
     };
     let asset = GeneratedAsset::from(
-        GeneratedAssetKind::Sqlpkg,
-        &manifest_dir.join("sqlite-dist-manifest.json"),
+        GeneratedAssetKind::Manifest,
+        &manifest_dir.join(MANIFEST_FILE_NAME),
         serde_json::to_string_pretty(&manifest)?.as_bytes(),
     )?;
     Ok(asset)
 }
+
+pub(crate) fn read_manifest(manifest_dir: &Path) -> Result<Manifest> {
+    let contents = std::fs::read_to_string(manifest_dir.join(MANIFEST_FILE_NAME))?;
+    Ok(serde_json::from_str(&contents)?)
+}
@@ -0,0 +1,214 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use zip::result::ZipError;
+
+use crate::jvm_loader;
+use crate::spec::{camel_case, TargetMaven};
+use crate::{
+    binary, create_zip, warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os,
+    PlatformFile, Project,
+};
+
+#[derive(Error, Debug)]
+pub enum MavenError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Zipfile error: {0}")]
+    ZipError(#[from] ZipError),
+    #[error("no linux, macos or windows platform directory has a loadable library")]
+    NoLibraries,
+}
+
+/// A zip with a META-INF/MANIFEST.MF in front of `files`.
+pub(crate) fn create_jar(
+    files: &[PlatformFile],
+    source_date_epoch: Option<u64>,
+) -> Result<Vec<u8>, ZipError> {
+    let mut jar_files = vec![PlatformFile::new(
+        "META-INF/MANIFEST.MF",
+        "Manifest-Version: 1.0\r\n\r\n",
+        None,
+    )];
+    jar_files.extend_from_slice(files);
+    create_zip(&jar_files, source_date_epoch)
+}
+
+// Maven repositories keep a .sha1 and .md5 of every file next to it
+fn write_checksums(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    fs::write(
+        path.with_file_name(format!("{file_name}.sha1")),
+        base16ct::lower::encode_string(&Sha1::digest(contents)),
+    )?;
+    fs::write(
+        path.with_file_name(format!("{file_name}.md5")),
+        base16ct::lower::encode_string(&Md5::digest(contents)),
+    )
+}
+
+fn write_repository_file(path: &Path, contents: &[u8]) -> io::Result<GeneratedAsset> {
+    let asset = GeneratedAsset::from(GeneratedAssetKind::Maven, &path.to_path_buf(), contents)?;
+    write_checksums(path, contents)?;
+    Ok(asset)
+}
+
+/// Writes a Maven repository layout with a .jar of every desktop loadable under
+/// native/<os>/<cpu>/ and a loader class, its POM and maven-metadata.xml, which can be copied into a file-based repository.
+pub(crate) fn write_maven(
+    project: &Project,
+    maven_path: &Path,
+    maven_config: &TargetMaven,
+) -> Result<Vec<GeneratedAsset>, MavenError> {
+    let mut native_files = vec![];
+    for platform_dir in &project.platform_directories {
+        match (&platform_dir.os, &platform_dir.cpu) {
+            (Os::Linux | Os::LinuxMusl | Os::Macos | Os::Windows, Cpu::X86_64 | Cpu::Aarch64)
+            | (Os::Windows, Cpu::I686) => (),
+            _ => {
+                warn_unsupported_platform("maven", platform_dir);
+                continue;
+            }
+        }
+        if let Some(loadable) = platform_dir.loadable_files.first() {
+            native_files.push((
+                format!("{}/{}", platform_dir.os, platform_dir.cpu),
+                loadable,
+            ));
+        }
+    }
+    let Some((_, first)) = native_files.first() else {
+        return Err(MavenError::NoLibraries);
+    };
+
+    let package = &project.spec.package;
+    let java_package = maven_config.java_package(&package.name);
+    let class_name = camel_case(&package.name);
+    let binary_class_name = format!("{}/{class_name}", java_package.replace('.', "/"));
+    let entrypoint = package
+        .entrypoint
+        .clone()
+        .unwrap_or_else(|| binary::init_function(&first.file.name, &first.init_symbols));
+    let file_names: Vec<(String, String)> = native_files
+        .iter()
+        .map(|(platform, loadable)| (platform.clone(), loadable.file.name.clone()))
+        .collect();
+
+    let mut jar_files = vec![PlatformFile::new(
+        format!("{binary_class_name}.class"),
+        jvm_loader::jar_loader(&package.name, &binary_class_name, &entrypoint, &file_names),
+        None,
+    )];
+    for (platform, loadable) in &native_files {
+        jar_files.push(PlatformFile::new(
+            format!("native/{platform}/{}", loadable.file.name),
+            loadable.file.data.clone(),
+            None,
+        ));
+    }
+    let artifact_path = maven_path
+        .join(maven_config.group_id.replace('.', "/"))
+        .join(&package.name);
+    let version_path = artifact_path.join(project.version.to_string());
+    fs::create_dir_all(&version_path)?;
+    let base_name = format!("{}-{}", package.name, project.version);
+    Ok(vec![
+        write_repository_file(
+            &version_path.join(format!("{base_name}.jar")),
+            &create_jar(&jar_files, project.source_date_epoch)?,
+        )?,
+        write_repository_file(
+            &version_path.join(format!("{base_name}.pom")),
+            templates::pom_xml(project, &maven_config.group_id, "jar").as_bytes(),
+        )?,
+        write_repository_file(
+            &artifact_path.join("maven-metadata.xml"),
+            templates::maven_metadata_xml(project, &maven_config.group_id).as_bytes(),
+        )?,
+    ])
+}
+
+pub(crate) mod templates {
+    use crate::{xml_escape, Project};
+
+    pub(crate) fn pom_xml(project: &Project, group_id: &str, packaging: &str) -> String {
+        let package = &project.spec.package;
+        let developers: String = package
+            .authors
+            .iter()
+            .map(|author| {
+                format!(
+                    "\n    <developer>\n      <name>{}</name>\n    </developer>",
+                    xml_escape(author)
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0"
+    xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+    xsi:schemaLocation="http://maven.apache.org/POM/4.0.0 https://maven.apache.org/xsd/maven-4.0.0.xsd">
+  <modelVersion>4.0.0</modelVersion>
+  <groupId>{group_id}</groupId>
+  <artifactId>{name}</artifactId>
+  <version>{version}</version>
+  <packaging>{packaging}</packaging>
+  <name>{name}</name>
+  <description>{description}</description>
+  <url>{homepage}</url>
+  <licenses>
+    <license>
+      <name>{license}</name>
+    </license>
+  </licenses>
+  <developers>{developers}
+  </developers>
+  <scm>
+    <url>{repo}</url>
+  </scm>
+</project>
+"#,
+            name = package.name,
+            version = project.version,
+            description = xml_escape(&package.description),
+            homepage = xml_escape(&package.homepage),
+            license = xml_escape(&package.license),
+            repo = xml_escape(&package.repo),
+        )
+    }
+
+    // only lists this version, so merge it by hand into an existing repository
+    pub(crate) fn maven_metadata_xml(project: &Project, group_id: &str) -> String {
+        let version = &project.version;
+        let last_updated = project
+            .source_date_epoch
+            .and_then(|epoch| chrono::DateTime::from_timestamp(epoch.try_into().ok()?, 0))
+            .unwrap_or_else(chrono::Utc::now)
+            .format("%Y%m%d%H%M%S");
+        let release = if version.pre.is_empty() {
+            format!("\n    <release>{version}</release>")
+        } else {
+            String::new()
+        };
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata>
+  <groupId>{group_id}</groupId>
+  <artifactId>{name}</artifactId>
+  <versioning>
+    <latest>{version}</latest>{release}
+    <versions>
+      <version>{version}</version>
+    </versions>
+    <lastUpdated>{last_updated}</lastUpdated>
+  </versioning>
+</metadata>
+"#,
+            name = project.spec.package.name,
+        )
+    }
+}
@@ -0,0 +1,296 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use base64::Engine;
+
+use crate::{Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
+
+// https://nixos.org/manual/nixpkgs/stable/#sec-platform-notes
+fn nix_system(os: &Os, cpu: &Cpu) -> Option<&'static str> {
+    match (os, cpu) {
+        (Os::Linux, Cpu::X86_64) => Some("x86_64-linux"),
+        (Os::Linux, Cpu::Aarch64) => Some("aarch64-linux"),
+        (Os::Linux, Cpu::I686) => Some("i686-linux"),
+        (Os::Linux, Cpu::Armv7a) => Some("armv7l-linux"),
+        (Os::Macos, Cpu::X86_64) => Some("x86_64-darwin"),
+        (Os::Macos, Cpu::Aarch64) => Some("aarch64-darwin"),
+        _ => None,
+    }
+}
+
+/// A release tarball with the SRI hash fetchurl checks it against.
+struct Source<'a> {
+    url: &'a str,
+    hash: String,
+}
+
+/// The release tarballs a Nix system's package is built from.
+struct SystemSources<'a> {
+    system: &'static str,
+    loadable: Source<'a>,
+    // the static tarball, for its headers and static libraries
+    static_: Option<Source<'a>>,
+}
+
+// hex sha256 -> "sha256-<base64>"
+fn sri_hash(checksum_sha256: &str) -> String {
+    let mut digest = [0u8; 32];
+    let digest = base16ct::lower::decode(checksum_sha256, &mut digest)
+        .expect("sha256 checksums are lowercase hex");
+    format!(
+        "sha256-{}",
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    )
+}
+
+fn source<'a>(
+    gh_release_assets: &'a [GeneratedAsset],
+    platform: &(Os, Cpu),
+    loadable: bool,
+) -> Option<Source<'a>> {
+    gh_release_assets
+        .iter()
+        .find_map(|asset| match &asset.kind {
+            GeneratedAssetKind::GithubReleaseLoadable(release)
+                if loadable && release.platform == *platform =>
+            {
+                Some((release, asset))
+            }
+            GeneratedAssetKind::GithubReleaseStatic(release)
+                if !loadable && release.platform == *platform =>
+            {
+                Some((release, asset))
+            }
+            _ => None,
+        })
+        .map(|(release, asset)| Source {
+            url: &release.url,
+            hash: sri_hash(&asset.checksum_sha256),
+        })
+}
+
+/// Writes a default.nix with a derivation that installs the release tarballs
+/// of the building system into $out, and a flake.nix exposing it as a package
+/// per supported system.
+pub(crate) fn write_nix(
+    project: &Project,
+    nix_path: &Path,
+    gh_release_assets: &[GeneratedAsset],
+) -> io::Result<Vec<GeneratedAsset>> {
+    let mut systems = vec![];
+    for os in [Os::Linux, Os::Macos] {
+        for cpu in [Cpu::X86_64, Cpu::Aarch64, Cpu::I686, Cpu::Armv7a] {
+            let Some(system) = nix_system(&os, &cpu) else {
+                continue;
+            };
+            let platform = (os.clone(), cpu);
+            // the universal build covers both macOS architectures
+            let universal = (os.clone(), Cpu::Universal);
+            let Some(loadable) = source(gh_release_assets, &platform, true)
+                .or_else(|| source(gh_release_assets, &universal, true))
+            else {
+                continue;
+            };
+            systems.push(SystemSources {
+                system,
+                loadable,
+                static_: source(gh_release_assets, &platform, false)
+                    .or_else(|| source(gh_release_assets, &universal, false)),
+            });
+        }
+    }
+    if systems.is_empty() {
+        println!("Warning: no macos or linux loadable found, skipping the nix target");
+        return Ok(vec![]);
+    }
+
+    fs::create_dir_all(nix_path)?;
+    Ok(vec![
+        GeneratedAsset::from(
+            GeneratedAssetKind::Nix,
+            &nix_path.join("default.nix"),
+            templates::default_nix(project, &systems).as_bytes(),
+        )?,
+        GeneratedAsset::from(
+            GeneratedAssetKind::Nix,
+            &nix_path.join("flake.nix"),
+            templates::flake_nix(project, &systems).as_bytes(),
+        )?,
+    ])
+}
+
+mod templates {
+    use super::{Source, SystemSources};
+    use crate::Project;
+
+    pub(super) fn nix_string(value: &str) -> String {
+        format!(
+            "\"{}\"",
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace("${", "\\${")
+                .replace('\n', "\\n")
+        )
+    }
+
+    fn fetchurl_args(source: &Source) -> String {
+        format!(
+            "{{ url = {}; hash = {}; }}",
+            nix_string(source.url),
+            nix_string(&source.hash)
+        )
+    }
+
+    pub(crate) fn default_nix(project: &Project, systems: &[SystemSources]) -> String {
+        let package = &project.spec.package;
+        let sources: String = systems
+            .iter()
+            .map(|sources| {
+                let static_ = match &sources.static_ {
+                    Some(static_) => format!("\n      static = {};", fetchurl_args(static_)),
+                    None => String::new(),
+                };
+                format!(
+                    "    {} = {{\n      loadable = {};{static_}\n    }};\n",
+                    sources.system,
+                    fetchurl_args(&sources.loadable)
+                )
+            })
+            .collect();
+        format!(
+            r#"# Generated by sqlite-dist.
+{{ pkgs ? import <nixpkgs> {{ }} }}:
+
+let
+  sources = {{
+{sources}  }};
+  system = pkgs.stdenv.hostPlatform.system;
+  source = sources.${{system}} or (throw "{name} is not available on ${{system}}");
+in
+pkgs.stdenvNoCC.mkDerivation {{
+  pname = {pname};
+  version = {version};
+
+  srcs = [ (pkgs.fetchurl source.loadable) ]
+    ++ pkgs.lib.optional (source ? static) (pkgs.fetchurl source.static);
+  # the release tarballs have no top-level directory
+  sourceRoot = ".";
+
+  dontConfigure = true;
+  dontBuild = true;
+
+  installPhase = ''
+    runHook preInstall
+    mkdir -p $out/lib
+    find . -maxdepth 1 \( -name '*.so' -o -name '*.dylib' \) -exec install -m755 {{}} $out/lib/ \;
+    if [ -d include ]; then cp -r include $out/; fi
+    if [ -d lib ]; then cp -r lib/. $out/lib/; fi
+    runHook postInstall
+  '';
+
+  meta = {{
+    description = {description};
+    homepage = {homepage};
+    platforms = builtins.attrNames sources;
+  }};
+}}
+"#,
+            name = package.name,
+            pname = nix_string(&package.name),
+            version = nix_string(&project.version.to_string()),
+            description = nix_string(package.description.trim()),
+            homepage = nix_string(&package.homepage),
+        )
+    }
+
+    pub(crate) fn flake_nix(project: &Project, systems: &[SystemSources]) -> String {
+        let package = &project.spec.package;
+        let systems = systems
+            .iter()
+            .map(|sources| nix_string(sources.system))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            r#"# Generated by sqlite-dist.
+{{
+  description = {description};
+
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+
+  outputs = {{ self, nixpkgs }}:
+    let
+      forAllSystems = nixpkgs.lib.genAttrs [ {systems} ];
+    in
+    {{
+      packages = forAllSystems (system:
+        let
+          package = import ./default.nix {{ pkgs = nixpkgs.legacyPackages.${{system}}; }};
+        in
+        {{
+          {name} = package;
+          default = package;
+        }});
+    }};
+}}
+"#,
+            description = nix_string(package.description.trim()),
+            name = nix_string(&package.name),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{project, release_asset};
+    use tempdir::TempDir;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn sri_hashes() {
+        assert_eq!(
+            sri_hash(EMPTY_SHA256),
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+
+    #[test]
+    fn nix_strings() {
+        assert_eq!(
+            templates::nix_string("say \"hi\" ${name} \\\n"),
+            r#""say \"hi\" \${name} \\\n""#
+        );
+    }
+
+    #[test]
+    fn nix_systems() {
+        let project = project("nix = {}", vec![]);
+        let assets = [
+            release_asset(true, (Os::Macos, Cpu::Universal), EMPTY_SHA256),
+            release_asset(true, (Os::Linux, Cpu::Aarch64), EMPTY_SHA256),
+            release_asset(true, (Os::Windows, Cpu::X86_64), EMPTY_SHA256),
+        ];
+        let dir = TempDir::new("sqlite-dist-nix").unwrap();
+        let generated = write_nix(&project, dir.path(), &assets).unwrap();
+        assert_eq!(generated.len(), 2);
+        let flake = fs::read_to_string(dir.path().join("flake.nix")).unwrap();
+        // the universal tarball covers both macOS systems
+        assert!(flake.contains(
+            "forAllSystems = nixpkgs.lib.genAttrs [ \"aarch64-linux\" \"x86_64-darwin\" \"aarch64-darwin\" ];"
+        ));
+        let default = fs::read_to_string(dir.path().join("default.nix")).unwrap();
+        assert!(default.contains("sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="));
+
+        let windows_only = [release_asset(
+            true,
+            (Os::Windows, Cpu::X86_64),
+            EMPTY_SHA256,
+        )];
+        assert!(write_nix(&project, dir.path(), &windows_only)
+            .unwrap()
+            .is_empty());
+    }
+}
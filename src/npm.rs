@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fs, io,
     path::{Path, PathBuf},
 };
@@ -44,7 +44,7 @@ pub struct PackageJson {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub types: Option<String>,
 
-    pub exports: HashMap<String, ExportTarget>,
+    pub exports: BTreeMap<String, ExportTarget>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<String>>,
@@ -53,26 +53,30 @@ pub struct PackageJson {
     pub keywords: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub dependencies: Option<HashMap<String, String>>,
+    pub dependencies: Option<BTreeMap<String, String>>,
 
     #[serde(rename = "optionalDependencies")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub optional_dependencies: Option<HashMap<String, String>>,
+    pub optional_dependencies: Option<BTreeMap<String, String>>,
 
     #[serde(rename = "devDependencies")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub dev_dependencies: Option<HashMap<String, String>>,
+    pub dev_dependencies: Option<BTreeMap<String, String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub os: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu: Option<Vec<String>>,
+
+    // "glibc" or "musl", so npm only installs the matching Linux package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub libc: Option<Vec<String>>,
 }
 
 use crate::{
-    create_targz, Cpu, GeneratedAsset, GeneratedAssetKind, Os, PlatformDirectory, PlatformFile,
-    Project,
+    create_targz, warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os,
+    PlatformDirectory, PlatformFile, Project,
 };
 
 use thiserror::Error;
@@ -87,8 +91,6 @@ pub enum NpmBuildError {
 
 struct NpmPlatformPackage {
     name: String,
-    os: Os,
-    cpu: Cpu,
     data: Vec<u8>,
 }
 pub(crate) fn write_npm_packages(
@@ -102,23 +104,33 @@ pub(crate) fn write_npm_packages(
         .platform_directories
         .iter()
         .filter(|platform_dir| {
-            matches!(platform_dir.os, Os::Linux | Os::Macos | Os::Windows)
-                && matches!(platform_dir.cpu, Cpu::X86_64 | Cpu::Aarch64)
+            let supported = matches!(
+                (&platform_dir.os, &platform_dir.cpu),
+                (
+                    Os::Linux | Os::LinuxMusl | Os::Macos | Os::Windows,
+                    Cpu::X86_64 | Cpu::Aarch64,
+                ) | (Os::Windows, Cpu::I686)
+            );
+            if !supported {
+                warn_unsupported_platform("npm", platform_dir);
+            }
+            supported
         })
         .collect();
-    let entrypoint = &npm_platform_directories
+    let loadable = npm_platform_directories
         .first()
         .unwrap()
         .loadable_files
         .first()
-        .unwrap()
-        .file_stem;
+        .unwrap();
+    let entrypoint = &loadable.file_stem;
+    let init_function = loadable.entrypoint(project.spec.package.entrypoint.as_deref());
 
     let platform_pkgs: Vec<PackageJson> = npm_platform_directories
         .iter()
         .map(|platform_dir| {
             let npm_os = match platform_dir.os {
-                Os::Linux => "linux",
+                Os::Linux | Os::LinuxMusl => "linux",
                 Os::Macos => "darwin",
                 Os::Windows => "win32",
                 _ => unreachable!(
@@ -127,7 +139,7 @@ pub(crate) fn write_npm_packages(
                 ),
             };
             let npm_pkg_os_name = match platform_dir.os {
-                Os::Linux => "linux",
+                Os::Linux | Os::LinuxMusl => "linux",
                 Os::Macos => "darwin",
                 Os::Windows => "windows",
                 _ => unreachable!(
@@ -138,17 +150,24 @@ pub(crate) fn write_npm_packages(
             let npm_cpu = match platform_dir.cpu {
                 Cpu::X86_64 => "x64",
                 Cpu::Aarch64 => "arm64",
+                Cpu::I686 => "ia32",
                 _ => unreachable!(
                     "Invalid npm CPU {:?} should be filtered from above.",
                     platform_dir.cpu
                 ),
             };
+            let (npm_libc, npm_pkg_libc_suffix) = match platform_dir.os {
+                Os::Linux => (Some("glibc"), ""),
+                Os::LinuxMusl => (Some("musl"), "-musl"),
+                _ => (None, ""),
+            };
             PackageJson {
                 name: format!(
-                    "{pkg}-{os}-{cpu}",
+                    "{pkg}-{os}-{cpu}{libc}",
                     pkg = project.spec.package.name,
                     os = npm_pkg_os_name,
-                    cpu = npm_cpu
+                    cpu = npm_cpu,
+                    libc = npm_pkg_libc_suffix
                 ),
                 version: project.version.to_string(),
                 author: author.clone(),
@@ -162,7 +181,7 @@ pub(crate) fn write_npm_packages(
                 main: Some("./index.cjs".to_owned()),
                 module: "./index.mjs".to_owned(),
                 types: Some("./index.d.ts".to_owned()),
-                exports: HashMap::from([(
+                exports: BTreeMap::from([(
                     ".".to_owned(),
                     ExportTarget {
                         require: Some("./index.cjs".to_owned()),
@@ -177,6 +196,7 @@ pub(crate) fn write_npm_packages(
                 dev_dependencies: None,
                 os: Some(vec![npm_os.to_owned()]),
                 cpu: Some(vec![npm_cpu.to_owned()]),
+                libc: npm_libc.map(|libc| vec![libc.to_owned()]),
             }
         })
         .collect();
@@ -199,9 +219,10 @@ pub(crate) fn write_npm_packages(
 
             Ok(NpmPlatformPackage {
                 name: pkg.name.clone(),
-                os: platform_dir.os.clone(),
-                cpu: platform_dir.cpu.clone(),
-                data: create_targz(&files.iter().collect::<Vec<&PlatformFile>>())?,
+                data: create_targz(
+                    &files.iter().collect::<Vec<&PlatformFile>>(),
+                    project.source_date_epoch,
+                )?,
             })
         })
         .collect();
@@ -221,7 +242,7 @@ pub(crate) fn write_npm_packages(
         main: Some("./index.cjs".to_owned()),
         module: "./index.mjs".to_owned(),
         types: Some("./index.d.ts".to_owned()),
-        exports: HashMap::from([(
+        exports: BTreeMap::from([(
             ".".to_owned(),
             ExportTarget {
                 require: Some("./index.cjs".to_owned()),
@@ -232,7 +253,7 @@ pub(crate) fn write_npm_packages(
         files: vec![].into(),
         keywords: vec![].into(),
         dependencies: None,
-        optional_dependencies: Some(HashMap::from_iter(
+        optional_dependencies: Some(BTreeMap::from_iter(
             platform_pkgs
                 .iter()
                 .map(|pkg| (pkg.name.clone(), pkg.version.clone())),
@@ -240,6 +261,7 @@ pub(crate) fn write_npm_packages(
         dev_dependencies: None,
         os: None,
         cpu: None,
+        libc: None,
     };
 
     let platforms = npm_platform_directories
@@ -247,7 +269,7 @@ pub(crate) fn write_npm_packages(
         .map(|pd| (pd.os.clone(), pd.cpu.clone()))
         .collect::<Vec<(Os, Cpu)>>();
     let pkg_name = project.spec.package.name.clone();
-    let top_pkg_targz_files = vec![
+    let top_pkg_targz_files = [
         PlatformFile::new("package/README.md", "TODO", None),
         PlatformFile::new(
             "package/package.json",
@@ -256,12 +278,24 @@ pub(crate) fn write_npm_packages(
         ),
         PlatformFile::new(
             "package/index.mjs",
-            templates::index_js(pkg_name.clone(), entrypoint, &platforms, JsFormat::ESM),
+            templates::index_js(
+                pkg_name.clone(),
+                entrypoint,
+                init_function.as_deref(),
+                &platforms,
+                JsFormat::ESM,
+            ),
             None,
         ),
         PlatformFile::new(
             "package/index.cjs",
-            templates::index_js(pkg_name.clone(), entrypoint, &platforms, JsFormat::CJS),
+            templates::index_js(
+                pkg_name.clone(),
+                entrypoint,
+                init_function.as_deref(),
+                &platforms,
+                JsFormat::CJS,
+            ),
             None,
         ),
         PlatformFile::new("package/index.d.ts", templates::index_dts(), None),
@@ -281,7 +315,7 @@ pub(crate) fn write_npm_packages(
             main: None,
             module: "./sqlite3.mjs".to_owned(),
             types: None,
-            exports: HashMap::from([(
+            exports: BTreeMap::from([(
                 ".".to_owned(),
                 ExportTarget {
                     require: None,
@@ -296,8 +330,9 @@ pub(crate) fn write_npm_packages(
             dev_dependencies: None,
             os: None,
             cpu: None,
+            libc: None,
         };
-        let wasm_pkg_targz_files = vec![
+        let wasm_pkg_targz_files = [
             PlatformFile::new("package/README.md", "TODO", None),
             PlatformFile::new(
                 "package/package.json",
@@ -315,25 +350,30 @@ pub(crate) fn write_npm_packages(
                 None,
             ),
         ];
-        let wasm_pkg_targz =
-            create_targz(&wasm_pkg_targz_files.iter().collect::<Vec<&PlatformFile>>())?;
+        let wasm_pkg_targz = create_targz(
+            &wasm_pkg_targz_files.iter().collect::<Vec<&PlatformFile>>(),
+            project.source_date_epoch,
+        )?;
         assets.push(GeneratedAsset::from(
-            GeneratedAssetKind::Npm(None),
+            GeneratedAssetKind::Npm,
             &npm_ouput_directory.join(format!("{}.tar.gz", wasm_pkg_json.name)),
             &wasm_pkg_targz,
         )?);
     }
-    let top_pkg_targz = create_targz(&top_pkg_targz_files.iter().collect::<Vec<&PlatformFile>>());
+    let top_pkg_targz = create_targz(
+        &top_pkg_targz_files.iter().collect::<Vec<&PlatformFile>>(),
+        project.source_date_epoch,
+    );
 
     for pkg in pkg_targzs {
         assets.push(GeneratedAsset::from(
-            GeneratedAssetKind::Npm(Some((pkg.os.clone(), pkg.cpu.clone()))),
+            GeneratedAssetKind::Npm,
             &npm_ouput_directory.join(format!("{}.tar.gz", pkg.name)),
             &pkg.data,
         )?);
     }
     assets.push(GeneratedAsset::from(
-        GeneratedAssetKind::Npm(None),
+        GeneratedAssetKind::Npm,
         &npm_ouput_directory.join(format!("{}.tar.gz", top_pkg.name)),
         &top_pkg_targz?,
     )?);
@@ -373,6 +413,7 @@ export declare function load(db: Db): void;
     pub(crate) fn index_js(
         pkg_name: String,
         entrypoint: &str,
+        init_function: Option<&str>,
         supported_platforms: &[(Os, Cpu)],
         format: JsFormat,
     ) -> String {
@@ -381,6 +422,15 @@ export declare function load(db: Db): void;
         let entrypoint_base_name =
             serde_json::to_string(&serde_json::Value::String(entrypoint.to_owned()))
                 .expect("String value should always serialize as JSON");
+        let load = match init_function {
+            Some(init_function) => {
+                let init_function =
+                    serde_json::to_string(&serde_json::Value::String(init_function.to_owned()))
+                        .expect("String value should always serialize as JSON");
+                format!("db.loadExtension(getLoadablePath(), {init_function});")
+            }
+            None => "db.loadExtension(getLoadablePath());".to_owned(),
+        };
 
         let supported_platforms: Vec<Vec<String>> = supported_platforms
             .iter()
@@ -442,9 +492,19 @@ function extensionSuffix(platform) {{
   if (platform === "darwin") return "dylib";
   return "so";
 }}
+function isMusl() {{
+  if (platform !== "linux") return false;
+  try {{
+    // glibcVersionRuntime is only reported when Node.js runs against glibc
+    return !process.report.getReport().header.glibcVersionRuntime;
+  }} catch {{
+    return false;
+  }}
+}}
 function platformPackageName(platform, arch) {{
   const os = platform === "win32" ? "windows" : platform;
-  return `${{BASE_PACKAGE_NAME}}-${{os}}-${{arch}}`;
+  const libc = isMusl() ? "-musl" : "";
+  return `${{BASE_PACKAGE_NAME}}-${{os}}-${{arch}}${{libc}}`;
 }}
 
 function getLoadablePath() {{
@@ -468,7 +528,7 @@ function getLoadablePath() {{
 }}
 
 function load(db) {{
-  db.loadExtension(getLoadablePath());
+  {load}
 }}
 
 {exports}
@@ -476,3 +536,101 @@ function load(db) {{
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::tests::{elf_object, macho_dylib, CPU_TYPE_ARM64, EM_X86_64};
+    use crate::tests::{platform_directory, project};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempdir::TempDir;
+
+    fn package_json(targz: &[u8]) -> serde_json::Value {
+        let mut archive = tar::Archive::new(GzDecoder::new(targz));
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.path().unwrap().as_ref() == Path::new("package/package.json") {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents).unwrap();
+                return serde_json::from_str(&contents).unwrap();
+            }
+        }
+        panic!("no package/package.json");
+    }
+
+    #[test]
+    fn platform_package_names_and_tags() {
+        let project = project(
+            "npm = {}",
+            vec![
+                platform_directory(Os::Linux, Cpu::X86_64, elf_object(EM_X86_64)),
+                platform_directory(Os::LinuxMusl, Cpu::X86_64, elf_object(EM_X86_64)),
+                platform_directory(Os::Macos, Cpu::Aarch64, macho_dylib(CPU_TYPE_ARM64, None)),
+                platform_directory(Os::Windows, Cpu::I686, vec![]),
+            ],
+        );
+        let dir = TempDir::new("sqlite-dist-npm").unwrap();
+        let assets = write_npm_packages(&project, dir.path(), &None).unwrap();
+
+        let packages: Vec<serde_json::Value> = assets
+            .iter()
+            .map(|asset| package_json(&fs::read(&asset.path).unwrap()))
+            .collect();
+        let tags: Vec<_> = packages
+            .iter()
+            .map(|pkg| {
+                (
+                    pkg["name"].as_str().unwrap(),
+                    pkg["os"].clone(),
+                    pkg["cpu"].clone(),
+                    pkg["libc"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                (
+                    "sqlite-sample-linux-x64",
+                    serde_json::json!(["linux"]),
+                    serde_json::json!(["x64"]),
+                    serde_json::json!(["glibc"])
+                ),
+                (
+                    "sqlite-sample-linux-x64-musl",
+                    serde_json::json!(["linux"]),
+                    serde_json::json!(["x64"]),
+                    serde_json::json!(["musl"])
+                ),
+                (
+                    "sqlite-sample-darwin-arm64",
+                    serde_json::json!(["darwin"]),
+                    serde_json::json!(["arm64"]),
+                    serde_json::Value::Null
+                ),
+                (
+                    "sqlite-sample-windows-ia32",
+                    serde_json::json!(["win32"]),
+                    serde_json::json!(["ia32"]),
+                    serde_json::Value::Null
+                ),
+                (
+                    "sqlite-sample",
+                    serde_json::Value::Null,
+                    serde_json::Value::Null,
+                    serde_json::Value::Null
+                ),
+            ]
+        );
+        assert_eq!(
+            packages[4]["optionalDependencies"],
+            serde_json::json!({
+                "sqlite-sample-linux-x64": "0.1.0",
+                "sqlite-sample-linux-x64-musl": "0.1.0",
+                "sqlite-sample-darwin-arm64": "0.1.0",
+                "sqlite-sample-windows-ia32": "0.1.0",
+            })
+        );
+    }
+}
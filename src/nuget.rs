@@ -0,0 +1,253 @@
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+use zip::result::ZipError;
+
+use crate::spec::{camel_case, TargetNuget};
+use crate::{
+    binary, create_zip, warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os,
+    PlatformFile, Project,
+};
+
+#[derive(Error, Debug)]
+pub enum NugetError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Zipfile error: {0}")]
+    ZipError(#[from] ZipError),
+    #[error("no linux, macos or windows platform directory has a loadable library")]
+    NoLibraries,
+}
+
+// https://learn.microsoft.com/en-us/dotnet/core/rid-catalog
+fn dotnet_rid(os: &Os, cpu: &Cpu) -> Option<&'static str> {
+    match (os, cpu) {
+        (Os::Linux, Cpu::X86_64) => Some("linux-x64"),
+        (Os::Linux, Cpu::Aarch64) => Some("linux-arm64"),
+        (Os::LinuxMusl, Cpu::X86_64) => Some("linux-musl-x64"),
+        (Os::LinuxMusl, Cpu::Aarch64) => Some("linux-musl-arm64"),
+        (Os::Macos, Cpu::X86_64) => Some("osx-x64"),
+        (Os::Macos, Cpu::Aarch64) => Some("osx-arm64"),
+        (Os::Windows, Cpu::X86_64) => Some("win-x64"),
+        (Os::Windows, Cpu::Aarch64) => Some("win-arm64"),
+        (Os::Windows, Cpu::I686) => Some("win-x86"),
+        _ => None,
+    }
+}
+
+/// Writes a .nupkg with every desktop loadable under runtimes/<rid>/native/, and
+/// a C# helper as a content file that's compiled into the consuming project.
+pub(crate) fn write_nuget(
+    project: &Project,
+    nuget_path: &Path,
+    nuget_config: &TargetNuget,
+) -> Result<Vec<GeneratedAsset>, NugetError> {
+    let mut native_files = vec![];
+    for platform_dir in &project.platform_directories {
+        let Some(rid) = dotnet_rid(&platform_dir.os, &platform_dir.cpu) else {
+            warn_unsupported_platform("nuget", platform_dir);
+            continue;
+        };
+        if let Some(loadable) = platform_dir.loadable_files.first() {
+            native_files.push((rid, loadable));
+        }
+    }
+    let Some((_, first)) = native_files.first() else {
+        return Err(NugetError::NoLibraries);
+    };
+
+    let package = &project.spec.package;
+    let class_name = format!("{}Extension", camel_case(&package.name));
+    let entrypoint = package
+        .entrypoint
+        .clone()
+        .unwrap_or_else(|| binary::init_function(&first.file.name, &first.init_symbols));
+    let file_names: Vec<(&str, &str)> = native_files
+        .iter()
+        .map(|(rid, loadable)| (*rid, loadable.file.name.as_str()))
+        .collect();
+
+    let nuspec_name = format!("{}.nuspec", package.name);
+    let mut files = vec![
+        PlatformFile::new("[Content_Types].xml", templates::content_types_xml(), None),
+        PlatformFile::new("_rels/.rels", templates::rels(&nuspec_name), None),
+        PlatformFile::new(nuspec_name, templates::nuspec(project), None),
+        PlatformFile::new(
+            format!("contentFiles/cs/any/{class_name}.cs"),
+            templates::extension_cs(
+                &package.name,
+                &nuget_config.namespace(&package.name),
+                &class_name,
+                &entrypoint,
+                &file_names,
+            ),
+            None,
+        ),
+    ];
+    for (rid, loadable) in &native_files {
+        files.push(PlatformFile::new(
+            format!("runtimes/{rid}/native/{}", loadable.file.name),
+            loadable.file.data.clone(),
+            None,
+        ));
+    }
+
+    Ok(vec![GeneratedAsset::from(
+        GeneratedAssetKind::Nuget,
+        &nuget_path.join(format!("{}.{}.nupkg", package.name, project.version)),
+        &create_zip(&files, project.source_date_epoch)?,
+    )?])
+}
+
+mod templates {
+    use crate::{xml_escape, Project};
+
+    // .nupkg files are Open Packaging Convention zips
+    pub(crate) fn content_types_xml() -> String {
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+  <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml" />
+  <Default Extension="nuspec" ContentType="application/octet" />
+  <Default Extension="cs" ContentType="application/octet" />
+  <Default Extension="so" ContentType="application/octet" />
+  <Default Extension="dylib" ContentType="application/octet" />
+  <Default Extension="dll" ContentType="application/octet" />
+</Types>
+"#
+        .to_owned()
+    }
+
+    pub(crate) fn rels(nuspec_name: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Type="http://schemas.microsoft.com/packaging/2010/07/manifest" Target="/{nuspec_name}" Id="nuspec" />
+</Relationships>
+"#
+        )
+    }
+
+    pub(crate) fn nuspec(project: &Project) -> String {
+        let package = &project.spec.package;
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://schemas.microsoft.com/packaging/2013/05/nuspec.xsd">
+  <metadata>
+    <id>{id}</id>
+    <version>{version}</version>
+    <authors>{authors}</authors>
+    <description>{description}</description>
+    <projectUrl>{homepage}</projectUrl>
+    <license type="expression">{license}</license>
+    <repository type="git" url="{repo}" />
+    <tags>sqlite sqlite-extension</tags>
+    <dependencies>
+      <dependency id="Microsoft.Data.Sqlite.Core" version="5.0.0" />
+    </dependencies>
+    <contentFiles>
+      <files include="cs/any/*.cs" buildAction="Compile" />
+    </contentFiles>
+  </metadata>
+</package>
+"#,
+            id = package.name,
+            version = project.version,
+            authors = xml_escape(&package.authors.join(", ")),
+            description = xml_escape(&package.description),
+            homepage = xml_escape(&package.homepage),
+            license = xml_escape(&package.license),
+            repo = xml_escape(&package.repo),
+        )
+    }
+
+    pub(crate) fn extension_cs(
+        package_name: &str,
+        namespace: &str,
+        class_name: &str,
+        entrypoint: &str,
+        file_names: &[(&str, &str)],
+    ) -> String {
+        let cases: String = file_names
+            .iter()
+            .map(|(rid, file_name)| {
+                format!("                case \"{rid}\": return \"{file_name}\";\n")
+            })
+            .collect();
+        format!(
+            r#"// <auto-generated/>
+using System;
+using System.IO;
+using System.Runtime.InteropServices;
+using Microsoft.Data.Sqlite;
+
+namespace {namespace}
+{{
+    /// <summary>The {package_name} SQLite extension bundled with this package.</summary>
+    internal static class {class_name}
+    {{
+        /// <summary>The extension's init function.</summary>
+        public const string Entrypoint = "{entrypoint}";
+
+        private static string RuntimeIdentifier
+        {{
+            get
+            {{
+                string os;
+                if (RuntimeInformation.IsOSPlatform(OSPlatform.Windows))
+                    os = "win";
+                else if (RuntimeInformation.IsOSPlatform(OSPlatform.OSX))
+                    os = "osx";
+                else if (Directory.Exists("/lib") && Directory.GetFiles("/lib", "ld-musl-*").Length > 0)
+                    os = "linux-musl";
+                else
+                    os = "linux";
+                string arch;
+                switch (RuntimeInformation.ProcessArchitecture)
+                {{
+                    case Architecture.X64: arch = "x64"; break;
+                    case Architecture.Arm64: arch = "arm64"; break;
+                    case Architecture.X86: arch = "x86"; break;
+                    default: arch = RuntimeInformation.ProcessArchitecture.ToString().ToLowerInvariant(); break;
+                }}
+                return os + "-" + arch;
+            }}
+        }}
+
+        private static string FileName(string rid)
+        {{
+            switch (rid)
+            {{
+{cases}                default: return null;
+            }}
+        }}
+
+        /// <summary>Path of the extension for the running platform.</summary>
+        public static string LoadablePath
+        {{
+            get
+            {{
+                string rid = RuntimeIdentifier;
+                string fileName = FileName(rid);
+                if (fileName == null)
+                    throw new PlatformNotSupportedException("{package_name} has no build for " + rid);
+                // RID-specific builds copy native assets next to the app, others keep runtimes/
+                string path = Path.Combine(AppContext.BaseDirectory, fileName);
+                if (File.Exists(path))
+                    return path;
+                return Path.Combine(AppContext.BaseDirectory, "runtimes", rid, "native", fileName);
+            }}
+        }}
+
+        /// <summary>Enables extension loading on the connection and loads {package_name}.</summary>
+        public static void Load(SqliteConnection connection)
+        {{
+            connection.EnableExtensions(true);
+            connection.LoadExtension(LoadablePath, Entrypoint);
+        }}
+    }}
+}}
+"#
+        )
+    }
+}
@@ -1,18 +1,20 @@
 use std::{
-    collections::HashMap,
+    fs,
     io::{self, Cursor, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use crate::{AssetPipWheel, Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
+use crate::binary::{self, OsVersion};
+use crate::{
+    warn_unsupported_platform, zip_file_options, AssetPipWheel, Cpu, GeneratedAsset,
+    GeneratedAssetKind, LoadablePlatformFile, Os, PlatformDirectory, PlatformFile, Project,
+};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use semver::Version;
 use sha2::{Digest, Sha256};
 use zip::{result::ZipError, write::FileOptions, ZipWriter};
 
 mod templates {
-    use crate::{pip::platform_target_tag, Cpu, Os};
-
     use super::PipPackage;
 
     pub(crate) fn dist_info_metadata(pkg: &PipPackage) -> String {
@@ -51,13 +53,9 @@ TODO readme"
 
         txt
     }
-    pub(crate) fn dist_info_wheel(platform: Option<(&Os, &Cpu)>) -> String {
+    pub(crate) fn dist_info_wheel(platform_tag: &str) -> String {
         let name = env!("CARGO_PKG_NAME");
         let version = env!("CARGO_PKG_VERSION");
-        let platform_tag = match platform {
-            Some((os, cpu)) => platform_target_tag(os, cpu),
-            None => "any".to_owned(),
-        };
         let tag = format!("py3-none-{platform_tag}");
         format!(
             "Wheel-Version: 1.0
@@ -81,9 +79,25 @@ Tag: {tag}",
 
         record
     }
-    pub(crate) fn base_init_py(pkg: &PipPackage, entrypoint: &str) -> String {
+    pub(crate) fn base_init_py(
+        pkg: &PipPackage,
+        entrypoint: &str,
+        init_function: Option<&str>,
+    ) -> String {
         let version = &pkg.package_version;
         let package_name = &pkg.package_name;
+        // sqlite3.Connection.load_extension() only accepts an entrypoint on Python 3.12+
+        let load = match init_function {
+            Some(init_function) => {
+                let init_function =
+                    serde_json::to_string(&serde_json::Value::String(init_function.to_owned()))
+                        .expect("String value should always serialize as JSON");
+                format!(
+                    r#"conn.execute("select load_extension(?, ?)", [loadable_path(), {init_function}])"#
+                )
+            }
+            None => "conn.load_extension(loadable_path())".to_owned(),
+        };
         format!(
             r#"
 from os import path
@@ -101,7 +115,7 @@ def loadable_path():
 def load(conn: sqlite3.Connection)  -> None:
   """ Load the {package_name} SQLite extension into the given database connection. """
 
-  conn.load_extension(loadable_path())
+  {load}
 
 "#,
         )
@@ -191,16 +205,71 @@ fn semver_to_pip_version(v: &Version) -> String {
     }*/
 }
 
-pub fn platform_target_tag(os: &Os, cpu: &Cpu) -> String {
-    match (os, cpu) {
-        (Os::Macos, Cpu::X86_64) => "macosx_10_6_x86_64".to_owned(),
-        (Os::Macos, Cpu::Aarch64) => "macosx_11_0_arm64".to_owned(),
-        (Os::Linux, Cpu::X86_64) => {
-            "manylinux_2_17_x86_64.manylinux2014_x86_64.manylinux1_x86_64".to_owned()
+/// Floor of the manylinux tag, the oldest glibc each CPU has a manylinux policy for.
+fn min_glibc_version(cpu: &Cpu) -> OsVersion {
+    match cpu {
+        Cpu::Aarch64 => (2, 17),
+        _ => (2, 5),
+    }
+}
+
+// manylinux_2_17_x86_64.manylinux2014_x86_64, keeping the legacy alias for pip < 20.3
+fn manylinux_tag(glibc: OsVersion, arch: &str) -> String {
+    let tag = format!("manylinux_{}_{}_{arch}", glibc.0, glibc.1);
+    let legacy = match glibc {
+        (2, 5) => "manylinux1",
+        (2, 12) => "manylinux2010",
+        (2, 17) => "manylinux2014",
+        _ => return tag,
+    };
+    format!("{tag}.{legacy}_{arch}")
+}
+
+fn macosx_tag(macos: OsVersion, arch: &str) -> String {
+    // pip only considers macosx_X_0 for macOS 11 and above
+    let (major, minor) = if macos.0 >= 11 { (macos.0, 0) } else { macos };
+    format!("macosx_{major}_{minor}_{arch}")
+}
+
+/// Wheel platform tag for a platform directory, from the glibc symbol versions or
+/// minimum macOS version its loadables require.
+pub fn platform_target_tag(platform_dir: &PlatformDirectory) -> String {
+    let loadables = platform_dir.loadable_files.iter().map(|f| &f.file.data);
+    match (&platform_dir.os, &platform_dir.cpu) {
+        (Os::Macos, cpu) => {
+            let (arch, fallback) = match cpu {
+                Cpu::X86_64 => ("x86_64", (10, 6)),
+                Cpu::Aarch64 => ("arm64", (11, 0)),
+                Cpu::Universal => ("universal2", (10, 9)),
+                _ => {
+                    unreachable!("Invalid pip platform macos-{cpu}, should have been filtered out")
+                }
+            };
+            // arm64 binaries can't target anything before macOS 11, and universal2
+            // wheels anything before 10.9, so the fallback is also the floor there
+            let macos = loadables
+                .filter_map(|data| binary::macos_version(data))
+                .max()
+                .map_or(fallback, |version| match cpu {
+                    Cpu::Aarch64 | Cpu::Universal => version.max(fallback),
+                    _ => version,
+                });
+            macosx_tag(macos, arch)
+        }
+        (Os::Linux, cpu @ (Cpu::X86_64 | Cpu::Aarch64)) => {
+            let glibc = loadables
+                .filter_map(|data| binary::glibc_version(data))
+                .max()
+                .unwrap_or_default()
+                .max(min_glibc_version(cpu));
+            manylinux_tag(glibc, &cpu.to_string())
         }
-        (Os::Linux, Cpu::Aarch64) => "manylinux_2_17_aarch64.manylinux2014_aarch64".to_owned(),
+        // musl 1.2, shipped since Alpine 3.13
+        (Os::LinuxMusl, cpu @ (Cpu::X86_64 | Cpu::Aarch64)) => format!("musllinux_1_2_{cpu}"),
         (Os::Windows, Cpu::X86_64) => "win_amd64".to_owned(),
-        _ => {
+        (Os::Windows, Cpu::Aarch64) => "win_arm64".to_owned(),
+        (Os::Windows, Cpu::I686) => "win32".to_owned(),
+        (os, cpu) => {
             unreachable!(
                 "Invalid pip platform {:?}-{:?} provided, should have been filtered out",
                 os, cpu
@@ -222,10 +291,16 @@ pub struct PipPackage {
 
     pub entrypoints: Vec<(String, String)>,
     pub extra_metadata: Vec<(String, String)>,
+
+    zip_options: FileOptions,
 }
 
 impl PipPackage {
-    pub fn new<S: Into<String>>(package_name: S, package_version: &Version) -> Self {
+    pub fn new<S: Into<String>>(
+        package_name: S,
+        package_version: &Version,
+        source_date_epoch: Option<u64>,
+    ) -> Self {
         let buffer = Cursor::new(Vec::new());
         let zipfile = zip::ZipWriter::new(buffer);
         let package_name = package_name.into();
@@ -237,6 +312,8 @@ impl PipPackage {
             written_files: vec![],
             entrypoints: vec![],
             extra_metadata: vec![],
+            zip_options: zip_file_options(source_date_epoch)
+                .compression_method(zip::CompressionMethod::Stored),
         }
     }
 
@@ -244,21 +321,16 @@ impl PipPackage {
         self.entrypoints.push((key.to_owned(), value.to_owned()));
     }
 
-    pub fn wheel_name(&self, platform: Option<(&Os, &Cpu)>) -> String {
+    pub fn wheel_name(&self, platform_tag: &str) -> String {
         let name = &self.python_package_name;
         let version = &self.package_version;
         let python_tag = "py3";
         let abi_tag = "none";
-        let platform_tag = match platform {
-            Some((os, cpu)) => platform_target_tag(os, cpu),
-            None => "any".to_owned(),
-        };
         format!("{name}-{version}-{python_tag}-{abi_tag}-{platform_tag}.whl")
     }
 
     fn write_file(&mut self, path: &str, data: &[u8]) -> Result<(), ZipError> {
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
-        self.zipfile.start_file(path, options)?;
+        self.zipfile.start_file(path, self.zip_options)?;
         self.zipfile.write_all(data)?;
         self.written_files.push(PipPackageFile::new(path, data));
         Ok(())
@@ -298,10 +370,10 @@ impl PipPackage {
             templates::dist_info_top_level_txt(self).as_bytes(),
         )
     }
-    fn write_dist_info_wheel(&mut self, platform: Option<(&Os, &Cpu)>) -> Result<(), ZipError> {
+    fn write_dist_info_wheel(&mut self, platform_tag: &str) -> Result<(), ZipError> {
         self.write_file(
             self.dist_info_file("WHEEL").as_str(),
-            templates::dist_info_wheel(platform).as_bytes(),
+            templates::dist_info_wheel(platform_tag).as_bytes(),
         )
     }
     fn write_dist_info_entrypoints(&mut self) -> Result<(), ZipError> {
@@ -311,9 +383,9 @@ impl PipPackage {
         )
     }
 
-    pub fn end(mut self, platform: Option<(&Os, &Cpu)>) -> Result<Cursor<Vec<u8>>, ZipError> {
+    pub fn end(mut self, platform_tag: &str) -> Result<Cursor<Vec<u8>>, ZipError> {
         self.write_dist_info_metadata()?;
-        self.write_dist_info_wheel(platform)?;
+        self.write_dist_info_wheel(platform_tag)?;
         if !self.entrypoints.is_empty() {
             self.write_dist_info_entrypoints()?;
         }
@@ -331,55 +403,138 @@ pub enum PipBuildError {
     ZipError(#[from] ZipError),
     #[error("I/O error: {0}")]
     IOError(#[from] io::Error),
+    #[error("No loadable file found in {0}")]
+    MissingLoadable(String),
+}
+
+pub const DEFAULT_PYODIDE_ABI: &str = "pyodide_2024_0";
+
+fn pyodide_loadable_files(pyodide_dir: &Path) -> io::Result<Vec<LoadablePlatformFile>> {
+    let mut loadable_files = vec![];
+    let mut entry_paths = fs::read_dir(pyodide_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entry_paths.sort();
+    for entry_path in entry_paths {
+        if entry_path.extension().and_then(|e| e.to_str()) != Some("so") {
+            continue;
+        }
+        let (Some(name), Some(file_stem)) = (
+            entry_path.file_name().and_then(|n| n.to_str()),
+            entry_path.file_stem().and_then(|n| n.to_str()),
+        ) else {
+            continue;
+        };
+        loadable_files.push(LoadablePlatformFile::new(
+            file_stem.to_owned(),
+            PlatformFile::new(
+                name,
+                fs::read(&entry_path)?,
+                Some(fs::metadata(&entry_path)?),
+            ),
+        ));
+    }
+    Ok(loadable_files)
+}
+
+fn write_wheel(
+    project: &Project,
+    pip_path: &Path,
+    source_dir: &Path,
+    loadable_files: &[LoadablePlatformFile],
+    platform_tag: &str,
+    kind: AssetPipWheel,
+) -> Result<GeneratedAsset, PipBuildError> {
+    let mut pkg = PipPackage::new(
+        &project.spec.package.name,
+        &project.version,
+        project.source_date_epoch,
+    );
+    let Some(loadable) = loadable_files.first() else {
+        return Err(PipBuildError::MissingLoadable(
+            source_dir.display().to_string(),
+        ));
+    };
+    let init_function = loadable.entrypoint(project.spec.package.entrypoint.as_deref());
+    let mut init_py = templates::base_init_py(&pkg, &loadable.file_stem, init_function.as_deref());
+    if let Some(extra_init_py) = project
+        .spec
+        .targets
+        .pip
+        .as_ref()
+        .and_then(|pip| pip.extra_init_py.as_deref())
+    {
+        let contents = std::fs::read_to_string(project.spec_directory.join(extra_init_py))?;
+        init_py += &contents;
+    }
+    pkg.write_library_file("__init__.py", init_py.as_bytes())?;
+
+    for f in loadable_files {
+        pkg.write_library_file(f.file.name.as_str(), &f.file.data)?;
+    }
+    let wheel_name = pkg.wheel_name(platform_tag);
+    let result = pkg.end(platform_tag)?.into_inner();
+    Ok(GeneratedAsset::from(
+        GeneratedAssetKind::Pip(kind),
+        &pip_path.join(wheel_name),
+        &result,
+    )?)
 }
 
 pub(crate) fn write_base_packages(
     project: &Project,
     pip_path: &Path,
+    pyodide_dir: &Option<PathBuf>,
 ) -> Result<Vec<GeneratedAsset>, PipBuildError> {
     let mut assets = vec![];
-    for platform_dir in &project.platform_directories {
+    for platform_dir in project
+        .platform_directories
+        .iter()
+        .chain(&project.macos_universal)
+    {
         // only a subset of platforms are supported in pip
         match (&platform_dir.os, &platform_dir.cpu) {
             (Os::Macos, Cpu::X86_64)
             | (Os::Macos, Cpu::Aarch64)
+            | (Os::Macos, Cpu::Universal)
             | (Os::Linux, Cpu::X86_64)
             | (Os::Linux, Cpu::Aarch64)
-            | (Os::Windows, Cpu::X86_64) => (),
-            //(Os::Linux, Cpu::Aarch64) => todo!(),
-            //(Os::Windows, Cpu::Aarch64) => todo!(),
-            _ => continue,
+            | (Os::LinuxMusl, Cpu::X86_64)
+            | (Os::LinuxMusl, Cpu::Aarch64)
+            | (Os::Windows, Cpu::X86_64)
+            | (Os::Windows, Cpu::Aarch64)
+            | (Os::Windows, Cpu::I686) => (),
+            _ => {
+                warn_unsupported_platform("pip", platform_dir);
+                continue;
+            }
         }
-        let mut pkg = PipPackage::new(&project.spec.package.name, &project.version);
-        assert!(!platform_dir.loadable_files.is_empty());
-        let entrypoint = &platform_dir.loadable_files.first().expect("TODO").file_stem;
-        let mut init_py = templates::base_init_py(&pkg, entrypoint);
-        if let Some(extra_init_py) = project
+        assets.push(write_wheel(
+            project,
+            pip_path,
+            &platform_dir.path,
+            &platform_dir.loadable_files,
+            &platform_target_tag(platform_dir),
+            AssetPipWheel::Standard((platform_dir.os.clone(), platform_dir.cpu.clone())),
+        )?);
+    }
+
+    if let Some(pyodide_dir) = pyodide_dir {
+        let loadable_files = pyodide_loadable_files(pyodide_dir)?;
+        let abi = project
             .spec
             .targets
             .pip
             .as_ref()
-            .and_then(|pip| pip.extra_init_py.as_deref())
-        {
-            let contents = std::fs::read_to_string(project.spec_directory.join(extra_init_py))?;
-            init_py += &contents;
-        }
-        pkg.write_library_file("__init__.py", init_py.as_bytes())?;
-
-        for f in &platform_dir.loadable_files {
-            pkg.write_library_file(f.file.name.as_str(), &f.file.data)?;
-        }
-        let platform = Some((&platform_dir.os, &platform_dir.cpu));
-        let wheel_name = pkg.wheel_name(platform);
-        let result = pkg.end(platform)?.into_inner();
-        let wheel_path = pip_path.join(wheel_name);
-        assets.push(GeneratedAsset::from(
-            GeneratedAssetKind::Pip(AssetPipWheel::Standard((
-                platform_dir.os.clone(),
-                platform_dir.cpu.clone(),
-            ))),
-            &wheel_path,
-            &result,
+            .and_then(|pip| pip.pyodide_abi.as_deref())
+            .unwrap_or(DEFAULT_PYODIDE_ABI);
+        assets.push(write_wheel(
+            project,
+            pip_path,
+            pyodide_dir,
+            &loadable_files,
+            &format!("{abi}_wasm32"),
+            AssetPipWheel::Pyodide,
         )?);
     }
     Ok(assets)
@@ -390,8 +545,16 @@ pub(crate) fn write_datasette(
     datasette_path: &Path,
 ) -> Result<GeneratedAsset, PipBuildError> {
     let datasette_package_name = format!("datasette-{}", project.spec.package.name);
-    let dep_pkg = PipPackage::new(&project.spec.package.name, &project.version);
-    let mut pkg = PipPackage::new(datasette_package_name.clone(), &project.version);
+    let dep_pkg = PipPackage::new(
+        &project.spec.package.name,
+        &project.version,
+        project.source_date_epoch,
+    );
+    let mut pkg = PipPackage::new(
+        datasette_package_name.clone(),
+        &project.version,
+        project.source_date_epoch,
+    );
     pkg.write_library_file(
         "__init__.py",
         templates::datasette_init_py(&dep_pkg).as_bytes(),
@@ -412,8 +575,8 @@ pub(crate) fn write_datasette(
         format!("{} (=={})", &project.spec.package.name, &project.version),
     ));
 
-    let wheel_name = pkg.wheel_name(None);
-    let result = pkg.end(None)?.into_inner();
+    let wheel_name = pkg.wheel_name("any");
+    let result = pkg.end("any")?.into_inner();
     Ok(GeneratedAsset::from(
         GeneratedAssetKind::Datasette,
         &datasette_path.join(wheel_name),
@@ -426,8 +589,16 @@ pub(crate) fn write_sqlite_utils(
     sqlite_utils_path: &Path,
 ) -> Result<GeneratedAsset, PipBuildError> {
     let sqlite_utils_name = format!("sqlite-utils-{}", project.spec.package.name);
-    let dep_pkg = PipPackage::new(&project.spec.package.name, &project.version);
-    let mut pkg = PipPackage::new(sqlite_utils_name.clone(), &project.version);
+    let dep_pkg = PipPackage::new(
+        &project.spec.package.name,
+        &project.version,
+        project.source_date_epoch,
+    );
+    let mut pkg = PipPackage::new(
+        sqlite_utils_name.clone(),
+        &project.version,
+        project.source_date_epoch,
+    );
     pkg.write_library_file(
         "__init__.py",
         templates::sqlite_utils_init_py(&dep_pkg).as_bytes(),
@@ -449,12 +620,97 @@ pub(crate) fn write_sqlite_utils(
         format!("{} (=={})", &project.spec.package.name, &project.version),
     ));
 
-    let wheel_name = pkg.wheel_name(None);
+    let wheel_name = pkg.wheel_name("any");
 
-    let result = pkg.end(None)?.into_inner();
+    let result = pkg.end("any")?.into_inner();
     Ok(GeneratedAsset::from(
         GeneratedAssetKind::SqliteUtils,
         &sqlite_utils_path.join(wheel_name),
         &result,
     )?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::tests::{
+        elf_object, macho_dylib, CPU_TYPE_ARM64, CPU_TYPE_X86_64, EM_AARCH64, EM_X86_64,
+    };
+    use crate::tests::platform_directory;
+
+    #[test]
+    fn manylinux_tags() {
+        assert_eq!(
+            manylinux_tag((2, 17), "aarch64"),
+            "manylinux_2_17_aarch64.manylinux2014_aarch64"
+        );
+        assert_eq!(
+            manylinux_tag((2, 5), "x86_64"),
+            "manylinux_2_5_x86_64.manylinux1_x86_64"
+        );
+        assert_eq!(manylinux_tag((2, 28), "x86_64"), "manylinux_2_28_x86_64");
+    }
+
+    #[test]
+    fn macosx_tags() {
+        assert_eq!(macosx_tag((10, 13), "x86_64"), "macosx_10_13_x86_64");
+        assert_eq!(macosx_tag((12, 3), "arm64"), "macosx_12_0_arm64");
+    }
+
+    #[test]
+    fn platform_target_tags() {
+        for (os, cpu, loadable, tag) in [
+            (
+                Os::Linux,
+                Cpu::X86_64,
+                elf_object(EM_X86_64),
+                "manylinux_2_5_x86_64.manylinux1_x86_64",
+            ),
+            (
+                Os::Linux,
+                Cpu::Aarch64,
+                elf_object(EM_AARCH64),
+                "manylinux_2_17_aarch64.manylinux2014_aarch64",
+            ),
+            (
+                Os::LinuxMusl,
+                Cpu::X86_64,
+                elf_object(EM_X86_64),
+                "musllinux_1_2_x86_64",
+            ),
+            (
+                Os::Macos,
+                Cpu::X86_64,
+                macho_dylib(CPU_TYPE_X86_64, Some((10, 13))),
+                "macosx_10_13_x86_64",
+            ),
+            (
+                Os::Macos,
+                Cpu::X86_64,
+                macho_dylib(CPU_TYPE_X86_64, None),
+                "macosx_10_6_x86_64",
+            ),
+            // arm64 can't target anything before macOS 11
+            (
+                Os::Macos,
+                Cpu::Aarch64,
+                macho_dylib(CPU_TYPE_ARM64, Some((10, 15))),
+                "macosx_11_0_arm64",
+            ),
+            (
+                Os::Macos,
+                Cpu::Aarch64,
+                macho_dylib(CPU_TYPE_ARM64, Some((13, 1))),
+                "macosx_13_0_arm64",
+            ),
+            (Os::Windows, Cpu::X86_64, vec![], "win_amd64"),
+            (Os::Windows, Cpu::Aarch64, vec![], "win_arm64"),
+            (Os::Windows, Cpu::I686, vec![], "win32"),
+        ] {
+            assert_eq!(
+                platform_target_tag(&platform_directory(os, cpu, loadable)),
+                tag
+            );
+        }
+    }
+}
@@ -0,0 +1,629 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::GzDecoder;
+use semver::Version;
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use thiserror::Error;
+
+use crate::manifest::{read_manifest, Manifest, ManifestArtifact, MANIFEST_FILE_NAME};
+
+pub const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+pub const DEFAULT_GITHUB_UPLOADS_URL: &str = "https://uploads.github.com";
+pub const DEFAULT_NPM_REGISTRY_URL: &str = "https://registry.npmjs.org";
+pub const DEFAULT_RUBYGEMS_URL: &str = "https://rubygems.org";
+pub const DEFAULT_PYPI_URL: &str = "https://upload.pypi.org/legacy/";
+
+#[derive(Error, Debug)]
+pub enum PublishError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("HTTP request to {0} failed: {1}")]
+    HttpError(String, String),
+    #[error("environment variable `{0}` is required to publish to {1}")]
+    MissingToken(String, String),
+    #[error("invalid manifest: {0}")]
+    InvalidManifest(String),
+    #[error("invalid artifact {0}: {1}")]
+    InvalidArtifact(String, String),
+}
+
+/// Base URLs of the registries that artifacts are uploaded to. Overridable so
+/// publishing can be exercised against local stand-in servers.
+pub struct Registries {
+    pub github_api: String,
+    pub github_uploads: String,
+    pub npm: String,
+    pub rubygems: String,
+    pub pypi: String,
+}
+
+/// Where a kind of generated asset gets published to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Registry {
+    GithubReleases,
+    Npm,
+    Rubygems,
+    Pypi,
+}
+
+/// What `publish` does with a kind of generated asset.
+enum Destination {
+    Registry(Registry),
+    /// Built for other tools to consume, ex committed to a tap or served as a repository
+    NotPublished,
+    Unknown,
+}
+
+impl Registry {
+    // keys are the serialized form of GeneratedAssetKind
+    fn for_kind(kind: &str) -> Destination {
+        match kind {
+            "github-release-loadable"
+            | "github-release-static"
+            | "sqlpkg"
+            | "spm"
+            | "amalgamation"
+            | "xcframework" => Destination::Registry(Registry::GithubReleases),
+            "npm" => Destination::Registry(Registry::Npm),
+            "gem" => Destination::Registry(Registry::Rubygems),
+            "pip" | "datasette" | "sqlite-utils" => Destination::Registry(Registry::Pypi),
+            "swift-package"
+            | "android"
+            | "maven"
+            | "nuget"
+            | "go"
+            | "cargo"
+            | "homebrew"
+            | "nix"
+            | "pip-pyodide"
+            | "deb"
+            | "rpm"
+            | "apk"
+            | "sqlite-dist-manifest" => Destination::NotPublished,
+            _ => Destination::Unknown,
+        }
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            Registry::GithubReleases => "GitHub Releases",
+            Registry::Npm => "npm",
+            Registry::Rubygems => "RubyGems",
+            Registry::Pypi => "PyPI",
+        }
+    }
+}
+
+fn token(variables: &[&str], registry: Registry) -> Result<String, PublishError> {
+    variables
+        .iter()
+        .find_map(|v| std::env::var(v).ok().filter(|value| !value.is_empty()))
+        .ok_or_else(|| {
+            PublishError::MissingToken(variables.join("` or `"), registry.name().to_owned())
+        })
+}
+
+fn http_error(url: &str, error: ureq::Error) -> PublishError {
+    let message = match error {
+        ureq::Error::Status(status, response) => {
+            let body = response.into_string().unwrap_or_default();
+            format!("status {status}: {body}")
+        }
+        ureq::Error::Transport(transport) => transport.to_string(),
+    };
+    PublishError::HttpError(url.to_owned(), message)
+}
+
+struct Artifact<'a> {
+    meta: &'a ManifestArtifact,
+    path: PathBuf,
+}
+
+impl Artifact<'_> {
+    fn read(&self) -> Result<Vec<u8>, PublishError> {
+        let data = fs::read(&self.path)?;
+        let checksum = base16ct::lower::encode_string(&Sha256::digest(&data));
+        if checksum != self.meta.checksum_sha256 {
+            return Err(PublishError::InvalidArtifact(
+                self.meta.name.clone(),
+                "checksum does not match the manifest".to_owned(),
+            ));
+        }
+        Ok(data)
+    }
+}
+
+fn github_repo(manifest: &Manifest) -> Result<(String, String), PublishError> {
+    let repo = manifest
+        .build_info
+        .repo
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+    let mut parts = repo.rsplit('/');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(owner)) if !name.is_empty() && !owner.is_empty() => {
+            Ok((owner.to_owned(), name.to_owned()))
+        }
+        _ => Err(PublishError::InvalidManifest(format!(
+            "could not determine GitHub owner/repository from '{}'",
+            manifest.build_info.repo
+        ))),
+    }
+}
+
+fn publish_github_release(
+    registries: &Registries,
+    manifest: &Manifest,
+    dir: &Path,
+    artifacts: &[Artifact],
+) -> Result<(), PublishError> {
+    let token = token(&["GITHUB_TOKEN", "GH_TOKEN"], Registry::GithubReleases)?;
+    let auth = format!("Bearer {token}");
+    let (owner, repo) = github_repo(manifest)?;
+    let tag = &manifest.build_info.git_tag;
+
+    let url = format!(
+        "{}/repos/{owner}/{repo}/releases/tags/{tag}",
+        registries.github_api
+    );
+    let release: Value = match ureq::get(&url)
+        .set("Authorization", &auth)
+        .set("Accept", "application/vnd.github+json")
+        .call()
+    {
+        Ok(response) => response.into_json()?,
+        Err(ureq::Error::Status(404, _)) => {
+            let url = format!("{}/repos/{owner}/{repo}/releases", registries.github_api);
+            let prerelease = Version::parse(&manifest.build_info.version)
+                .map(|v| !v.pre.is_empty())
+                .unwrap_or(false);
+            ureq::post(&url)
+                .set("Authorization", &auth)
+                .set("Accept", "application/vnd.github+json")
+                .send_json(json!({
+                    "tag_name": tag,
+                    "name": tag,
+                    "prerelease": prerelease,
+                }))
+                .map_err(|e| http_error(&url, e))?
+                .into_json()?
+        }
+        Err(error) => return Err(http_error(&url, error)),
+    };
+    let release_id = release["id"].as_u64().ok_or_else(|| {
+        PublishError::HttpError(url.clone(), "release response is missing an id".to_owned())
+    })?;
+
+    let mut uploads: Vec<(String, Vec<u8>)> = vec![];
+    for artifact in artifacts {
+        uploads.push((artifact.meta.name.clone(), artifact.read()?));
+    }
+    // files written next to the manifest that aren't tracked as artifacts
+    for name in ["checksums.txt", "install.sh", MANIFEST_FILE_NAME] {
+        let path = dir.join(name);
+        if path.exists() {
+            uploads.push((name.to_owned(), fs::read(path)?));
+        }
+    }
+
+    // assets left by an earlier, partially failed publish are skipped when
+    // unchanged and replaced otherwise, since uploading an existing name fails
+    let existing: HashMap<&str, &Value> = release["assets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| Some((asset["name"].as_str()?, asset)))
+        .collect();
+
+    for (name, data) in uploads {
+        if let Some(asset) = existing.get(name.as_str()) {
+            let digest = format!(
+                "sha256:{}",
+                base16ct::lower::encode_string(&Sha256::digest(&data))
+            );
+            if asset["digest"].as_str() == Some(digest.as_str()) {
+                println!("Skipping {name}, already uploaded to GitHub release {tag}");
+                continue;
+            }
+            let url = format!(
+                "{}/repos/{owner}/{repo}/releases/assets/{}",
+                registries.github_api, asset["id"]
+            );
+            println!("Replacing {name} in GitHub release {tag}");
+            ureq::delete(&url)
+                .set("Authorization", &auth)
+                .set("Accept", "application/vnd.github+json")
+                .call()
+                .map_err(|e| http_error(&url, e))?;
+        }
+        let url = format!(
+            "{}/repos/{owner}/{repo}/releases/{release_id}/assets",
+            registries.github_uploads
+        );
+        println!("Uploading {name} to GitHub release {tag}");
+        ureq::post(&url)
+            .query("name", &name)
+            .set("Authorization", &auth)
+            .set("Accept", "application/vnd.github+json")
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&data)
+            .map_err(|e| http_error(&url, e))?;
+    }
+    Ok(())
+}
+
+fn npm_package_json(name: &str, tarball: &[u8]) -> Result<Value, PublishError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_ref() == Path::new("package/package.json") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            return Ok(serde_json::from_str(&contents)?);
+        }
+    }
+    Err(PublishError::InvalidArtifact(
+        name.to_owned(),
+        "package/package.json not found in npm tarball".to_owned(),
+    ))
+}
+
+fn publish_npm(registries: &Registries, artifacts: &[Artifact]) -> Result<(), PublishError> {
+    let token = token(&["NPM_TOKEN"], Registry::Npm)?;
+    let registry = registries.npm.trim_end_matches('/');
+    for artifact in artifacts {
+        let data = artifact.read()?;
+        let mut package_json = npm_package_json(&artifact.meta.name, &data)?;
+        let (Some(name), Some(version)) = (
+            package_json["name"].as_str().map(str::to_owned),
+            package_json["version"].as_str().map(str::to_owned),
+        ) else {
+            return Err(PublishError::InvalidArtifact(
+                artifact.meta.name.clone(),
+                "package.json is missing name or version".to_owned(),
+            ));
+        };
+        let unscoped_name = name.rsplit('/').next().unwrap_or(&name);
+        let tarball_name = format!("{unscoped_name}-{version}.tgz");
+        package_json["_id"] = json!(format!("{name}@{version}"));
+        package_json["dist"] = json!({
+            "shasum": base16ct::lower::encode_string(&Sha1::digest(&data)),
+            "integrity": format!("sha512-{}", STANDARD.encode(Sha512::digest(&data))),
+            "tarball": format!("{registry}/{name}/-/{tarball_name}"),
+        });
+        let dist_tag = if Version::parse(&version).is_ok_and(|v| !v.pre.is_empty()) {
+            "next"
+        } else {
+            "latest"
+        };
+        let document = json!({
+            "_id": name,
+            "name": name,
+            "description": package_json["description"],
+            "dist-tags": { dist_tag: version },
+            "versions": { version.clone(): package_json },
+            "access": "public",
+            "_attachments": {
+                tarball_name: {
+                    "content_type": "application/octet-stream",
+                    "data": STANDARD.encode(&data),
+                    "length": data.len(),
+                }
+            },
+        });
+
+        let url = format!("{registry}/{}", name.replace('/', "%2f"));
+        println!("Publishing {name}@{version} to npm");
+        ureq::put(&url)
+            .set("Authorization", &format!("Bearer {token}"))
+            .send_json(document)
+            .map_err(|e| http_error(&url, e))?;
+    }
+    Ok(())
+}
+
+fn publish_rubygems(registries: &Registries, artifacts: &[Artifact]) -> Result<(), PublishError> {
+    let token = token(&["GEM_HOST_API_KEY"], Registry::Rubygems)?;
+    let url = format!("{}/api/v1/gems", registries.rubygems.trim_end_matches('/'));
+    for artifact in artifacts {
+        let data = artifact.read()?;
+        println!("Pushing {} to RubyGems", artifact.meta.name);
+        ureq::post(&url)
+            .set("Authorization", &token)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&data)
+            .map_err(|e| http_error(&url, e))?;
+    }
+    Ok(())
+}
+
+/// multipart/form-data body for the PyPI legacy upload API
+fn pypi_upload_body(
+    boundary: &str,
+    fields: &[(&str, &str)],
+    filename: &str,
+    content: &[u8],
+) -> Vec<u8> {
+    let mut body = vec![];
+    for (key, value) in fields {
+        body.extend(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{key}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"content\"; filename=\"{filename}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend(content);
+    body.extend(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+fn publish_pypi(registries: &Registries, artifacts: &[Artifact]) -> Result<(), PublishError> {
+    let password = token(&["PYPI_TOKEN", "TWINE_PASSWORD"], Registry::Pypi)?;
+    let username = std::env::var("TWINE_USERNAME").unwrap_or_else(|_| "__token__".to_owned());
+    let auth = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{username}:{password}"))
+    );
+    let url = &registries.pypi;
+    for artifact in artifacts {
+        let filename = &artifact.meta.name;
+        // {name}-{version}-{python tag}-{abi tag}-{platform tag}.whl
+        let mut parts = filename.split('-');
+        let (Some(name), Some(version)) = (parts.next(), parts.next()) else {
+            return Err(PublishError::InvalidArtifact(
+                filename.clone(),
+                "not a valid wheel file name".to_owned(),
+            ));
+        };
+        let data = artifact.read()?;
+        let sha256_digest = base16ct::lower::encode_string(&Sha256::digest(&data));
+        let boundary = format!("sqlite-dist-{}", &sha256_digest[..16]);
+        let body = pypi_upload_body(
+            &boundary,
+            &[
+                (":action", "file_upload"),
+                ("protocol_version", "1"),
+                ("metadata_version", "2.1"),
+                ("name", name),
+                ("version", version),
+                ("filetype", "bdist_wheel"),
+                ("pyversion", "py3"),
+                ("sha256_digest", &sha256_digest),
+            ],
+            filename,
+            &data,
+        );
+        println!("Uploading {filename} to PyPI");
+        ureq::post(url)
+            .set("Authorization", &auth)
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={boundary}"),
+            )
+            .send_bytes(&body)
+            .map_err(|e| http_error(url, e))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn publish(
+    dir: &Path,
+    registries: &Registries,
+    dry_run: bool,
+) -> Result<(), PublishError> {
+    let manifest = read_manifest(dir)?;
+
+    let mut groups: HashMap<Registry, Vec<Artifact>> = HashMap::new();
+    for meta in &manifest.artifacts {
+        match Registry::for_kind(&meta.kind) {
+            Destination::Registry(registry) => groups.entry(registry).or_default().push(Artifact {
+                meta,
+                path: dir.join(&meta.path),
+            }),
+            Destination::NotPublished => println!(
+                "Skipping {}, {} assets are not published by `publish`",
+                meta.name, meta.kind
+            ),
+            Destination::Unknown => println!(
+                "Warning: skipping {}, unknown kind '{}'",
+                meta.name, meta.kind
+            ),
+        }
+    }
+
+    // GitHub first, so the release URLs that other packages point to exist
+    for registry in [
+        Registry::GithubReleases,
+        Registry::Npm,
+        Registry::Rubygems,
+        Registry::Pypi,
+    ] {
+        let Some(artifacts) = groups.get(&registry) else {
+            continue;
+        };
+        if dry_run {
+            for artifact in artifacts {
+                println!(
+                    "Would publish {} to {}",
+                    artifact.path.display(),
+                    registry.name()
+                );
+            }
+            continue;
+        }
+        match registry {
+            Registry::GithubReleases => {
+                publish_github_release(registries, &manifest, dir, artifacts)?
+            }
+            Registry::Npm => publish_npm(registries, artifacts)?,
+            Registry::Rubygems => publish_rubygems(registries, artifacts)?,
+            Registry::Pypi => publish_pypi(registries, artifacts)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssetPipWheel, Cpu, GeneratedAssetKind, GithubRelease, Os};
+    use flate2::{write::GzEncoder, Compression};
+    use tempdir::TempDir;
+
+    fn destination(kind: GeneratedAssetKind) -> Option<Registry> {
+        match Registry::for_kind(&kind.to_string()) {
+            Destination::Registry(registry) => Some(registry),
+            Destination::NotPublished => None,
+            Destination::Unknown => panic!("{kind} has no destination"),
+        }
+    }
+
+    fn release() -> GithubRelease {
+        GithubRelease {
+            url: "https://github.com/asg017/sqlite-sample/releases/download/v0.1.0/x.tar.gz"
+                .to_owned(),
+            platform: (Os::Linux, Cpu::X86_64),
+        }
+    }
+
+    fn npm_tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn every_kind_has_a_destination() {
+        let published = [
+            (GeneratedAssetKind::Npm, Registry::Npm),
+            (GeneratedAssetKind::Gem, Registry::Rubygems),
+            (
+                GeneratedAssetKind::Pip(AssetPipWheel::Standard((Os::Linux, Cpu::X86_64))),
+                Registry::Pypi,
+            ),
+            (GeneratedAssetKind::Datasette, Registry::Pypi),
+            (GeneratedAssetKind::SqliteUtils, Registry::Pypi),
+            (
+                GeneratedAssetKind::GithubReleaseLoadable(release()),
+                Registry::GithubReleases,
+            ),
+            (
+                GeneratedAssetKind::GithubReleaseStatic(release()),
+                Registry::GithubReleases,
+            ),
+            (GeneratedAssetKind::Sqlpkg, Registry::GithubReleases),
+            (GeneratedAssetKind::Spm, Registry::GithubReleases),
+            (GeneratedAssetKind::Amalgamation, Registry::GithubReleases),
+            (GeneratedAssetKind::Xcframework, Registry::GithubReleases),
+        ];
+        for (kind, registry) in published {
+            assert_eq!(destination(kind), Some(registry));
+        }
+        let not_published = [
+            GeneratedAssetKind::Pip(AssetPipWheel::Pyodide),
+            GeneratedAssetKind::SwiftPackage,
+            GeneratedAssetKind::Android,
+            GeneratedAssetKind::Maven,
+            GeneratedAssetKind::Nuget,
+            GeneratedAssetKind::Go,
+            GeneratedAssetKind::Cargo,
+            GeneratedAssetKind::Homebrew,
+            GeneratedAssetKind::Nix,
+            GeneratedAssetKind::Deb,
+            GeneratedAssetKind::Rpm,
+            GeneratedAssetKind::Apk,
+            GeneratedAssetKind::Manifest,
+        ];
+        for kind in not_published {
+            assert_eq!(destination(kind), None);
+        }
+        assert!(matches!(Registry::for_kind("wasm"), Destination::Unknown));
+    }
+
+    #[test]
+    fn reads_package_json_from_npm_tarballs() {
+        let tarball = npm_tarball(&[
+            ("package/README.md", "# sqlite-sample\n"),
+            (
+                "package/package.json",
+                r#"{"name": "sqlite-sample", "version": "0.1.0"}"#,
+            ),
+        ]);
+        let package_json = npm_package_json("sqlite-sample.tgz", &tarball).unwrap();
+        assert_eq!(package_json["name"], "sqlite-sample");
+        assert_eq!(package_json["version"], "0.1.0");
+
+        let tarball = npm_tarball(&[("package/README.md", "# sqlite-sample\n")]);
+        assert!(matches!(
+            npm_package_json("sqlite-sample.tgz", &tarball),
+            Err(PublishError::InvalidArtifact(name, _)) if name == "sqlite-sample.tgz"
+        ));
+    }
+
+    #[test]
+    fn pypi_upload_body_is_multipart() {
+        let body = pypi_upload_body(
+            "b",
+            &[("name", "sqlite-sample"), ("version", "0.1.0")],
+            "sqlite_sample-0.1.0-py3-none-any.whl",
+            b"wheel",
+        );
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--b\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nsqlite-sample\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"version\"\r\n\r\n0.1.0\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"content\"; filename=\"sqlite_sample-0.1.0-py3-none-any.whl\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\nwheel\r\n--b--\r\n"
+        );
+    }
+
+    #[test]
+    fn artifacts_must_match_the_manifest_checksum() {
+        let dir = TempDir::new("sqlite-dist-publish").unwrap();
+        let path = dir.path().join("sqlite-sample.tgz");
+        fs::write(&path, b"tarball").unwrap();
+        let mut meta = ManifestArtifact {
+            kind: "npm".to_owned(),
+            name: "sqlite-sample.tgz".to_owned(),
+            path: "sqlite-sample.tgz".to_owned(),
+            checksum_sha256: base16ct::lower::encode_string(&Sha256::digest(b"tarball")),
+            size: 7,
+            platform: None,
+        };
+        let artifact = Artifact {
+            meta: &meta,
+            path: path.clone(),
+        };
+        assert_eq!(artifact.read().unwrap(), b"tarball");
+
+        meta.checksum_sha256 = base16ct::lower::encode_string(&Sha256::digest(b"other"));
+        let artifact = Artifact { meta: &meta, path };
+        assert!(matches!(
+            artifact.read(),
+            Err(PublishError::InvalidArtifact(_, message)) if message == "checksum does not match the manifest"
+        ));
+    }
+}
@@ -0,0 +1,590 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{warn_unsupported_platform, Cpu, GeneratedAsset, GeneratedAssetKind, Os, Project};
+
+// https://rpm-software-management.github.io/rpm/manual/format_v4.html
+const RPM_INT16_TYPE: u32 = 3;
+const RPM_INT32_TYPE: u32 = 4;
+const RPM_STRING_TYPE: u32 = 6;
+const RPM_BIN_TYPE: u32 = 7;
+const RPM_STRING_ARRAY_TYPE: u32 = 8;
+const RPM_I18NSTRING_TYPE: u32 = 9;
+
+const RPMTAG_HEADERSIGNATURES: u32 = 62;
+const RPMTAG_HEADERIMMUTABLE: u32 = 63;
+const RPMTAG_HEADERI18NTABLE: u32 = 100;
+
+const RPMSIGTAG_SHA1: u32 = 269;
+const RPMSIGTAG_SHA256: u32 = 273;
+const RPMSIGTAG_SIZE: u32 = 1000;
+const RPMSIGTAG_MD5: u32 = 1004;
+const RPMSIGTAG_PAYLOADSIZE: u32 = 1007;
+
+const RPMTAG_NAME: u32 = 1000;
+const RPMTAG_VERSION: u32 = 1001;
+const RPMTAG_RELEASE: u32 = 1002;
+const RPMTAG_SUMMARY: u32 = 1004;
+const RPMTAG_DESCRIPTION: u32 = 1005;
+const RPMTAG_BUILDTIME: u32 = 1006;
+const RPMTAG_BUILDHOST: u32 = 1007;
+const RPMTAG_SIZE: u32 = 1009;
+const RPMTAG_LICENSE: u32 = 1014;
+const RPMTAG_GROUP: u32 = 1016;
+const RPMTAG_URL: u32 = 1020;
+const RPMTAG_OS: u32 = 1021;
+const RPMTAG_ARCH: u32 = 1022;
+const RPMTAG_FILESIZES: u32 = 1028;
+const RPMTAG_FILEMODES: u32 = 1030;
+const RPMTAG_FILERDEVS: u32 = 1033;
+const RPMTAG_FILEMTIMES: u32 = 1034;
+const RPMTAG_FILEDIGESTS: u32 = 1035;
+const RPMTAG_FILELINKTOS: u32 = 1036;
+const RPMTAG_FILEFLAGS: u32 = 1037;
+const RPMTAG_FILEUSERNAME: u32 = 1039;
+const RPMTAG_FILEGROUPNAME: u32 = 1040;
+const RPMTAG_SOURCERPM: u32 = 1044;
+const RPMTAG_PROVIDENAME: u32 = 1047;
+const RPMTAG_REQUIREFLAGS: u32 = 1048;
+const RPMTAG_REQUIRENAME: u32 = 1049;
+const RPMTAG_REQUIREVERSION: u32 = 1050;
+const RPMTAG_FILEDEVICES: u32 = 1095;
+const RPMTAG_FILEINODES: u32 = 1096;
+const RPMTAG_FILELANGS: u32 = 1097;
+const RPMTAG_PROVIDEFLAGS: u32 = 1112;
+const RPMTAG_PROVIDEVERSION: u32 = 1113;
+const RPMTAG_DIRINDEXES: u32 = 1116;
+const RPMTAG_BASENAMES: u32 = 1117;
+const RPMTAG_DIRNAMES: u32 = 1118;
+const RPMTAG_PAYLOADFORMAT: u32 = 1124;
+const RPMTAG_PAYLOADCOMPRESSOR: u32 = 1125;
+const RPMTAG_PAYLOADFLAGS: u32 = 1126;
+const RPMTAG_FILEDIGESTALGO: u32 = 5011;
+const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+const PGPHASHALGO_SHA256: u32 = 8;
+const RPMSENSE_LESS: u32 = 1 << 1;
+const RPMSENSE_EQUAL: u32 = 1 << 3;
+const RPMSENSE_RPMLIB: u32 = 1 << 24;
+
+// the rpmlib() features the package format relies on
+const RPMLIB_REQUIRES: [(&str, &str); 3] = [
+    ("rpmlib(CompressedFileNames)", "3.0.4-1"),
+    ("rpmlib(FileDigests)", "4.6.0-1"),
+    ("rpmlib(PayloadFilesHavePrefix)", "4.0-1"),
+];
+// needed by versions with a "~", which older rpm versions misorder
+const RPMLIB_TILDE_IN_VERSIONS: (&str, &str) = ("rpmlib(TildeInVersions)", "4.10.0-1");
+
+/// RPM architecture, lead architecture number and %{_libdir}.
+fn rpm_architecture(cpu: &Cpu) -> Option<(&'static str, u16, &'static str)> {
+    match cpu {
+        Cpu::X86_64 => Some(("x86_64", 1, "/usr/lib64")),
+        Cpu::Aarch64 => Some(("aarch64", 19, "/usr/lib64")),
+        Cpu::I686 => Some(("i686", 1, "/usr/lib")),
+        Cpu::Armv7a => Some(("armv7hl", 12, "/usr/lib")),
+        Cpu::Universal => None,
+    }
+}
+
+/// RPM versions can't contain "-" and sort "~" before anything, so semver
+/// pre-releases become "~" suffixes. The release is always 1.
+fn rpm_version(version: &semver::Version) -> (String, String) {
+    let mut rpm = format!("{}.{}.{}", version.major, version.minor, version.patch);
+    if !version.pre.is_empty() {
+        rpm.push_str(&format!("~{}", version.pre));
+    }
+    if !version.build.is_empty() {
+        rpm.push_str(&format!("+{}", version.build));
+    }
+    (rpm.replace('-', "_"), "1".to_owned())
+}
+
+/// A header structure: an index of tagged, typed values and their data store.
+struct Header {
+    // tag, type, count and data
+    entries: Vec<(u32, u32, u32, Vec<u8>)>,
+}
+
+impl Header {
+    fn new() -> Self {
+        Self { entries: vec![] }
+    }
+    fn entry(&mut self, tag: u32, type_: u32, count: usize, data: Vec<u8>) -> &mut Self {
+        self.entries.push((tag, type_, count as u32, data));
+        self
+    }
+    fn string(&mut self, tag: u32, value: &str) -> &mut Self {
+        self.entry(tag, RPM_STRING_TYPE, 1, nul_terminated(&[value]))
+    }
+    // translatable, with the one "C" locale from the i18n table
+    fn i18n_string(&mut self, tag: u32, value: &str) -> &mut Self {
+        self.entry(tag, RPM_I18NSTRING_TYPE, 1, nul_terminated(&[value]))
+    }
+    fn strings<S: AsRef<str>>(&mut self, tag: u32, values: &[S]) -> &mut Self {
+        let data = nul_terminated(values);
+        self.entry(tag, RPM_STRING_ARRAY_TYPE, values.len(), data)
+    }
+    fn int16(&mut self, tag: u32, values: &[u16]) -> &mut Self {
+        let data = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.entry(tag, RPM_INT16_TYPE, values.len(), data)
+    }
+    fn int32(&mut self, tag: u32, values: &[u32]) -> &mut Self {
+        let data = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.entry(tag, RPM_INT32_TYPE, values.len(), data)
+    }
+    fn bin(&mut self, tag: u32, value: &[u8]) -> &mut Self {
+        self.entry(tag, RPM_BIN_TYPE, value.len(), value.to_vec())
+    }
+
+    /// The header, with every entry inside an immutable region tagged
+    /// `region_tag`, which rpm expects first.
+    fn to_bytes(&self, region_tag: u32) -> Vec<u8> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(tag, ..)| *tag);
+        let index_length = entries.len() + 1;
+
+        let mut index = vec![];
+        let mut store = vec![];
+        for (tag, type_, count, data) in entries {
+            let alignment = match *type_ {
+                RPM_INT16_TYPE => 2,
+                RPM_INT32_TYPE => 4,
+                _ => 1,
+            };
+            store.resize(store.len().next_multiple_of(alignment), 0);
+            for value in [*tag, *type_, store.len() as u32, *count] {
+                index.extend(value.to_be_bytes());
+            }
+            store.extend(data);
+        }
+        // the region trailer points back at the start of the index
+        let trailer_offset = store.len() as u32;
+        for value in [
+            region_tag,
+            RPM_BIN_TYPE,
+            (-(index_length as i32 * 16)) as u32,
+            16,
+        ] {
+            store.extend(value.to_be_bytes());
+        }
+
+        let mut header = vec![0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+        header.extend((index_length as u32).to_be_bytes());
+        header.extend((store.len() as u32).to_be_bytes());
+        for value in [region_tag, RPM_BIN_TYPE, trailer_offset, 16] {
+            header.extend(value.to_be_bytes());
+        }
+        header.extend(index);
+        header.extend(store);
+        header
+    }
+}
+
+fn nul_terminated<S: AsRef<str>>(values: &[S]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.as_ref().bytes().chain([0]))
+        .collect()
+}
+
+/// A file or directory in the package, under its absolute path.
+struct RpmFile<'a> {
+    path: String,
+    mode: u32,
+    data: &'a [u8],
+}
+
+/// The payload, an SVR4 "newc" cpio archive with "./"-prefixed paths.
+fn create_cpio(files: &[RpmFile], mtime: u32) -> Vec<u8> {
+    let mut cpio = vec![];
+    let entries = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| (format!(".{}", file.path), index + 1, file.mode, file.data))
+        .chain([("TRAILER!!!".to_owned(), 0, 0, &[][..])]);
+    for (name, inode, mode, data) in entries {
+        let nlink = if mode & 0o040000 != 0 { 2 } else { 1 };
+        let mtime = if inode == 0 { 0 } else { mtime };
+        cpio.extend(b"070701");
+        for field in [
+            inode as u32,
+            mode,
+            0,
+            0,
+            nlink,
+            mtime,
+            data.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ] {
+            cpio.extend(format!("{field:08x}").as_bytes());
+        }
+        cpio.extend(name.as_bytes());
+        cpio.push(0);
+        cpio.resize(cpio.len().next_multiple_of(4), 0);
+        cpio.extend(data);
+        cpio.resize(cpio.len().next_multiple_of(4), 0);
+    }
+    cpio
+}
+
+fn create_rpm(
+    project: &Project,
+    files: &[RpmFile],
+    (arch, archnum): (&str, u16),
+) -> io::Result<Vec<u8>> {
+    let package = &project.spec.package;
+    let (version, release) = rpm_version(&project.version);
+    let nvr = format!("{}-{version}-{release}", package.name);
+    let build_time = project.source_date_epoch.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }) as u32;
+
+    let cpio = create_cpio(files, build_time);
+    let mut payload = GzEncoder::new(vec![], Compression::best());
+    payload.write_all(&cpio)?;
+    let payload = payload.finish()?;
+
+    // paths are stored as directory indexes and base names
+    let mut dirnames: Vec<String> = vec![];
+    let mut dirindexes = vec![];
+    let mut basenames = vec![];
+    for file in files {
+        let (dirname, basename) = file.path.rsplit_once('/').unwrap();
+        let dirname = format!("{dirname}/");
+        let index = dirnames
+            .iter()
+            .position(|d| *d == dirname)
+            .unwrap_or_else(|| {
+                dirnames.push(dirname);
+                dirnames.len() - 1
+            });
+        dirindexes.push(index as u32);
+        basenames.push(basename);
+    }
+    let file_count = files.len();
+    let summary = package
+        .description
+        .trim()
+        .lines()
+        .next()
+        .unwrap_or(&package.name);
+
+    let mut rpmlib_requires = RPMLIB_REQUIRES.to_vec();
+    if version.contains('~') {
+        rpmlib_requires.push(RPMLIB_TILDE_IN_VERSIONS);
+    }
+
+    let mut header = Header::new();
+    header
+        .strings(RPMTAG_HEADERI18NTABLE, &["C"])
+        .string(RPMTAG_NAME, &package.name)
+        .string(RPMTAG_VERSION, &version)
+        .string(RPMTAG_RELEASE, &release)
+        .i18n_string(RPMTAG_SUMMARY, summary)
+        .i18n_string(RPMTAG_DESCRIPTION, package.description.trim())
+        .int32(RPMTAG_BUILDTIME, &[build_time])
+        .string(RPMTAG_BUILDHOST, "localhost")
+        .int32(
+            RPMTAG_SIZE,
+            &[files.iter().map(|file| file.data.len() as u32).sum()],
+        )
+        .string(RPMTAG_LICENSE, &package.license)
+        .i18n_string(RPMTAG_GROUP, "Unspecified")
+        .string(RPMTAG_URL, &package.homepage)
+        .string(RPMTAG_OS, "linux")
+        .string(RPMTAG_ARCH, arch)
+        .int32(
+            RPMTAG_FILESIZES,
+            &files
+                .iter()
+                .map(|file| file.data.len() as u32)
+                .collect::<Vec<_>>(),
+        )
+        .int16(
+            RPMTAG_FILEMODES,
+            &files
+                .iter()
+                .map(|file| file.mode as u16)
+                .collect::<Vec<_>>(),
+        )
+        .int16(RPMTAG_FILERDEVS, &vec![0; file_count])
+        .int32(RPMTAG_FILEMTIMES, &vec![build_time; file_count])
+        .strings(
+            RPMTAG_FILEDIGESTS,
+            &files
+                .iter()
+                .map(|file| match file.mode & 0o040000 {
+                    0 => base16ct::lower::encode_string(&Sha256::digest(file.data)),
+                    _ => String::new(),
+                })
+                .collect::<Vec<_>>(),
+        )
+        .strings(RPMTAG_FILELINKTOS, &vec![""; file_count])
+        .int32(RPMTAG_FILEFLAGS, &vec![0; file_count])
+        .strings(RPMTAG_FILEUSERNAME, &vec!["root"; file_count])
+        .strings(RPMTAG_FILEGROUPNAME, &vec!["root"; file_count])
+        // binary packages are told apart from source ones by having this
+        .string(RPMTAG_SOURCERPM, &format!("{nvr}.src.rpm"))
+        .strings(RPMTAG_PROVIDENAME, &[&package.name])
+        .int32(RPMTAG_PROVIDEFLAGS, &[RPMSENSE_EQUAL])
+        .strings(RPMTAG_PROVIDEVERSION, &[format!("{version}-{release}")])
+        .strings(
+            RPMTAG_REQUIRENAME,
+            &rpmlib_requires
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>(),
+        )
+        .int32(
+            RPMTAG_REQUIREFLAGS,
+            &vec![RPMSENSE_RPMLIB | RPMSENSE_LESS | RPMSENSE_EQUAL; rpmlib_requires.len()],
+        )
+        .strings(
+            RPMTAG_REQUIREVERSION,
+            &rpmlib_requires
+                .iter()
+                .map(|(_, version)| *version)
+                .collect::<Vec<_>>(),
+        )
+        .int32(RPMTAG_FILEDEVICES, &vec![1; file_count])
+        .int32(
+            RPMTAG_FILEINODES,
+            &(1..=file_count as u32).collect::<Vec<_>>(),
+        )
+        .strings(RPMTAG_FILELANGS, &vec![""; file_count])
+        .int32(RPMTAG_DIRINDEXES, &dirindexes)
+        .strings(RPMTAG_BASENAMES, &basenames)
+        .strings(RPMTAG_DIRNAMES, &dirnames)
+        .string(RPMTAG_PAYLOADFORMAT, "cpio")
+        .string(RPMTAG_PAYLOADCOMPRESSOR, "gzip")
+        .string(RPMTAG_PAYLOADFLAGS, "9")
+        .int32(RPMTAG_FILEDIGESTALGO, &[PGPHASHALGO_SHA256])
+        .strings(
+            RPMTAG_PAYLOADDIGEST,
+            &[base16ct::lower::encode_string(&Sha256::digest(&payload))],
+        )
+        .int32(RPMTAG_PAYLOADDIGESTALGO, &[PGPHASHALGO_SHA256]);
+    let header = header.to_bytes(RPMTAG_HEADERIMMUTABLE);
+
+    let mut header_and_payload = header.clone();
+    header_and_payload.extend(&payload);
+    let mut signature = Header::new();
+    signature
+        .string(
+            RPMSIGTAG_SHA1,
+            &base16ct::lower::encode_string(&Sha1::digest(&header)),
+        )
+        .string(
+            RPMSIGTAG_SHA256,
+            &base16ct::lower::encode_string(&Sha256::digest(&header)),
+        )
+        .int32(RPMSIGTAG_SIZE, &[header_and_payload.len() as u32])
+        .bin(RPMSIGTAG_MD5, &Md5::digest(&header_and_payload))
+        .int32(RPMSIGTAG_PAYLOADSIZE, &[cpio.len() as u32]);
+    let mut signature = signature.to_bytes(RPMTAG_HEADERSIGNATURES);
+    // the header that follows starts on an 8 byte boundary
+    signature.resize(signature.len().next_multiple_of(8), 0);
+
+    let mut rpm = vec![0xed, 0xab, 0xee, 0xdb, 3, 0];
+    rpm.extend(0u16.to_be_bytes()); // binary package
+    rpm.extend(archnum.to_be_bytes());
+    let mut name = [0u8; 66];
+    let nvr_bytes = &nvr.as_bytes()[..nvr.len().min(65)];
+    name[..nvr_bytes.len()].copy_from_slice(nvr_bytes);
+    rpm.extend(name);
+    rpm.extend(1u16.to_be_bytes()); // linux
+    rpm.extend(5u16.to_be_bytes()); // header-style signature
+    rpm.extend([0; 16]);
+    rpm.extend(signature);
+    rpm.extend(header_and_payload);
+    Ok(rpm)
+}
+
+/// Writes an .rpm per linux platform directory with the loadable in
+/// %{_libdir}/sqlite3/.
+pub(crate) fn write_rpm(project: &Project, rpm_path: &Path) -> io::Result<Vec<GeneratedAsset>> {
+    let (version, release) = rpm_version(&project.version);
+    let mut assets = vec![];
+    for platform_dir in &project.platform_directories {
+        let (arch, archnum, libdir) = match (&platform_dir.os, rpm_architecture(&platform_dir.cpu))
+        {
+            (Os::Linux, Some(architecture)) => architecture,
+            // RHEL and Fedora are glibc-based, so musl builds are skipped too
+            (Os::Linux | Os::LinuxMusl, _) => {
+                warn_unsupported_platform("rpm", platform_dir);
+                continue;
+            }
+            _ => continue,
+        };
+        if platform_dir.loadable_files.is_empty() {
+            continue;
+        }
+        let sqlite3_dir = format!("{libdir}/sqlite3");
+        let mut files = vec![RpmFile {
+            path: sqlite3_dir.clone(),
+            mode: 0o040755,
+            data: &[],
+        }];
+        files.extend(platform_dir.loadable_files.iter().map(|loadable| RpmFile {
+            path: format!("{sqlite3_dir}/{}", loadable.file.name),
+            mode: 0o100755,
+            data: &loadable.file.data,
+        }));
+        // rpm looks files up by path, so they're sorted
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assets.push(GeneratedAsset::from(
+            GeneratedAssetKind::Rpm,
+            &rpm_path.join(format!(
+                "{}-{version}-{release}.{arch}.rpm",
+                project.spec.package.name
+            )),
+            &create_rpm(project, &files, (arch, archnum))?,
+        )?);
+    }
+    if assets.is_empty() {
+        println!("Warning: no linux platform directory found, skipping the rpm target");
+    }
+    Ok(assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    fn be32(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// name, mode, nlink, mtime and data of each newc cpio entry.
+    fn cpio_entries(cpio: &[u8]) -> Vec<(String, u32, u32, u32, Vec<u8>)> {
+        let mut entries = vec![];
+        let mut offset = 0;
+        while offset < cpio.len() {
+            assert_eq!(&cpio[offset..offset + 6], b"070701");
+            let field = |index: usize| {
+                let start = offset + 6 + index * 8;
+                u32::from_str_radix(std::str::from_utf8(&cpio[start..start + 8]).unwrap(), 16)
+                    .unwrap()
+            };
+            let (mode, nlink, mtime) = (field(1), field(4), field(5));
+            let (size, name_size) = (field(6) as usize, field(11) as usize);
+            let name_start = offset + 110;
+            let name =
+                String::from_utf8(cpio[name_start..name_start + name_size - 1].to_vec()).unwrap();
+            assert_eq!(cpio[name_start + name_size - 1], 0);
+            let data_start = (name_start + name_size).next_multiple_of(4);
+            entries.push((
+                name,
+                mode,
+                nlink,
+                mtime,
+                cpio[data_start..data_start + size].to_vec(),
+            ));
+            offset = (data_start + size).next_multiple_of(4);
+        }
+        assert_eq!(offset, cpio.len());
+        entries
+    }
+
+    #[test]
+    fn rpm_versions() {
+        for (version, rpm) in [
+            ("1.2.3", "1.2.3"),
+            ("1.2.3-alpha.1", "1.2.3~alpha.1"),
+            ("1.2.3-pre-release", "1.2.3~pre_release"),
+            ("1.2.3+build.5", "1.2.3+build.5"),
+        ] {
+            assert_eq!(
+                rpm_version(&Version::parse(version).unwrap()),
+                (rpm.to_owned(), "1".to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn header_bytes() {
+        let bytes = Header::new()
+            .int16(RPMTAG_FILEMODES, &[0o100644])
+            .string(RPMTAG_NAME, "a")
+            .int32(RPMTAG_SIZE, &[7])
+            .to_bytes(RPMTAG_HEADERIMMUTABLE);
+
+        let mut expected = vec![0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+        // 3 entries plus the region, 26 bytes of data
+        expected.extend(be32(&[4, 26]));
+        expected.extend(be32(&[RPMTAG_HEADERIMMUTABLE, RPM_BIN_TYPE, 10, 16]));
+        // sorted by tag, with integers aligned to their size
+        expected.extend(be32(&[RPMTAG_NAME, RPM_STRING_TYPE, 0, 1]));
+        expected.extend(be32(&[RPMTAG_SIZE, RPM_INT32_TYPE, 4, 1]));
+        expected.extend(be32(&[RPMTAG_FILEMODES, RPM_INT16_TYPE, 8, 1]));
+        expected.extend(b"a\0\0\0");
+        expected.extend(be32(&[7]));
+        expected.extend(0o100644u16.to_be_bytes());
+        // the region trailer, binary data needs no alignment
+        expected.extend(be32(&[
+            RPMTAG_HEADERIMMUTABLE,
+            RPM_BIN_TYPE,
+            -64i32 as u32,
+            16,
+        ]));
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn cpio_archives() {
+        let cpio = create_cpio(
+            &[
+                RpmFile {
+                    path: "/usr/lib/sqlite3".to_owned(),
+                    mode: 0o040755,
+                    data: &[],
+                },
+                RpmFile {
+                    path: "/usr/lib/sqlite3/sample0.so".to_owned(),
+                    mode: 0o100644,
+                    data: b"loadable",
+                },
+            ],
+            1700000000,
+        );
+        assert!(cpio.starts_with(b"07070100000001000041ed"));
+        assert_eq!(
+            cpio_entries(&cpio),
+            vec![
+                (
+                    "./usr/lib/sqlite3".to_owned(),
+                    0o040755,
+                    2,
+                    1700000000,
+                    vec![]
+                ),
+                (
+                    "./usr/lib/sqlite3/sample0.so".to_owned(),
+                    0o100644,
+                    1,
+                    1700000000,
+                    b"loadable".to_vec()
+                ),
+                ("TRAILER!!!".to_owned(), 0, 1, 0, vec![]),
+            ]
+        );
+    }
+}
@@ -10,6 +10,23 @@ pub struct SpecPackage {
     pub homepage: String,
     pub repo: String,
     pub git_tag_format: Option<String>,
+    /// Init function of the loadable extension, when it can't be detected
+    /// from the exported symbols.
+    pub entrypoint: Option<String>,
+}
+
+// sqlite-sample -> SqliteSample
+pub(crate) fn camel_case(name: &str) -> String {
+    name.split(['-', '_'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
 }
 
 impl SpecPackage {
@@ -34,6 +51,9 @@ pub struct TargetDatasette {}
 #[derive(Deserialize)]
 pub struct TargetPip {
     pub(crate) extra_init_py: Option<String>,
+    /// Pyodide ABI used for the wheel built from the `pyodide` input directory,
+    /// ex "pyodide_2024_0". The platform tag is "$ABI_wasm32".
+    pub(crate) pyodide_abi: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -49,6 +69,128 @@ pub struct TargetGem {
 #[derive(Deserialize)]
 pub struct TargetAmalgamation {
     pub include: Vec<String>,
+    /// Merge the .c files into a single {name}.c and the headers into {name}.h,
+    /// instead of archiving them as they are.
+    #[serde(default)]
+    pub merge: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TargetXcframework {
+    /// Clang module name declared in the module map, defaults to the package
+    /// name in CamelCase.
+    pub(crate) module_name: Option<String>,
+}
+
+impl TargetXcframework {
+    pub(crate) fn module_name(&self, package_name: &str) -> String {
+        self.module_name
+            .clone()
+            .unwrap_or_else(|| camel_case(package_name))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TargetSwiftPackage {}
+
+#[derive(Deserialize)]
+pub struct TargetAndroid {
+    /// Maven groupId of the .aar, ex "io.github.asg017"
+    pub(crate) group_id: String,
+    /// Java package of the generated loader class, defaults to the groupId
+    /// followed by the package name without dashes.
+    pub(crate) java_package: Option<String>,
+    pub(crate) min_sdk_version: Option<u32>,
+}
+
+// io.github.asg017 + sqlite-sample -> io.github.asg017.sqlitesample
+fn default_java_package(group_id: &str, package_name: &str) -> String {
+    let name: String = package_name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("{group_id}.{name}")
+}
+
+impl TargetAndroid {
+    pub(crate) fn java_package(&self, package_name: &str) -> String {
+        self.java_package
+            .clone()
+            .unwrap_or_else(|| default_java_package(&self.group_id, package_name))
+    }
+    pub(crate) fn min_sdk_version(&self) -> u32 {
+        // Android 5.0, the oldest release with 64-bit ABIs
+        self.min_sdk_version.unwrap_or(21)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TargetMaven {
+    /// Maven groupId of the .jar, ex "io.github.asg017"
+    pub(crate) group_id: String,
+    /// Java package of the generated loader class, defaults to the groupId
+    /// followed by the package name without dashes.
+    pub(crate) java_package: Option<String>,
+}
+
+impl TargetMaven {
+    pub(crate) fn java_package(&self, package_name: &str) -> String {
+        self.java_package
+            .clone()
+            .unwrap_or_else(|| default_java_package(&self.group_id, package_name))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TargetNuget {
+    /// C# namespace of the generated helper class, defaults to the package
+    /// name in CamelCase.
+    pub(crate) namespace: Option<String>,
+}
+
+impl TargetNuget {
+    pub(crate) fn namespace(&self, package_name: &str) -> String {
+        self.namespace
+            .clone()
+            .unwrap_or_else(|| camel_case(package_name))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TargetGo {
+    /// Go module path, ex "github.com/asg017/sqlite-sample/bindings/go"
+    pub(crate) module: String,
+}
+
+#[derive(Deserialize)]
+pub struct TargetCargo {
+    /// Ship the static release tarballs inside the crate instead of
+    /// downloading them in build.rs.
+    #[serde(default)]
+    pub(crate) vendored: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TargetHomebrew {}
+
+#[derive(Deserialize)]
+pub struct TargetNix {}
+
+#[derive(Deserialize)]
+pub struct TargetDeb {}
+
+#[derive(Deserialize)]
+pub struct TargetRpm {}
+
+#[derive(Deserialize)]
+pub struct TargetApk {
+    /// abuild RSA private key to sign the packages and APKINDEX with, relative
+    /// to the spec file, ex "keys/me-5f0a1b2c.rsa"
+    pub(crate) private_key: Option<String>,
+    /// Name of the public key in /etc/apk/keys/, defaults to the private
+    /// key's file name + ".pub"
+    pub(crate) key_name: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -62,9 +204,85 @@ pub struct Targets {
     pub npm: Option<TargetNpm>,
     pub gem: Option<TargetGem>,
     pub amalgamation: Option<TargetAmalgamation>,
+    pub xcframework: Option<TargetXcframework>,
+    pub swift_package: Option<TargetSwiftPackage>,
+    pub android: Option<TargetAndroid>,
+    pub maven: Option<TargetMaven>,
+    pub nuget: Option<TargetNuget>,
+    pub go: Option<TargetGo>,
+    pub cargo: Option<TargetCargo>,
+    pub homebrew: Option<TargetHomebrew>,
+    pub nix: Option<TargetNix>,
+    pub deb: Option<TargetDeb>,
+    pub rpm: Option<TargetRpm>,
+    pub apk: Option<TargetApk>,
 }
 #[derive(Deserialize)]
 pub struct Spec {
     pub package: SpecPackage,
     pub targets: Targets,
 }
+
+impl Spec {
+    /// Targets that are built on top of another target, which must also be enabled.
+    pub(crate) fn dependency_errors(&self) -> Vec<String> {
+        let targets = &self.targets;
+        let dependencies = [
+            (
+                targets.sqlpkg.is_some(),
+                targets.github_releases.is_some(),
+                "sqlpkg target requires the github_releases target",
+            ),
+            (
+                targets.spm.is_some(),
+                targets.github_releases.is_some(),
+                "spm target requires the github_releases target",
+            ),
+            (
+                targets.amalgamation.is_some(),
+                targets.github_releases.is_some(),
+                "amalgamation target requires the github_releases target",
+            ),
+            (
+                targets.datasette.is_some(),
+                targets.pip.is_some(),
+                "datasette target requires the pip target",
+            ),
+            (
+                targets.sqlite_utils.is_some(),
+                targets.pip.is_some(),
+                "sqlite_utils target requires the pip target",
+            ),
+            (
+                targets.swift_package.is_some(),
+                targets.xcframework.is_some(),
+                "swift_package target requires the xcframework target",
+            ),
+            (
+                targets.swift_package.is_some(),
+                targets.github_releases.is_some(),
+                "swift_package target requires the github_releases target",
+            ),
+            (
+                targets.cargo.is_some(),
+                targets.github_releases.is_some(),
+                "cargo target requires the github_releases target",
+            ),
+            (
+                targets.homebrew.is_some(),
+                targets.github_releases.is_some(),
+                "homebrew target requires the github_releases target",
+            ),
+            (
+                targets.nix.is_some(),
+                targets.github_releases.is_some(),
+                "nix target requires the github_releases target",
+            ),
+        ];
+        dependencies
+            .into_iter()
+            .filter(|(enabled, dependency_enabled, _)| *enabled && !dependency_enabled)
+            .map(|(_, _, message)| message.to_owned())
+            .collect()
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::io::Result;
 use std::path::Path;
 
@@ -10,8 +10,8 @@ use serde::{Deserialize, Serialize};
 pub struct Asset {
     //path:
     pub pattern: Option<String>,
-    pub files: HashMap<String, String>,
-    pub checksums: HashMap<String, String>,
+    pub files: BTreeMap<String, String>,
+    pub checksums: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -43,8 +43,8 @@ pub(crate) fn write_sqlpkg(project: &Project, sqlpkg_dir: &Path) -> Result<Vec<G
         symbols: None,
         assets: Asset {
             pattern: None,
-            files: HashMap::new(),
-            checksums: HashMap::new(),
+            files: BTreeMap::new(),
+            checksums: BTreeMap::new(),
         },
     };
     let asset = GeneratedAsset::from(
@@ -0,0 +1,128 @@
+use std::io;
+use std::path::Path;
+
+use crate::{binary, GeneratedAsset, GeneratedAssetKind, Os, Project};
+
+/// Name of the C init function in the Apple static libraries, which SwiftPM
+/// links directly since iOS apps can't load extensions at runtime.
+fn init_function(project: &Project) -> String {
+    if let Some(entrypoint) = &project.spec.package.entrypoint {
+        return entrypoint.clone();
+    }
+    project
+        .platform_directories
+        .iter()
+        .filter(|platform_dir| matches!(platform_dir.os, Os::Ios | Os::IosSimulator | Os::Macos))
+        .find_map(|platform_dir| platform_dir.static_files.first())
+        .map_or_else(
+            || binary::default_init_symbols(&project.spec.package.name)[1].clone(),
+            |file| binary::init_function(&file.name, &binary::init_symbols(&file.data)),
+        )
+}
+
+/// Writes a Package.swift with a `binaryTarget` for the released XCFramework zip,
+/// and a `{module}Loader` target that registers the extension on a connection.
+pub(crate) fn write_swift_package(
+    project: &Project,
+    swift_package_path: &Path,
+    module_name: &str,
+    xcframework_asset: &GeneratedAsset,
+) -> io::Result<Vec<GeneratedAsset>> {
+    let loader_name = format!("{module_name}Loader");
+    let sources_path = swift_package_path.join("Sources").join(&loader_name);
+    std::fs::create_dir_all(&sources_path)?;
+
+    let package_swift = templates::package_swift(
+        module_name,
+        &loader_name,
+        &project.release_download_url(&xcframework_asset.name),
+        &xcframework_asset.checksum_sha256,
+    );
+    let loader_swift = templates::loader_swift(
+        &project.spec.package.name,
+        module_name,
+        &init_function(project),
+    );
+    Ok(vec![
+        GeneratedAsset::from(
+            GeneratedAssetKind::SwiftPackage,
+            &swift_package_path.join("Package.swift"),
+            package_swift.as_bytes(),
+        )?,
+        GeneratedAsset::from(
+            GeneratedAssetKind::SwiftPackage,
+            &sources_path.join(format!("{loader_name}.swift")),
+            loader_swift.as_bytes(),
+        )?,
+    ])
+}
+
+mod templates {
+    pub(crate) fn package_swift(
+        module_name: &str,
+        loader_name: &str,
+        url: &str,
+        checksum: &str,
+    ) -> String {
+        // the binaryTarget name must match the .xcframework directory in the zip
+        format!(
+            r#"// swift-tools-version:5.7
+import PackageDescription
+
+let package = Package(
+    name: "{module_name}",
+    products: [
+        .library(name: "{module_name}", targets: ["{loader_name}"]),
+    ],
+    targets: [
+        .binaryTarget(
+            name: "{module_name}",
+            url: "{url}",
+            checksum: "{checksum}"
+        ),
+        .target(
+            name: "{loader_name}",
+            dependencies: ["{module_name}"],
+            linkerSettings: [.linkedLibrary("sqlite3")]
+        ),
+    ]
+)
+"#
+        )
+    }
+
+    pub(crate) fn loader_swift(
+        package_name: &str,
+        module_name: &str,
+        init_function: &str,
+    ) -> String {
+        format!(
+            r#"@_exported import {module_name}
+import SQLite3
+
+public struct {module_name}Error: Error, CustomStringConvertible {{
+    public let code: Int32
+    public let message: String
+
+    public var description: String {{
+        "{package_name} failed to load (\(code)): \(message)"
+    }}
+}}
+
+/// Registers {package_name} on an open SQLite connection, ex GRDB's
+/// `db.sqliteConnection` or SQLite.swift's `connection.handle`.
+public func load(into db: OpaquePointer) throws {{
+    var errorMessage: UnsafeMutablePointer<CChar>? = nil
+    defer {{ sqlite3_free(errorMessage) }}
+    let rc = {init_function}(db, &errorMessage, nil)
+    if rc != SQLITE_OK {{
+        throw {module_name}Error(
+            code: rc,
+            message: errorMessage.map {{ String(cString: $0) }} ?? String(cString: sqlite3_errstr(rc))
+        )
+    }}
+}}
+"#
+        )
+    }
+}
@@ -0,0 +1,213 @@
+use object::macho::{CPU_TYPE_ARM64, FAT_MAGIC, MH_MAGIC, MH_MAGIC_64};
+use object::read::archive::ArchiveFile;
+use object::FileKind;
+use thiserror::Error;
+
+use crate::{Cpu, LoadablePlatformFile, Os, PlatformDirectory, PlatformFile};
+
+#[derive(Error, Debug)]
+pub enum UniversalError {
+    #[error("{0} is not a single-architecture Mach-O binary or archive")]
+    NotThinMachO(String),
+    #[error("{0} is too large for a universal binary")]
+    TooLarge(String),
+}
+
+/// cputype and cpusubtype of a thin Mach-O, or of the first Mach-O member of a
+/// static archive. Only little-endian headers are read, as x86_64 and arm64 are.
+fn macho_cpu_type(data: &[u8]) -> Option<(u32, u32)> {
+    if FileKind::parse(data).ok()? == FileKind::Archive {
+        let archive = ArchiveFile::parse(data).ok()?;
+        return archive
+            .members()
+            .filter_map(|member| member.ok()?.data(data).ok())
+            .find_map(macho_cpu_type);
+    }
+    let read_u32 = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    match read_u32(0)? {
+        MH_MAGIC | MH_MAGIC_64 => Some((read_u32(4)?, read_u32(8)?)),
+        _ => None,
+    }
+}
+
+/// Combines thin Mach-O binaries or static archives into one fat binary, like
+/// `lipo -create`. Each slice is page aligned, 16K for arm64 and 4K otherwise.
+pub(crate) fn fat_macho(name: &str, slices: &[&[u8]]) -> Result<Vec<u8>, UniversalError> {
+    let too_large = || UniversalError::TooLarge(name.to_owned());
+    let mut arches = vec![];
+    let mut offset = 8 + 20 * slices.len();
+    for slice in slices {
+        let (cputype, cpusubtype) =
+            macho_cpu_type(slice).ok_or_else(|| UniversalError::NotThinMachO(name.to_owned()))?;
+        let align: u32 = if cputype == CPU_TYPE_ARM64 { 14 } else { 12 };
+        offset = offset.next_multiple_of(1 << align);
+        arches.push((cputype, cpusubtype, offset, slice.len(), align));
+        offset += slice.len();
+    }
+
+    let mut fat = Vec::with_capacity(offset);
+    fat.extend(FAT_MAGIC.to_be_bytes());
+    fat.extend((slices.len() as u32).to_be_bytes());
+    for (cputype, cpusubtype, offset, size, align) in &arches {
+        fat.extend(cputype.to_be_bytes());
+        fat.extend(cpusubtype.to_be_bytes());
+        fat.extend(
+            u32::try_from(*offset)
+                .map_err(|_| too_large())?
+                .to_be_bytes(),
+        );
+        fat.extend(u32::try_from(*size).map_err(|_| too_large())?.to_be_bytes());
+        fat.extend(align.to_be_bytes());
+    }
+    for ((_, _, offset, _, _), slice) in arches.iter().zip(slices) {
+        fat.resize(*offset, 0);
+        fat.extend_from_slice(slice);
+    }
+    Ok(fat)
+}
+
+fn fat_platform_file(
+    x86_64: &PlatformFile,
+    aarch64: &PlatformFile,
+) -> Result<PlatformFile, UniversalError> {
+    Ok(PlatformFile::new(
+        x86_64.name.clone(),
+        fat_macho(&x86_64.name, &[&x86_64.data, &aarch64.data])?,
+        None,
+    ))
+}
+
+/// A `macos-universal` platform directory with a fat Mach-O for every loadable
+/// and static library found under the same name in both `macos-x86_64` and
+/// `macos-aarch64`. None when either directory is missing or nothing pairs up.
+pub(crate) fn macos_universal(
+    platform_directories: &[PlatformDirectory],
+) -> Result<Option<PlatformDirectory>, UniversalError> {
+    let find = |cpu: Cpu| {
+        platform_directories
+            .iter()
+            .find(|platform_dir| platform_dir.os == Os::Macos && platform_dir.cpu == cpu)
+    };
+    let (Some(x86_64), Some(aarch64)) = (find(Cpu::X86_64), find(Cpu::Aarch64)) else {
+        return Ok(None);
+    };
+
+    let mut loadable_files = vec![];
+    for loadable in &x86_64.loadable_files {
+        let Some(other) = aarch64
+            .loadable_files
+            .iter()
+            .find(|other| other.file.name == loadable.file.name)
+        else {
+            println!(
+                "Warning: {} is missing from macos-aarch64, leaving it out of macos-universal",
+                loadable.file.name
+            );
+            continue;
+        };
+        loadable_files.push(LoadablePlatformFile::new(
+            loadable.file_stem.clone(),
+            fat_platform_file(&loadable.file, &other.file)?,
+        ));
+    }
+    if loadable_files.is_empty() {
+        return Ok(None);
+    }
+
+    let mut static_files = vec![];
+    for file in &x86_64.static_files {
+        if let Some(other) = aarch64.static_files.iter().find(|o| o.name == file.name) {
+            static_files.push(fat_platform_file(file, other)?);
+        }
+    }
+
+    Ok(Some(PlatformDirectory {
+        os: Os::Macos,
+        cpu: Cpu::Universal,
+        path: aarch64.path.with_file_name("macos-universal"),
+        loadable_files,
+        static_files,
+        header_files: aarch64.header_files.clone(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary;
+    use crate::binary::tests::{
+        elf_object, macho_dylib, CPU_TYPE_ARM64, CPU_TYPE_X86_64, EM_X86_64,
+    };
+    use crate::tests::platform_directory;
+    use object::read::macho::{FatArch, MachOFatFile32};
+
+    #[test]
+    fn fat_macho_slices() {
+        let x86_64 = macho_dylib(CPU_TYPE_X86_64, Some((10, 13)));
+        let aarch64 = macho_dylib(CPU_TYPE_ARM64, Some((11, 0)));
+        let fat = fat_macho("sample0.dylib", &[&x86_64, &aarch64]).unwrap();
+
+        let arches: Vec<_> = MachOFatFile32::parse(fat.as_slice())
+            .unwrap()
+            .arches()
+            .iter()
+            .map(|arch| {
+                (
+                    arch.cputype(),
+                    arch.offset(),
+                    arch.align(),
+                    arch.data(fat.as_slice()).unwrap().to_vec(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            arches,
+            vec![
+                (CPU_TYPE_X86_64, 4096, 12, x86_64),
+                (CPU_TYPE_ARM64, 16384, 14, aarch64),
+            ]
+        );
+        let info = binary::inspect(&fat).unwrap().unwrap();
+        assert!(info.fat);
+        assert!(info.matches(&Os::Macos, &Cpu::X86_64));
+        assert!(info.matches(&Os::Macos, &Cpu::Aarch64));
+        // each slice only has to run on its own CPU
+        assert_eq!(binary::macos_version(&fat), Some((10, 13)));
+    }
+
+    #[test]
+    fn fat_macho_rejects_other_binaries() {
+        let x86_64 = macho_dylib(CPU_TYPE_X86_64, None);
+        assert!(matches!(
+            fat_macho("sample0.so", &[&x86_64, &elf_object(EM_X86_64)]),
+            Err(UniversalError::NotThinMachO(name)) if name == "sample0.so"
+        ));
+        let fat = fat_macho("sample0.dylib", &[&x86_64]).unwrap();
+        assert!(matches!(
+            fat_macho("sample0.dylib", &[&fat]),
+            Err(UniversalError::NotThinMachO(_))
+        ));
+    }
+
+    #[test]
+    fn macos_universal_pairs_both_macos_directories() {
+        let x86_64 = platform_directory(Os::Macos, Cpu::X86_64, macho_dylib(CPU_TYPE_X86_64, None));
+        let aarch64 =
+            platform_directory(Os::Macos, Cpu::Aarch64, macho_dylib(CPU_TYPE_ARM64, None));
+        let linux = platform_directory(Os::Linux, Cpu::X86_64, elf_object(EM_X86_64));
+
+        let universal = macos_universal(&[x86_64.clone(), linux.clone(), aarch64])
+            .unwrap()
+            .unwrap();
+        assert_eq!(universal.cpu, Cpu::Universal);
+        assert_eq!(universal.path, std::path::Path::new("macos-universal"));
+        assert_eq!(universal.loadable_files.len(), 1);
+        assert_eq!(universal.loadable_files[0].file.name, "sample0.dylib");
+
+        assert!(macos_universal(&[x86_64, linux]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,365 @@
+use std::fs;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::spec::Spec;
+use crate::{universal, Cpu, InputDirectory, Os, PlatformDirectory};
+
+#[derive(Error, Debug)]
+pub enum ValidateError {
+    #[error("could not read spec file {0}: {1}")]
+    UnreadableSpec(String, std::io::Error),
+    #[error("invalid spec file: {0}")]
+    InvalidSpec(#[from] toml::de::Error),
+    #[error("found {0} problem(s)")]
+    Problems(usize),
+}
+
+fn check_spec_file(problems: &mut Vec<String>, spec_directory: &Path, target: &str, file: &str) {
+    if !spec_directory.join(file).is_file() {
+        problems.push(format!(
+            "{target} target references '{file}', which does not exist"
+        ));
+    }
+}
+
+fn is_desktop_platform(platform_dir: &PlatformDirectory) -> bool {
+    matches!(
+        platform_dir.os,
+        Os::Linux | Os::LinuxMusl | Os::Macos | Os::Windows
+    ) && matches!(platform_dir.cpu, Cpu::X86_64 | Cpu::Aarch64)
+}
+
+/// Problems with a spec and input directory that would fail or silently break
+/// a build. Every problem is collected rather than stopping at the first one.
+fn spec_problems(spec: &Spec, spec_directory: &Path, input_dir: &Path) -> Vec<String> {
+    let mut problems = spec.dependency_errors();
+
+    if let Some(extra_init_py) = spec
+        .targets
+        .pip
+        .as_ref()
+        .and_then(|pip| pip.extra_init_py.as_deref())
+    {
+        check_spec_file(&mut problems, spec_directory, "pip", extra_init_py);
+    }
+    if let Some(amalgamation) = &spec.targets.amalgamation {
+        for include in &amalgamation.include {
+            check_spec_file(&mut problems, spec_directory, "amalgamation", include);
+        }
+    }
+    if let Some(private_key) = spec
+        .targets
+        .apk
+        .as_ref()
+        .and_then(|apk| apk.private_key.as_deref())
+    {
+        check_spec_file(&mut problems, spec_directory, "apk", private_key);
+    }
+    if spec.targets.npm.is_some() && spec.package.authors.is_empty() {
+        problems.push("npm target requires at least one entry in package.authors".to_owned());
+    }
+
+    let input = match InputDirectory::read(input_dir) {
+        Ok(input) => input,
+        Err(err) => {
+            problems.push(format!(
+                "could not read input directory {}: {err}",
+                input_dir.display()
+            ));
+            return problems;
+        }
+    };
+
+    let mut platform_directories = vec![];
+    for entry in &input.platform_entries {
+        match PlatformDirectory::from_path(entry.to_owned()) {
+            Ok(platform_dir) => platform_directories.push(platform_dir),
+            Err(err) => problems.push(format!("{}: {err}", entry.display())),
+        }
+    }
+
+    for platform_dir in &platform_directories {
+        // iOS apps can't load extensions at runtime, so static libraries are enough there
+        let static_only = matches!(platform_dir.os, Os::Ios | Os::IosSimulator)
+            && !platform_dir.static_files.is_empty();
+        if platform_dir.loadable_files.is_empty() && !static_only {
+            problems.push(format!(
+                "{}: no loadable (.so, .dylib, .dll) files found",
+                platform_dir.path.display()
+            ));
+        }
+        for loadable in &platform_dir.loadable_files {
+            let symbols = &loadable.init_symbols;
+            let file = platform_dir.path.join(&loadable.file.name);
+            if let Some(entrypoint) = spec.package.entrypoint.as_deref() {
+                if !symbols.is_empty() && !symbols.iter().any(|s| s == entrypoint) {
+                    problems.push(format!(
+                        "{}: package.entrypoint '{entrypoint}' is not exported, found {}",
+                        file.display(),
+                        symbols.join(", ")
+                    ));
+                }
+            } else if symbols.len() > 1 && loadable.entrypoint(None).is_some() {
+                problems.push(format!(
+                    "{}: exports several init functions ({}), set package.entrypoint",
+                    file.display(),
+                    symbols.join(", ")
+                ));
+            }
+        }
+    }
+
+    // the same fat binaries the build writes to macos-universal
+    if let Err(err) = universal::macos_universal(&platform_directories) {
+        problems.push(format!("macos-universal: {err}"));
+    }
+
+    if spec.targets.xcframework.is_some()
+        && !platform_directories.iter().any(|platform_dir| {
+            matches!(platform_dir.os, Os::Ios | Os::IosSimulator | Os::Macos)
+                && matches!(platform_dir.cpu, Cpu::X86_64 | Cpu::Aarch64)
+                && !platform_dir.static_files.is_empty()
+        })
+    {
+        problems.push(
+            "xcframework target requires an ios, iossimulator, or macos platform directory with a static library"
+                .to_owned(),
+        );
+    }
+    if spec.targets.xcframework.is_some() {
+        for platform_dir in &platform_directories {
+            if matches!(platform_dir.os, Os::Ios | Os::IosSimulator | Os::Macos)
+                && platform_dir.static_files.len() > 1
+            {
+                problems.push(format!(
+                    "{}: xcframework target takes one static library per platform directory",
+                    platform_dir.path.display()
+                ));
+            }
+        }
+    }
+
+    if spec.targets.android.is_some()
+        && !platform_directories.iter().any(|platform_dir| {
+            platform_dir.os == Os::Android && !platform_dir.loadable_files.is_empty()
+        })
+    {
+        problems.push(
+            "android target requires an android platform directory with a loadable library"
+                .to_owned(),
+        );
+    }
+
+    // Swift imports the init function through the module map built from the headers
+    if spec.targets.swift_package.is_some()
+        && platform_directories.iter().any(|platform_dir| {
+            matches!(platform_dir.os, Os::Ios | Os::IosSimulator | Os::Macos)
+                && !platform_dir.static_files.is_empty()
+                && platform_dir.header_files.is_empty()
+        })
+    {
+        problems.push(
+            "swift_package target requires a header (.h) declaring the init function next to every ios, iossimulator, and macos static library"
+                .to_owned(),
+        );
+    }
+
+    if spec.targets.cargo.is_some()
+        && platform_directories
+            .iter()
+            .all(|platform_dir| platform_dir.static_files.is_empty())
+    {
+        problems.push(
+            "cargo target requires at least one platform directory with a static library"
+                .to_owned(),
+        );
+    }
+
+    let has_macos_or_linux_loadable = platform_directories.iter().any(|platform_dir| {
+        matches!(platform_dir.os, Os::Macos | Os::Linux) && !platform_dir.loadable_files.is_empty()
+    });
+    for (target, enabled) in [
+        ("homebrew", spec.targets.homebrew.is_some()),
+        ("nix", spec.targets.nix.is_some()),
+    ] {
+        if enabled && !has_macos_or_linux_loadable {
+            problems.push(format!(
+                "{target} target requires a macos or linux platform directory with a loadable library"
+            ));
+        }
+    }
+
+    let has_linux_platform = platform_directories
+        .iter()
+        .any(|platform_dir| platform_dir.os == Os::Linux);
+    for (target, enabled) in [
+        ("deb", spec.targets.deb.is_some()),
+        ("rpm", spec.targets.rpm.is_some()),
+    ] {
+        if enabled && !has_linux_platform {
+            problems.push(format!(
+                "{target} target requires a linux platform directory"
+            ));
+        }
+    }
+
+    if spec.targets.apk.is_some()
+        && !platform_directories.iter().any(|platform_dir| {
+            platform_dir.os == Os::LinuxMusl
+                && matches!(platform_dir.cpu, Cpu::X86_64 | Cpu::Aarch64)
+        })
+    {
+        problems.push(
+            "apk target requires a linux-x86_64-musl or linux-aarch64-musl platform directory"
+                .to_owned(),
+        );
+    }
+
+    let has_desktop_platform = platform_directories.iter().any(is_desktop_platform);
+    for (target, enabled) in [
+        ("maven", spec.targets.maven.is_some()),
+        ("nuget", spec.targets.nuget.is_some()),
+        ("go", spec.targets.go.is_some()),
+    ] {
+        if enabled && !has_desktop_platform {
+            problems.push(format!(
+                "{target} target requires at least one linux, macos, or windows x86_64/aarch64 platform directory"
+            ));
+        }
+    }
+    if spec.targets.npm.is_some() && !has_desktop_platform {
+        problems.push(
+            "npm target requires at least one linux, macos, or windows x86_64/aarch64 platform directory"
+                .to_owned(),
+        );
+    }
+
+    if let Some(emscripten_dir) = &input.emscripten_dir {
+        if spec.targets.npm.is_some() {
+            for file in ["sqlite3.mjs", "sqlite3.wasm"] {
+                if !emscripten_dir.join(file).is_file() {
+                    problems.push(format!("{}: missing {file}", emscripten_dir.display()));
+                }
+            }
+        }
+    }
+
+    if let Some(pyodide_dir) = &input.pyodide_dir {
+        if spec.targets.pip.is_some() {
+            let has_loadable = fs::read_dir(pyodide_dir)
+                .map(|entries| {
+                    entries.filter_map(Result::ok).any(|entry| {
+                        entry.path().extension().and_then(|e| e.to_str()) == Some("so")
+                    })
+                })
+                .unwrap_or(false);
+            if !has_loadable {
+                problems.push(format!(
+                    "{}: no .so loadable file found",
+                    pyodide_dir.display()
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+pub(crate) fn validate(input_file: &Path, input_dir: &Path) -> Result<(), ValidateError> {
+    let contents = fs::read_to_string(input_file)
+        .map_err(|err| ValidateError::UnreadableSpec(input_file.display().to_string(), err))?;
+    let spec: Spec = toml::from_str(&contents)?;
+    let spec_directory = input_file.parent().unwrap_or(Path::new("."));
+
+    let problems = spec_problems(&spec, spec_directory, input_dir);
+    if problems.is_empty() {
+        println!("✅ {} is valid", input_file.display());
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("✗ {problem}");
+    }
+    Err(ValidateError::Problems(problems.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::tests::{elf_object, EM_X86_64};
+    use tempdir::TempDir;
+
+    fn directory(files: &[(&str, &[u8])]) -> TempDir {
+        let dir = TempDir::new("sqlite-dist-validate").unwrap();
+        for (path, contents) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn problems(spec: &str, input: &TempDir) -> Vec<String> {
+        let spec_directory = directory(&[("extra_init.py", b"")]);
+        let spec: Spec = toml::from_str(spec).unwrap();
+        spec_problems(&spec, spec_directory.path(), input.path())
+    }
+
+    const PACKAGE: &str = r#"
+        [package]
+        name = "sqlite-sample"
+        license = "MIT"
+        homepage = "https://example.com"
+        repo = "https://github.com/asg017/sqlite-sample"
+        description = "A sample SQLite extension"
+        authors = ["Alex Garcia"]
+    "#;
+
+    #[test]
+    fn valid_spec() {
+        let input = directory(&[("linux-x86_64/sample0.so", &elf_object(EM_X86_64))]);
+        let spec = format!(
+            "{PACKAGE}\n[targets]\npip = {{ extra_init_py = \"extra_init.py\" }}\ndeb = {{}}\n"
+        );
+        assert_eq!(problems(&spec, &input), Vec::<String>::new());
+    }
+
+    #[test]
+    fn collects_every_problem() {
+        let input = directory(&[
+            ("linux-x86_64/sample0.so", &elf_object(EM_X86_64)),
+            ("linux-x86_64-musl/README.md", b""),
+            ("solaris-x86_64/sample0.so", b""),
+        ]);
+        let spec = format!(
+            "{PACKAGE}\n[targets]\npip = {{ extra_init_py = \"missing.py\" }}\napk = {{}}\nandroid = {{ group_id = \"io.github.asg017\" }}\n"
+        );
+        let input_path = input.path().display();
+        assert_eq!(
+            problems(&spec, &input),
+            vec![
+                "pip target references 'missing.py', which does not exist".to_owned(),
+                format!("{input_path}/solaris-x86_64: Invalid operating system 'solaris'. Must be one of 'macos', 'linux', 'windows', 'android', 'ios', or 'iossimulator', with a '-musl' suffix for linux musl builds"),
+                format!(
+                    "{input_path}/linux-x86_64-musl: no loadable (.so, .dylib, .dll) files found"
+                ),
+                "android target requires an android platform directory with a loadable library"
+                    .to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn unreadable_input_directory() {
+        let input = directory(&[]);
+        let missing = input.path().join("missing");
+        let spec: Spec = toml::from_str(&format!("{PACKAGE}\n[targets]\n")).unwrap();
+        let problems = spec_problems(&spec, input.path(), &missing);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with(&format!(
+            "could not read input directory {}: ",
+            missing.display()
+        )));
+    }
+}
@@ -0,0 +1,233 @@
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+use zip::result::ZipError;
+
+use crate::spec::TargetXcframework;
+use crate::universal::{fat_macho, UniversalError};
+use crate::{
+    create_zip, Cpu, GeneratedAsset, GeneratedAssetKind, Os, PlatformDirectory, PlatformFile,
+    Project,
+};
+
+#[derive(Error, Debug)]
+pub enum XcframeworkError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Zipfile error: {0}")]
+    ZipError(#[from] ZipError),
+    #[error("{0}")]
+    UniversalError(#[from] UniversalError),
+    #[error("no ios, iossimulator or macos platform directory has a static library")]
+    NoLibraries,
+    #[error("{0} has more than one static library, an XCFramework takes one per platform")]
+    MultipleStaticLibraries(String),
+}
+
+/// One entry of the XCFramework's AvailableLibraries, a static library for one
+/// Apple platform with every architecture it was built for.
+struct Library<'a> {
+    identifier: String,
+    platform: &'static str,
+    variant: Option<&'static str>,
+    architectures: Vec<&'static str>,
+    library: PlatformFile,
+    headers: &'a [PlatformFile],
+}
+
+fn apple_architecture(cpu: &Cpu) -> Option<&'static str> {
+    match cpu {
+        Cpu::Aarch64 => Some("arm64"),
+        Cpu::X86_64 => Some("x86_64"),
+        _ => None,
+    }
+}
+
+fn library<'a>(
+    platform_directories: &'a [PlatformDirectory],
+    os: Os,
+    platform: &'static str,
+    variant: Option<&'static str>,
+) -> Result<Option<Library<'a>>, XcframeworkError> {
+    let mut slices: Vec<(&'static str, &PlatformDirectory)> = platform_directories
+        .iter()
+        .filter(|platform_dir| platform_dir.os == os && !platform_dir.static_files.is_empty())
+        .filter_map(|platform_dir| Some((apple_architecture(&platform_dir.cpu)?, platform_dir)))
+        .collect();
+    // Xcode lists architectures alphabetically, ex ios-arm64_x86_64-simulator
+    slices.sort_by_key(|(architecture, _)| *architecture);
+    let Some((_, first)) = slices.first() else {
+        return Ok(None);
+    };
+    if let Some((_, platform_dir)) = slices
+        .iter()
+        .find(|(_, platform_dir)| platform_dir.static_files.len() > 1)
+    {
+        return Err(XcframeworkError::MultipleStaticLibraries(
+            platform_dir.path.display().to_string(),
+        ));
+    }
+
+    let name = first.static_files[0].name.clone();
+    let data = match slices.as_slice() {
+        [(_, platform_dir)] => platform_dir.static_files[0].data.clone(),
+        slices => fat_macho(
+            &name,
+            &slices
+                .iter()
+                .map(|(_, platform_dir)| platform_dir.static_files[0].data.as_slice())
+                .collect::<Vec<&[u8]>>(),
+        )?,
+    };
+    let architectures: Vec<&'static str> = slices.iter().map(|(a, _)| *a).collect();
+    let identifier = match variant {
+        Some(variant) => format!("{platform}-{}-{variant}", architectures.join("_")),
+        None => format!("{platform}-{}", architectures.join("_")),
+    };
+    Ok(Some(Library {
+        identifier,
+        platform,
+        variant,
+        architectures,
+        library: PlatformFile::new(name, data, None),
+        headers: &first.header_files,
+    }))
+}
+
+pub(crate) fn write_xcframework(
+    project: &Project,
+    xcframework_path: &Path,
+    xcframework_config: &TargetXcframework,
+) -> Result<Vec<GeneratedAsset>, XcframeworkError> {
+    let module_name = xcframework_config.module_name(&project.spec.package.name);
+    let platforms = [
+        (Os::Ios, "ios", None),
+        (Os::IosSimulator, "ios", Some("simulator")),
+        (Os::Macos, "macos", None),
+    ];
+    let mut libraries = vec![];
+    for (os, platform, variant) in platforms {
+        if let Some(library) = library(&project.platform_directories, os, platform, variant)? {
+            libraries.push(library);
+        }
+    }
+    if libraries.is_empty() {
+        return Err(XcframeworkError::NoLibraries);
+    }
+
+    let root = format!("{module_name}.xcframework");
+    let mut files = vec![PlatformFile::new(
+        format!("{root}/Info.plist"),
+        templates::info_plist(&libraries),
+        None,
+    )];
+    for library in &libraries {
+        let dir = format!("{root}/{}", library.identifier);
+        files.push(PlatformFile::new(
+            format!("{dir}/{}", library.library.name),
+            library.library.data.clone(),
+            None,
+        ));
+        for header in library.headers {
+            files.push(PlatformFile::new(
+                format!("{dir}/Headers/{}", header.name),
+                header.data.clone(),
+                None,
+            ));
+        }
+        if !library.headers.is_empty() {
+            files.push(PlatformFile::new(
+                format!("{dir}/Headers/module.modulemap"),
+                templates::module_map(&module_name, library.headers),
+                None,
+            ));
+        }
+    }
+
+    // the asset's sha256 is the checksum SwiftPM expects for a binaryTarget
+    Ok(vec![GeneratedAsset::from(
+        GeneratedAssetKind::Xcframework,
+        &xcframework_path.join(format!(
+            "{}-{}.xcframework.zip",
+            project.spec.package.name, project.version
+        )),
+        &create_zip(&files, project.source_date_epoch)?,
+    )?])
+}
+
+mod templates {
+    use super::Library;
+    use crate::PlatformFile;
+
+    pub(crate) fn info_plist(libraries: &[Library]) -> String {
+        let libraries: String = libraries
+            .iter()
+            .map(|library| {
+                let architectures: String = library
+                    .architectures
+                    .iter()
+                    .map(|a| format!("\n\t\t\t\t<string>{a}</string>"))
+                    .collect();
+                let headers = if library.headers.is_empty() {
+                    ""
+                } else {
+                    "\n\t\t\t<key>HeadersPath</key>\n\t\t\t<string>Headers</string>"
+                };
+                let variant = library.variant.map_or(String::new(), |variant| {
+                    format!(
+                        "\n\t\t\t<key>SupportedPlatformVariant</key>\n\t\t\t<string>{variant}</string>"
+                    )
+                });
+                format!(
+                    r#"
+		<dict>
+			<key>BinaryPath</key>
+			<string>{name}</string>{headers}
+			<key>LibraryIdentifier</key>
+			<string>{identifier}</string>
+			<key>LibraryPath</key>
+			<string>{name}</string>
+			<key>SupportedArchitectures</key>
+			<array>{architectures}
+			</array>
+			<key>SupportedPlatform</key>
+			<string>{platform}</string>{variant}
+		</dict>"#,
+                    name = library.library.name,
+                    identifier = library.identifier,
+                    platform = library.platform,
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AvailableLibraries</key>
+	<array>{libraries}
+	</array>
+	<key>CFBundlePackageType</key>
+	<string>XFWK</string>
+	<key>XCFrameworkFormatVersion</key>
+	<string>1.0</string>
+</dict>
+</plist>
+"#
+        )
+    }
+
+    pub(crate) fn module_map(module_name: &str, headers: &[PlatformFile]) -> String {
+        let headers: String = headers
+            .iter()
+            .map(|header| format!("    header \"{}\"\n", header.name))
+            .collect();
+        format!(
+            r#"module {module_name} {{
+{headers}    export *
+}}
+"#
+        )
+    }
+}